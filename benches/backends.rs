@@ -0,0 +1,33 @@
+//! Runs each script in `benches/scripts` on the stack VM and on the
+//! register VM. The instruction count of each is printed before timing;
+//! each script is compiled once and only running it is timed.
+
+#![allow(clippy::needless_return)]
+
+use std::fs;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use cherry_script::Engine;
+
+fn backends(c: &mut Criterion) {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/scripts");
+    let mut paths: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    let mut group = c.benchmark_group("backends");
+    group.sample_size(20);
+    let mut engine = Engine::new();
+    for path in paths {
+        let src = fs::read_to_string(&path).unwrap();
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let stack = engine.compile(&src).unwrap_or_else(|err| panic!("{}", err));
+        let registers = engine.compile_registers(&src).unwrap_or_else(|err| panic!("{}", err));
+        println!("{}: {} stack ops, {} register ops", name, stack.instruction_count(), registers.instruction_count());
+        group.bench_function(BenchmarkId::new("stack", &name), |b| b.iter(|| engine.run(&stack).unwrap()));
+        group.bench_function(BenchmarkId::new("register", &name), |b| b.iter(|| engine.run_registers(&registers).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
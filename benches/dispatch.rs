@@ -0,0 +1,28 @@
+//! Measures the interpreter loop on the scripts in `benches/scripts`. Each
+//! script is compiled once and only running it is timed.
+
+#![allow(clippy::needless_return)]
+
+use std::fs;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use cherry_script::Engine;
+
+fn dispatch(c: &mut Criterion) {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/scripts");
+    let mut paths: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    let mut group = c.benchmark_group("dispatch");
+    group.sample_size(20);
+    for path in paths {
+        let mut engine = Engine::new();
+        let program = engine.compile(&fs::read_to_string(&path).unwrap()).unwrap_or_else(|err| panic!("{}", err));
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        group.bench_function(name, |b| b.iter(|| engine.run(&program).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
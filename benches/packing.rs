@@ -0,0 +1,39 @@
+//! Moves ints and strings through the value stack and globals, to compare
+//! the cost of each value kind with and without the `nan-boxing` feature.
+//! Ints fit in a NaN box, while every push of a string allocates a box.
+
+#![allow(clippy::needless_return)]
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use cherry_script::Engine;
+
+/// Reads and compares a global `value` on every iteration.
+fn copy_loop(value: &str) -> String {
+    return format!(r#"
+        var value = {};
+        var copy = value;
+        var same = 0;
+        var i = 0;
+        while (i < 100000) {{
+            copy = value;
+            if (copy == value) {{ same = same + 1; }}
+            i = i + 1;
+        }}
+        same;
+    "#, value);
+}
+
+fn packing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("packing");
+    group.sample_size(20);
+    for (name, value) in [("ints", "42"), ("strings", "\"cherry\"")] {
+        let mut engine = Engine::new();
+        let program = engine.compile(&copy_loop(value)).unwrap_or_else(|err| panic!("{}", err));
+        group.bench_function(name, |b| b.iter(|| engine.run(&program).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, packing);
+criterion_main!(benches);
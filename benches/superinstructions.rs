@@ -0,0 +1,33 @@
+//! Runs each script in `benches/scripts` compiled with `-O1` and with `-O2`,
+//! showing what superinstructions save. Each script is compiled once and
+//! only running it is timed.
+
+#![allow(clippy::needless_return)]
+
+use std::fs;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use cherry_script::Engine;
+
+fn superinstructions(c: &mut Criterion) {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/scripts");
+    let mut paths: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    let mut group = c.benchmark_group("superinstructions");
+    group.sample_size(20);
+    for path in paths {
+        let src = fs::read_to_string(&path).unwrap();
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        for opt_level in [1, 2] {
+            let mut engine = Engine::new();
+            engine.set_opt_level(opt_level);
+            let program = engine.compile(&src).unwrap_or_else(|err| panic!("{}", err));
+            group.bench_function(BenchmarkId::new(format!("O{}", opt_level), &name), |b| b.iter(|| engine.run(&program).unwrap()));
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, superinstructions);
+criterion_main!(benches);
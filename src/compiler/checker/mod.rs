@@ -0,0 +1,528 @@
+use std::{collections::{HashMap, HashSet}, fmt};
+
+use crate::compiler::{
+    error::CompileError,
+    location::Location,
+    parser::ast::{ArmBody, BinaryOp, Expr, FunctionDef, LogicalOp, Pattern, Stmt, TypeAnn, UnaryOp}
+};
+
+/// The static type of a value. `Any` is the type of everything that is not
+/// annotated, and is compatible with every other type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Int,
+    BigInt,
+    Float,
+    Str,
+    Char,
+    Bool,
+    Nil,
+    List,
+    Map,
+    Function,
+    Named(String)
+}
+
+impl Type {
+    fn is_known(&self) -> bool {
+        return *self != Type::Any;
+    }
+
+    fn is_numeric(&self) -> bool {
+        return matches!(self, Type::Int | Type::BigInt | Type::Float);
+    }
+
+    /// Whether a value of type `found` may be stored where `self` is expected.
+    fn accepts(&self, found: &Type) -> bool {
+        return !self.is_known() || !found.is_known() || self == found;
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Int => write!(f, "int"),
+            Type::BigInt => write!(f, "bigint"),
+            Type::Float => write!(f, "float"),
+            Type::Str => write!(f, "str"),
+            Type::Char => write!(f, "char"),
+            Type::Bool => write!(f, "bool"),
+            Type::Nil => write!(f, "nil"),
+            Type::List => write!(f, "list"),
+            Type::Map => write!(f, "map"),
+            Type::Function => write!(f, "function"),
+            Type::Named(name) => write!(f, "{}", name)
+        }
+    }
+}
+
+/// The annotated signature of a named function.
+#[derive(Clone)]
+struct Signature {
+    params: Vec<Type>,
+    ret: Type
+}
+
+#[derive(Clone)]
+struct Binding {
+    ty: Type,
+    signature: Option<Signature>
+}
+
+/// Checks annotated code before bytecode is generated. Only values whose
+/// type is known are checked, so unannotated code stays dynamically typed.
+#[derive(Default)]
+pub struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    type_names: HashSet<String>,
+    classes: HashSet<String>,
+    enums: HashSet<String>,
+    return_types: Vec<Type>
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        let mut checker = Self { scopes: vec![HashMap::new()], ..Self::default() };
+        checker.type_names.insert("Result".to_string());
+        checker.enums.insert("Result".to_string());
+        return checker;
+    }
+
+    pub fn check(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        for stmt in stmts {
+            match stmt.unexported() {
+                Stmt::Struct(name, ..) => { self.type_names.insert(name.clone()); }
+                Stmt::Enum(name, ..) => {
+                    self.type_names.insert(name.clone());
+                    self.enums.insert(name.clone());
+                }
+                Stmt::Class(def) => {
+                    self.type_names.insert(def.name.clone());
+                    self.classes.insert(def.name.clone());
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts {
+            self.check_stmt(stmt)?;
+        }
+        return Ok(());
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::VarDef(name, ann, value, _, _) => {
+                let ty = self.resolve_type(ann.as_ref())?;
+                if let Some(value) = value {
+                    let found = self.check_expr(value)?;
+                    self.expect_type(&ty, &found, &value.location())?;
+                }
+                self.declare(name, ty, None);
+            }
+            Stmt::Print(expr, _) | Stmt::Expr(expr, _) => { self.check_expr(expr)?; }
+            Stmt::Assign(target, value, _) => {
+                let found = self.check_expr(value)?;
+                match target {
+                    Expr::Var(name, _) => {
+                        if let Some(binding) = self.lookup_mut(name) {
+                            binding.signature = None;
+                            let expected = binding.ty.clone();
+                            self.expect_type(&expected, &found, &value.location())?;
+                        }
+                    }
+                    target => { self.check_expr(target)?; }
+                }
+            }
+            Stmt::Block(stmts, _) => {
+                self.scopes.push(HashMap::new());
+                let result = stmts.iter().try_for_each(|stmt| self.check_stmt(stmt));
+                self.scopes.pop();
+                result?;
+            }
+            Stmt::If(cond, then_branch, else_branch, _) => {
+                self.check_condition(cond)?;
+                self.check_stmt(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.check_stmt(else_branch)?;
+                }
+            }
+            Stmt::While(cond, body, _) => {
+                self.check_condition(cond)?;
+                self.check_stmt(body)?;
+            }
+            Stmt::For(name, iterable, body, _) => {
+                self.check_expr(iterable)?;
+                self.scopes.push(HashMap::new());
+                self.declare(name, Type::Any, None);
+                let result = self.check_stmt(body);
+                self.scopes.pop();
+                result?;
+            }
+            Stmt::Fn(def) => {
+                let signature = self.signature(def)?;
+                self.declare(def.name.as_ref().unwrap(), Type::Function, signature);
+                self.check_function(def)?;
+            }
+            Stmt::Return(value, loc) => {
+                let found = match value {
+                    Some(value) => self.check_expr(value)?,
+                    None => Type::Nil
+                };
+                if let Some(expected) = self.return_types.last().cloned() {
+                    let loc = value.as_ref().map_or(loc.clone(), Expr::location);
+                    self.expect_type(&expected, &found, &loc)?;
+                }
+            }
+            Stmt::Class(def) => {
+                for method in &def.methods {
+                    self.check_function(method)?;
+                }
+            }
+            Stmt::Export(decl, _) => self.check_stmt(decl)?,
+            Stmt::Throw(value, _) => { self.check_expr(value)?; }
+            Stmt::Try(body, catch, finally, _) => {
+                self.check_stmt(body)?;
+                if let Some((name, handler)) = catch {
+                    self.scopes.push(HashMap::new());
+                    self.declare(name, Type::Any, None);
+                    let result = self.check_stmt(handler);
+                    self.scopes.pop();
+                    result?;
+                }
+                if let Some(finally) = finally {
+                    self.check_stmt(finally)?;
+                }
+            }
+            Stmt::Struct(..) | Stmt::Enum(..) | Stmt::Import(..) => {}
+        }
+        return Ok(());
+    }
+
+    fn check_function(&mut self, def: &FunctionDef) -> Result<(), CompileError> {
+        let ret = self.resolve_type(def.return_type.as_ref())?;
+        let mut scope = HashMap::new();
+        for (param, ann) in def.params.iter().zip(&def.param_types) {
+            let ty = self.resolve_type(ann.as_ref())?;
+            scope.insert(param.clone(), Binding { ty, signature: None });
+        }
+        self.scopes.push(scope);
+        self.return_types.push(ret.clone());
+        let result = def.body.iter().try_for_each(|stmt| self.check_stmt(stmt));
+        self.return_types.pop();
+        self.scopes.pop();
+        result?;
+        if ret.is_known() && ret != Type::Nil && !Self::always_returns(&def.body) {
+            let name = def.name.as_deref().unwrap_or("<fn>");
+            let loc = def.return_type.as_ref().map_or(def.location.clone(), |ann| ann.location.clone());
+            return Err(CompileError::new(format!("Function `{}` does not return a value of type {} on every path", name, ret), loc));
+        }
+        return Ok(());
+    }
+
+    /// Whether every path through `stmts` ends in a `return` or `throw`.
+    fn always_returns(stmts: &[Stmt]) -> bool {
+        return stmts.iter().any(|stmt| match stmt {
+            Stmt::Return(..) | Stmt::Throw(..) => true,
+            Stmt::Try(body, catch, finally, _) => {
+                let handled = catch.as_ref().is_none_or(|(_, handler)| Self::always_returns(std::slice::from_ref(handler)));
+                (Self::always_returns(std::slice::from_ref(body)) && handled)
+                    || finally.as_ref().is_some_and(|finally| Self::always_returns(std::slice::from_ref(finally)))
+            }
+            Stmt::Block(stmts, _) => Self::always_returns(stmts),
+            Stmt::If(_, then_branch, Some(else_branch), _) =>
+                Self::always_returns(std::slice::from_ref(then_branch)) && Self::always_returns(std::slice::from_ref(else_branch)),
+            _ => false
+        });
+    }
+
+    /// Returns the signature of `def` when at least one of its parameters or
+    /// its return value is annotated.
+    fn signature(&self, def: &FunctionDef) -> Result<Option<Signature>, CompileError> {
+        if def.return_type.is_none() && def.param_types.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let params = def.param_types.iter().map(|ann| self.resolve_type(ann.as_ref())).collect::<Result<_, _>>()?;
+        let ret = self.resolve_type(def.return_type.as_ref())?;
+        return Ok(Some(Signature { params, ret }));
+    }
+
+    fn check_condition(&mut self, cond: &Expr) -> Result<(), CompileError> {
+        let ty = self.check_expr(cond)?;
+        if ty.is_known() && ty != Type::Bool {
+            return Err(CompileError::new(format!("Condition must be a bool, found {}", ty), cond.location()));
+        }
+        return Ok(());
+    }
+
+    fn check_expr(&mut self, expr: &Expr) -> Result<Type, CompileError> {
+        let ty = match expr {
+            Expr::Int(..) => Type::Int,
+            Expr::BigInt(..) => Type::BigInt,
+            Expr::Float(..) => Type::Float,
+            Expr::Str(..) => Type::Str,
+            Expr::Char(..) => Type::Char,
+            Expr::Bool(..) => Type::Bool,
+            Expr::Nil(_) => Type::Nil,
+            Expr::Var(name, _) => self.lookup(name).map_or(Type::Any, |binding| binding.ty.clone()),
+            Expr::Unary(op, operand, loc) => {
+                let ty = self.check_expr(operand)?;
+                match op {
+                    UnaryOp::Neg if ty.is_known() && !ty.is_numeric() =>
+                        return Err(CompileError::new(format!("Cannot apply `-` to {}", ty), loc.clone())),
+                    UnaryOp::Neg => ty,
+                    UnaryOp::Not if ty.is_known() && ty != Type::Bool =>
+                        return Err(CompileError::new(format!("Cannot apply `!` to {}", ty), loc.clone())),
+                    UnaryOp::Not => Type::Bool
+                }
+            }
+            Expr::Binary(op, lhs, rhs, loc) => {
+                let lhs = self.check_expr(lhs)?;
+                let rhs = self.check_expr(rhs)?;
+                Self::binary_type(*op, &lhs, &rhs, loc)?
+            }
+            Expr::Logical(op, lhs, rhs, _) => {
+                for operand in [lhs, rhs] {
+                    let ty = self.check_expr(operand)?;
+                    if ty.is_known() && ty != Type::Bool {
+                        let op = if *op == LogicalOp::And { "&&" } else { "||" };
+                        return Err(CompileError::new(format!("Operands of `{}` must be bools, found {}", op, ty), operand.location()));
+                    }
+                }
+                Type::Bool
+            }
+            Expr::Call(callee, args, loc) => return self.check_call(callee, args, loc),
+            Expr::MethodCall(receiver, _, args, _) => {
+                self.check_exprs(args)?;
+                match self.enum_name(receiver) {
+                    Some(name) => Type::Named(name),
+                    None => {
+                        self.check_expr(receiver)?;
+                        Type::Any
+                    }
+                }
+            }
+            Expr::List(items, _) => {
+                self.check_exprs(items)?;
+                Type::List
+            }
+            Expr::Map(entries, _) => {
+                for (key, value) in entries {
+                    self.check_expr(key)?;
+                    self.check_expr(value)?;
+                }
+                Type::Map
+            }
+            Expr::Index(obj, index, _) => {
+                self.check_expr(obj)?;
+                self.check_expr(index)?;
+                Type::Any
+            }
+            Expr::Get(obj, _, _) => match self.enum_name(obj) {
+                Some(name) => Type::Named(name),
+                None => {
+                    self.check_expr(obj)?;
+                    Type::Any
+                }
+            },
+            Expr::Function(def) => {
+                self.check_function(def)?;
+                Type::Function
+            }
+            Expr::StructLit(name, fields, _) => {
+                for (_, value) in fields {
+                    self.check_expr(value)?;
+                }
+                Type::Named(name.clone())
+            }
+            Expr::SelfRef(_) | Expr::Super(..) => Type::Any,
+            Expr::Match(value, arms, _) => {
+                self.check_expr(value)?;
+                for arm in arms {
+                    let mut scope = HashMap::new();
+                    Self::declare_bindings(&arm.pattern, &mut scope);
+                    self.scopes.push(scope);
+                    let result = self.check_arm(arm.guard.as_ref(), &arm.body);
+                    self.scopes.pop();
+                    result?;
+                }
+                Type::Any
+            }
+            Expr::Interp(parts, _) => {
+                self.check_exprs(parts)?;
+                Type::Str
+            }
+            Expr::Propagate(value, loc) => {
+                let ty = self.check_expr(value)?;
+                if ty.is_known() && ty != Type::Named("Result".to_string()) {
+                    return Err(CompileError::new(format!("`?` expects a Result, found {}", ty), loc.clone()));
+                }
+                Type::Any
+            }
+        };
+        return Ok(ty);
+    }
+
+    /// Returns the name of the enum `expr` refers to in `Enum.Variant`.
+    fn enum_name(&self, expr: &Expr) -> Option<String> {
+        return match expr {
+            Expr::Var(name, _) if self.enums.contains(name) && self.lookup(name).is_none() => Some(name.clone()),
+            _ => None
+        };
+    }
+
+    fn check_exprs(&mut self, exprs: &[Expr]) -> Result<(), CompileError> {
+        for expr in exprs {
+            self.check_expr(expr)?;
+        }
+        return Ok(());
+    }
+
+    fn check_arm(&mut self, guard: Option<&Expr>, body: &ArmBody) -> Result<(), CompileError> {
+        if let Some(guard) = guard {
+            self.check_condition(guard)?;
+        }
+        match body {
+            ArmBody::Expr(expr) => { self.check_expr(expr)?; }
+            ArmBody::Block(block) => self.check_stmt(block)?
+        }
+        return Ok(());
+    }
+
+    fn declare_bindings(pattern: &Pattern, scope: &mut HashMap<String, Binding>) {
+        match pattern {
+            Pattern::Binding(name, _) => { scope.insert(name.clone(), Binding { ty: Type::Any, signature: None }); }
+            Pattern::Variant(_, _, items, _) | Pattern::List(items, _) | Pattern::Or(items, _) =>
+                items.iter().for_each(|item| Self::declare_bindings(item, scope)),
+            Pattern::Struct(_, fields, _) => fields.iter().for_each(|(_, sub)| Self::declare_bindings(sub, scope)),
+            Pattern::Wildcard(_) | Pattern::Literal(_) | Pattern::Range(..) => {}
+        }
+    }
+
+    /// Checks the arguments of a call against the signature of the callee
+    /// when it is a named function with annotations.
+    fn check_call(&mut self, callee: &Expr, args: &[Expr], loc: &Location) -> Result<Type, CompileError> {
+        let (name, binding) = match callee {
+            Expr::Var(name, _) => (name, self.lookup(name).cloned()),
+            callee => {
+                self.check_expr(callee)?;
+                self.check_exprs(args)?;
+                return Ok(Type::Any);
+            }
+        };
+        let signature = match binding {
+            Some(Binding { signature: Some(signature), .. }) => signature,
+            Some(_) => {
+                self.check_exprs(args)?;
+                return Ok(Type::Any);
+            }
+            None => {
+                self.check_exprs(args)?;
+                let ty = match name.as_str() {
+                    "Ok" | "Err" => Type::Named("Result".to_string()),
+                    name if self.classes.contains(name) => Type::Named(name.to_string()),
+                    _ => Type::Any
+                };
+                return Ok(ty);
+            }
+        };
+        if args.len() != signature.params.len() {
+            let message = format!("`{}` expects {} arguments, got {}", name, signature.params.len(), args.len());
+            return Err(CompileError::new(message, loc.clone()));
+        }
+        for (arg, expected) in args.iter().zip(&signature.params) {
+            let found = self.check_expr(arg)?;
+            self.expect_type(expected, &found, &arg.location())?;
+        }
+        return Ok(signature.ret);
+    }
+
+    fn binary_type(op: BinaryOp, lhs: &Type, rhs: &Type, loc: &Location) -> Result<Type, CompileError> {
+        let ty = match op {
+            BinaryOp::Eq | BinaryOp::NotEq => return Ok(Type::Bool),
+            _ if !lhs.is_known() || !rhs.is_known() => {
+                if matches!(op, BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq) {
+                    return Ok(Type::Bool);
+                }
+                return Ok(Type::Any);
+            }
+            BinaryOp::Add if *lhs == Type::Str && *rhs == Type::Str => Some(Type::Str),
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Rem => match (lhs, rhs) {
+                (Type::Int, Type::Int) => Some(Type::Int),
+                (Type::Int | Type::BigInt, Type::Int | Type::BigInt) => Some(Type::BigInt),
+                (Type::BigInt, _) | (_, Type::BigInt) => None,
+                (lhs, rhs) if lhs.is_numeric() && rhs.is_numeric() => Some(Type::Float),
+                _ => None
+            },
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => match (lhs, rhs) {
+                (lhs, rhs) if lhs.is_numeric() && rhs.is_numeric() => Some(Type::Bool),
+                (Type::Str, Type::Str) | (Type::Char, Type::Char) => Some(Type::Bool),
+                _ => None
+            }
+        };
+        return match ty {
+            Some(ty) => Ok(ty),
+            None => Err(CompileError::new(format!("Cannot apply `{}` to {} and {}", Self::op_symbol(op), lhs, rhs), loc.clone()))
+        };
+    }
+
+    fn op_symbol(op: BinaryOp) -> &'static str {
+        match op {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::FloorDiv => "//",
+            BinaryOp::Rem => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">="
+        }
+    }
+
+    fn expect_type(&self, expected: &Type, found: &Type, loc: &Location) -> Result<(), CompileError> {
+        if expected.accepts(found) {
+            return Ok(());
+        }
+        return Err(CompileError::new(format!("Type mismatch: expected {}, found {}", expected, found), loc.clone()));
+    }
+
+    fn resolve_type(&self, ann: Option<&TypeAnn>) -> Result<Type, CompileError> {
+        let ann = match ann {
+            Some(ann) => ann,
+            None => return Ok(Type::Any)
+        };
+        let ty = match ann.name.as_str() {
+            "any" => Type::Any,
+            "int" => Type::Int,
+            "bigint" => Type::BigInt,
+            "float" => Type::Float,
+            "str" => Type::Str,
+            "char" => Type::Char,
+            "bool" => Type::Bool,
+            "nil" => Type::Nil,
+            "list" => Type::List,
+            "map" => Type::Map,
+            "function" => Type::Function,
+            name if self.type_names.contains(name) => Type::Named(name.to_string()),
+            name => return Err(CompileError::new(format!("Unknown type `{}`", name), ann.location.clone()))
+        };
+        return Ok(ty);
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, signature: Option<Signature>) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), Binding { ty, signature });
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name));
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        return self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name));
+    }
+}
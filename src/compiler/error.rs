@@ -0,0 +1,60 @@
+use std::fmt;
+
+use crate::compiler::location::Location;
+
+/// An error that stops compilation. `file` names the imported module the
+/// error is in; it is `None` for the script being compiled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub location: Location,
+    pub file: Option<String>
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>, location: Location) -> Self {
+        Self { message: message.into(), location, file: None }
+    }
+
+    /// Attributes the error to `file` unless it already names a module.
+    pub fn in_file(mut self, file: Option<&str>) -> Self {
+        if self.file.is_none() {
+            self.file = file.map(str::to_string);
+        }
+        return self;
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "Error in {} at {}:{}: {}", file, self.location.line, self.location.col, self.message),
+            None => write!(f, "Error at {}:{}: {}", self.location.line, self.location.col, self.message)
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// A diagnostic that does not stop compilation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileWarning {
+    pub message: String,
+    pub location: Location,
+    pub file: Option<String>
+}
+
+impl CompileWarning {
+    pub fn new(message: impl Into<String>, location: Location) -> Self {
+        Self { message: message.into(), location, file: None }
+    }
+}
+
+impl fmt::Display for CompileWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "Warning in {} at {}:{}: {}", file, self.location.line, self.location.col, self.message),
+            None => write!(f, "Warning at {}:{}: {}", self.location.line, self.location.col, self.message)
+        }
+    }
+}
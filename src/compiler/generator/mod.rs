@@ -0,0 +1,1598 @@
+use std::{collections::{HashMap, HashSet}, rc::Rc};
+
+use crate::{
+    compiler::{
+        error::{CompileError, CompileWarning},
+        infer::NumType,
+        location::Location,
+        module::Module,
+        parser::ast::{ArmBody, BinaryOp, ClassDef, Expr, FunctionDef, LogicalOp, MatchArm, Pattern, Stmt, UnaryOp}
+    },
+    vm::{
+        chunk::{Chunk, MAX_WIDTH},
+        enum_def::{EnumDef, ERR_VARIANT, OK_VARIANT, RESULT_ENUM, VariantDef},
+        function::FunctionProto,
+        interner::Interner,
+        opcodes::OpCode,
+        native::{NativeFunction, VARIADIC},
+        program::Program,
+        stack_slot::StackSlot,
+        struct_def::StructDef
+    }
+};
+
+/// A global variable of the module `module`. Other modules can only see
+/// it if it is exported.
+struct Global {
+    name: String,
+    is_const: bool,
+    module: usize,
+    is_exported: bool
+}
+
+struct Local {
+    name: String,
+    slot: usize,
+    depth: usize,
+    is_const: bool,
+    is_captured: bool
+}
+
+/// A variable of an enclosing function captured by the function being
+/// generated: either a local of the directly enclosing function or one of
+/// its own upvalues.
+struct Upvalue {
+    is_local: bool,
+    index: usize,
+    is_const: bool
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer
+}
+
+/// Per-function compilation state. The top-level script is the outermost
+/// function; every `fn` being generated pushes a new state. `temps` counts
+/// the operands currently pushed above the locals, so that a local declared
+/// in the middle of an expression gets the right frame slot. `tries` has
+/// one entry per exception handler installed by an enclosing `try`, with
+/// the `finally` block a `return` has to run before leaving it.
+struct FunctionState {
+    kind: FunctionKind,
+    chunk_index: usize,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: usize,
+    temps: usize,
+    tries: Vec<Option<Stmt>>
+}
+
+impl FunctionState {
+    fn new(kind: FunctionKind, chunk_index: usize, scope_depth: usize) -> Self {
+        Self { kind, chunk_index, locals: Vec::new(), upvalues: Vec::new(), scope_depth, temps: 0, tries: Vec::new() }
+    }
+}
+
+/// How a sub-value of a matched value is reached from the value itself.
+enum PathStep {
+    Payload(usize),
+    Field(String, usize, usize),
+    Item(usize)
+}
+
+/// The int cases of a match compiled to a jump table: the smallest value,
+/// the number of table entries, the values handled by each arm and the arm
+/// that handles all other values, if any.
+struct JumpTable {
+    min: i64,
+    count: usize,
+    cases: Vec<Vec<i64>>,
+    default: Option<usize>
+}
+
+enum Variable {
+    Global(usize, bool),
+    Local(usize, bool),
+    Upvalue(usize, bool)
+}
+
+pub struct Generator<'a> {
+    chunks: Vec<Chunk>,
+    functions: Vec<FunctionProto>,
+    globals: Vec<Global>,
+    hoisted: HashMap<String, usize>,
+    structs: Vec<StructDef>,
+    enums: Vec<EnumDef>,
+    methods: HashSet<String>,
+    warnings: Vec<CompileWarning>,
+    num_types: Vec<HashMap<Location, NumType>>,
+    optimize: bool,
+    module: usize,
+    file: Option<String>,
+    imports: HashMap<String, usize>,
+    aliases: HashMap<String, usize>,
+    states: Vec<FunctionState>,
+    natives: &'a [NativeFunction],
+    interner: Rc<Interner>
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(natives: &'a [NativeFunction]) -> Self {
+        let interner = Rc::new(Interner::new());
+        Self {
+            chunks: vec![Chunk::with_interner(Rc::clone(&interner))],
+            functions: Vec::new(),
+            globals: Vec::new(),
+            hoisted: HashMap::new(),
+            structs: Vec::new(),
+            enums: vec![EnumDef::result()],
+            methods: HashSet::new(),
+            warnings: Vec::new(),
+            num_types: Vec::new(),
+            optimize: false,
+            module: 0,
+            file: None,
+            imports: HashMap::new(),
+            aliases: HashMap::new(),
+            states: vec![FunctionState::new(FunctionKind::Script, 0, 0)],
+            natives,
+            interner
+        }
+    }
+
+    /// Uses the operand types found by type inference, one map per module,
+    /// to emit specialised arithmetic instructions.
+    pub fn with_num_types(mut self, num_types: Vec<HashMap<Location, NumType>>) -> Self {
+        self.num_types = num_types;
+        return self;
+    }
+
+    /// Leaves out code that can never run: the untaken branch of an `if` on
+    /// a constant condition, `while (false)` loops, the condition check of
+    /// `while (true)` loops and statements after a `return` or `throw`.
+    pub fn with_optimizations(mut self) -> Self {
+        self.optimize = true;
+        return self;
+    }
+
+    /// Emits bytecode for `modules`, which come in the order they must be
+    /// initialised in. Each module runs once, and the script, which is last,
+    /// keeps the value of a trailing expression statement on the stack so
+    /// that it becomes the program result.
+    pub fn generate(mut self, modules: &[Module]) -> Result<Program, CompileError> {
+        for module in modules {
+            self.declare_types(&module.stmts).map_err(|err| err.in_file(module.name.as_deref()))?;
+        }
+        for (index, module) in modules.iter().enumerate() {
+            self.module = index;
+            self.file = module.name.clone();
+            self.imports = module.imports.clone();
+            self.aliases.clear();
+            let is_script = index + 1 == modules.len();
+            self.generate_module(&module.stmts, is_script).map_err(|err| err.in_file(module.name.as_deref()))?;
+        }
+        for chunk in &mut self.chunks {
+            chunk.compact(&self.functions).map_err(|pos| {
+                let location = chunk.location_at(pos).unwrap_or(Location { line: 1, col: 1 });
+                CompileError::new("Too many constants, variables or instructions: an operand does not fit in four bytes", location)
+            })?;
+        }
+        self.interner.prune();
+        return Ok(Program {
+            chunks: self.chunks,
+            functions: self.functions,
+            structs: self.structs,
+            enums: self.enums,
+            warnings: self.warnings,
+            globals: self.globals.len(),
+            interner: self.interner
+        });
+    }
+
+    fn generate_module(&mut self, stmts: &[Stmt], is_script: bool) -> Result<(), CompileError> {
+        self.declare_functions(stmts);
+        for (i, stmt) in stmts.iter().enumerate() {
+            let is_last = i + 1 == stmts.len();
+            match stmt {
+                Stmt::Expr(expr, _) if is_script && is_last => self.generate_expr(expr)?,
+                _ => self.generate_stmt(stmt)?
+            }
+        }
+        return Ok(());
+    }
+
+    /// Top-level functions get their global before any code of the module
+    /// is generated, so that they can call functions declared further down
+    /// and each other. The first declaration of a name stores into it.
+    fn declare_functions(&mut self, stmts: &[Stmt]) {
+        self.hoisted.clear();
+        for stmt in stmts {
+            if let Stmt::Fn(def) = stmt.unexported() && let Some(name) = &def.name && !self.hoisted.contains_key(name) {
+                let index = self.add_global(name, true);
+                self.hoisted.insert(name.clone(), index);
+            }
+        }
+    }
+
+    /// Struct and enum declarations are hoisted so that code anywhere in the
+    /// script can construct them and have their field offsets resolved. Class
+    /// method names are collected so that `obj.name()` is never bound to a
+    /// native that a class method could override.
+    fn declare_types(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        for stmt in stmts {
+            match stmt.unexported() {
+                Stmt::Struct(name, fields, loc) => {
+                    if self.structs.iter().any(|s| s.name == *name) {
+                        return Err(CompileError::new(format!("Struct `{}` is already defined", name), loc.clone()));
+                    }
+                    self.structs.push(StructDef { name: name.clone(), fields: fields.clone() });
+                }
+                Stmt::Class(def) => self.methods.extend(def.methods.iter().filter_map(|m| m.name.clone())),
+                Stmt::Enum(name, variants, loc) => {
+                    if self.enums.iter().any(|e| e.name == *name) {
+                        return Err(CompileError::new(format!("Enum `{}` is already defined", name), loc.clone()));
+                    }
+                    let variants = variants.iter().map(|(name, arity)| VariantDef { name: name.clone(), arity: *arity }).collect();
+                    self.enums.push(EnumDef { name: name.clone(), variants });
+                }
+                _ => {}
+            }
+        }
+        return Ok(());
+    }
+
+    /// Returns the struct and offset of field `name` when exactly one struct
+    /// declares a field with that name.
+    fn unique_field(&self, name: &str) -> Option<(usize, usize)> {
+        let mut found = None;
+        for (index, def) in self.structs.iter().enumerate() {
+            if let Some(offset) = def.field_offset(name) {
+                if found.is_some() {
+                    return None;
+                }
+                found = Some((index, offset));
+            }
+        }
+        return found;
+    }
+
+    fn emit_get_field(&mut self, name: &str) {
+        match self.unique_field(name) {
+            Some((def, offset)) => { self.chunk().emit_get_field_at(name, def, offset); }
+            None => { self.chunk().emit_get_field(name); }
+        }
+    }
+
+    fn emit_set_field(&mut self, name: &str) {
+        match self.unique_field(name) {
+            Some((def, offset)) => { self.chunk().emit_set_field_at(name, def, offset); }
+            None => { self.chunk().emit_set_field(name); }
+        }
+    }
+
+    fn state(&self) -> &FunctionState {
+        return self.states.last().unwrap();
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        return self.states.last_mut().unwrap();
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        let chunk_index = self.state().chunk_index;
+        return &mut self.chunks[chunk_index];
+    }
+
+    fn here(&self) -> usize {
+        return self.chunks[self.state().chunk_index].bytecode.len();
+    }
+
+    /// Variables are global only at the top level of the script itself.
+    fn is_global_scope(&self) -> bool {
+        return self.states.len() == 1 && self.state().scope_depth == 0;
+    }
+
+    fn generate_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        self.chunk().mark(&stmt.location());
+        match stmt {
+            Stmt::VarDef(name, _, value, is_const, loc) => self.generate_var_def(name, value.as_ref(), *is_const, loc),
+            Stmt::Print(expr, _) => self.generate_print(expr),
+            Stmt::Expr(expr, _) => {
+                self.generate_expr(expr)?;
+                self.chunk().emit_byte(OpCode::Pop as u8);
+                Ok(())
+            }
+            Stmt::Assign(target, value, loc) => self.generate_assign(target, value, loc),
+            Stmt::Block(stmts, _) => {
+                self.begin_scope();
+                let reachable = self.generate_stmts(stmts)?;
+                match reachable {
+                    true => self.end_scope(),
+                    false => self.drop_scope()
+                }
+                Ok(())
+            }
+            Stmt::If(cond, then_branch, else_branch, _) => self.generate_if(cond, then_branch, else_branch.as_deref()),
+            Stmt::While(cond, body, _) => self.generate_while(cond, body),
+            Stmt::For(name, iterable, body, _) => self.generate_for(name, iterable, body),
+            Stmt::Fn(def) => self.generate_fn_decl(def),
+            Stmt::Return(value, loc) => self.generate_return(value.as_ref(), loc),
+            Stmt::Struct(_, _, loc) if !self.is_global_scope() =>
+                Err(CompileError::new("Structs can only be declared at the top level", loc.clone())),
+            Stmt::Struct(..) => Ok(()),
+            Stmt::Class(def) if !self.is_global_scope() =>
+                Err(CompileError::new("Classes can only be declared at the top level", def.location.clone())),
+            Stmt::Class(def) => self.generate_class(def),
+            Stmt::Enum(_, _, loc) if !self.is_global_scope() =>
+                Err(CompileError::new("Enums can only be declared at the top level", loc.clone())),
+            Stmt::Enum(..) => Ok(()),
+            Stmt::Import(_, _, loc) if !self.is_global_scope() =>
+                Err(CompileError::new("Imports can only appear at the top level", loc.clone())),
+            Stmt::Import(path, alias, loc) => {
+                if self.aliases.contains_key(alias) {
+                    return Err(CompileError::new(format!("Module alias `{}` is already defined", alias), loc.clone()));
+                }
+                self.aliases.insert(alias.clone(), self.imports[path]);
+                Ok(())
+            }
+            Stmt::Export(_, loc) if !self.is_global_scope() =>
+                Err(CompileError::new("Exports can only appear at the top level", loc.clone())),
+            Stmt::Export(decl, _) => self.generate_export(decl),
+            Stmt::Throw(value, loc) => {
+                self.generate_expr(value)?;
+                self.chunk().mark(loc);
+                self.chunk().emit_byte(OpCode::Throw as u8);
+                Ok(())
+            }
+            Stmt::Try(body, catch, finally, _) => self.generate_try(body, catch.as_ref(), finally.as_deref())
+        }
+    }
+
+    /// Generates a sequence of statements and returns whether its end can
+    /// be reached. When optimizing, statements after one that never
+    /// completes are not kept.
+    fn generate_stmts(&mut self, stmts: &[Stmt]) -> Result<bool, CompileError> {
+        let mut reachable = true;
+        for stmt in stmts {
+            match reachable {
+                true => self.generate_stmt(stmt)?,
+                false => self.generate_unreachable(stmt)?
+            }
+            reachable = reachable && !(self.optimize && self.diverges(stmt));
+        }
+        return Ok(reachable);
+    }
+
+    /// Generates code that can never run, for the errors it reports only:
+    /// the bytecode, constants and functions it produces are dropped.
+    fn generate_unreachable(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        let (chunks, functions) = (self.chunks.len(), self.functions.len());
+        let chunk = self.chunk();
+        let (bytecode, constants) = (chunk.bytecode.len(), chunk.constants.len());
+        let (locations, last_location) = (chunk.locations.len(), chunk.locations.last().cloned());
+        self.generate_stmt(stmt)?;
+        let chunk = self.chunk();
+        chunk.bytecode.truncate(bytecode);
+        chunk.truncate_constants(constants);
+        chunk.locations.truncate(locations);
+        if let (Some(last), Some(location)) = (chunk.locations.last_mut(), last_location) {
+            *last = location;
+        }
+        self.chunks.truncate(chunks);
+        self.functions.truncate(functions);
+        return Ok(());
+    }
+
+    /// Whether control never continues past `stmt`.
+    fn diverges(&self, stmt: &Stmt) -> bool {
+        return match stmt {
+            Stmt::Return(..) | Stmt::Throw(..) => true,
+            Stmt::Block(stmts, _) => stmts.iter().any(|stmt| self.diverges(stmt)),
+            Stmt::If(Expr::Bool(true, _), then_branch, _, _) => self.diverges(then_branch),
+            Stmt::If(Expr::Bool(false, _), _, else_branch, _) => else_branch.as_ref().is_some_and(|stmt| self.diverges(stmt)),
+            Stmt::If(_, then_branch, Some(else_branch), _) => self.diverges(then_branch) && self.diverges(else_branch),
+            Stmt::While(Expr::Bool(true, _), ..) => true,
+            Stmt::Try(body, catch, finally, _) => {
+                let handled = catch.as_ref().is_none_or(|(_, handler)| self.diverges(handler));
+                (self.diverges(body) && handled) || finally.as_ref().is_some_and(|finally| self.diverges(finally))
+            }
+            _ => false
+        };
+    }
+
+    /// Generates an exported declaration and makes the global it defines
+    /// visible to importing modules. Types are visible to every module.
+    fn generate_export(&mut self, decl: &Stmt) -> Result<(), CompileError> {
+        self.generate_stmt(decl)?;
+        let name = match decl {
+            Stmt::VarDef(name, ..) => name,
+            Stmt::Fn(def) => def.name.as_ref().unwrap(),
+            Stmt::Class(def) => &def.name,
+            _ => return Ok(())
+        };
+        let module = self.module;
+        if let Some(global) = self.globals.iter_mut().rev().find(|g| g.module == module && g.name == *name) {
+            global.is_exported = true;
+        }
+        return Ok(());
+    }
+
+    /// Resolves `alias.name` to the global exported as `name` by the module
+    /// imported as `alias`, unless `alias` is shadowed by a variable.
+    fn resolve_export(&mut self, obj: &Expr, name: &str, loc: &Location) -> Result<Option<usize>, CompileError> {
+        let alias = match obj {
+            Expr::Var(alias, _) => alias,
+            _ => return Ok(None)
+        };
+        let module = match self.aliases.get(alias).copied() {
+            Some(module) if self.resolve(alias).is_none() => module,
+            _ => return Ok(None)
+        };
+        return match self.globals.iter().rposition(|g| g.module == module && g.name == name && g.is_exported) {
+            Some(index) => Ok(Some(index)),
+            None => Err(CompileError::new(format!("Module `{}` does not export `{}`", alias, name), loc.clone()))
+        };
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    /// Drops the locals of the innermost scope.
+    fn end_scope(&mut self) {
+        self.emit_scope_exit();
+        self.drop_scope();
+    }
+
+    /// Forgets the locals of the innermost scope without emitting anything,
+    /// for scopes whose end cannot be reached.
+    fn drop_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+        let depth = self.state().scope_depth;
+        while let Some(local) = self.state().locals.last() && local.depth > depth {
+            self.state_mut().locals.pop();
+        }
+    }
+
+    /// Emits the instructions that remove the locals of the innermost scope
+    /// from the stack. Captured locals are moved off the stack into their
+    /// upvalue instead of being discarded.
+    fn emit_scope_exit(&mut self) {
+        let depth = self.state().scope_depth;
+        let opcodes: Vec<OpCode> = self.state().locals.iter().rev()
+            .take_while(|local| local.depth == depth)
+            .map(|local| if local.is_captured { OpCode::CloseUpvalue } else { OpCode::Pop })
+            .collect();
+        for opcode in opcodes {
+            self.chunk().emit_byte(opcode as u8);
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<Variable> {
+        let level = self.states.len() - 1;
+        if let Some(index) = self.resolve_local(level, name) {
+            let local = &self.states[level].locals[index];
+            return Some(Variable::Local(local.slot, local.is_const));
+        }
+        if let Some(index) = self.resolve_upvalue(level, name) {
+            return Some(Variable::Upvalue(index, self.states[level].upvalues[index].is_const));
+        }
+        if let Some(index) = self.globals.iter().rposition(|g| g.module == self.module && g.name == name) {
+            return Some(Variable::Global(index, self.globals[index].is_const));
+        }
+        return None;
+    }
+
+    fn resolve_local(&self, level: usize, name: &str) -> Option<usize> {
+        return self.states[level].locals.iter().rposition(|l| l.name == name);
+    }
+
+    /// Looks `name` up in the functions enclosing `level`, threading the
+    /// capture through every function in between.
+    fn resolve_upvalue(&mut self, level: usize, name: &str) -> Option<usize> {
+        if level == 0 {
+            return None;
+        }
+        if let Some(index) = self.resolve_local(level - 1, name) {
+            let local = &mut self.states[level - 1].locals[index];
+            local.is_captured = true;
+            let (slot, is_const) = (local.slot, local.is_const);
+            return Some(self.add_upvalue(level, true, slot, is_const));
+        }
+        if let Some(index) = self.resolve_upvalue(level - 1, name) {
+            let is_const = self.states[level - 1].upvalues[index].is_const;
+            return Some(self.add_upvalue(level, false, index, is_const));
+        }
+        return None;
+    }
+
+    fn add_upvalue(&mut self, level: usize, is_local: bool, index: usize, is_const: bool) -> usize {
+        let upvalues = &mut self.states[level].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| u.is_local == is_local && u.index == index) {
+            return existing;
+        }
+        upvalues.push(Upvalue { is_local, index, is_const });
+        return upvalues.len() - 1;
+    }
+
+    fn generate_var_def(&mut self, name: &str, value: Option<&Expr>, is_const: bool, loc: &Location) -> Result<(), CompileError> {
+        match value {
+            Some(expr) => self.generate_expr(expr)?,
+            None => { self.chunk().emit_const(StackSlot::Nil); }
+        }
+
+        if self.is_global_scope() {
+            let index = self.add_global(name, is_const);
+            self.chunk().store_global(index);
+        }
+        else {
+            self.generate_local_var(name, is_const, loc)?;
+        }
+        return Ok(());
+    }
+
+    fn add_global(&mut self, name: &str, is_const: bool) -> usize {
+        self.globals.push(Global { name: name.to_string(), is_const, module: self.module, is_exported: false });
+        return self.globals.len() - 1;
+    }
+
+    /// Locals live on the stack: the value just generated becomes the
+    /// variable's slot.
+    fn generate_local_var(&mut self, name: &str, is_const: bool, loc: &Location) -> Result<(), CompileError> {
+        let depth = self.state().scope_depth;
+        if self.state().locals.iter().any(|l| l.depth == depth && l.name == name) {
+            return Err(CompileError::new(format!("Variable `{}` is already defined in this scope", name), loc.clone()));
+        }
+        self.add_local(name, is_const);
+        return Ok(());
+    }
+
+    /// Declares the value on top of the stack as a local and returns its
+    /// frame slot.
+    fn add_local(&mut self, name: &str, is_const: bool) -> usize {
+        let state = self.state_mut();
+        let slot = state.locals.len() + state.temps;
+        state.locals.push(Local { name: name.to_string(), slot, depth: state.scope_depth, is_const, is_captured: false });
+        return slot;
+    }
+
+    /// Generates `expr` as an operand that stays on the stack while the
+    /// following operands are generated. Release it with `release_temps`.
+    fn generate_operand(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        self.generate_expr(expr)?;
+        self.state_mut().temps += 1;
+        return Ok(());
+    }
+
+    fn release_temps(&mut self, count: usize) {
+        self.state_mut().temps -= count;
+    }
+
+    /// The function is declared before its body is generated so that it
+    /// can call itself.
+    fn generate_fn_decl(&mut self, def: &FunctionDef) -> Result<(), CompileError> {
+        let name = def.name.as_deref().unwrap_or_default();
+        if self.is_global_scope() {
+            let index = match self.hoisted.remove(name) {
+                Some(index) => index,
+                None => self.add_global(name, true)
+            };
+            self.generate_function(def, FunctionKind::Function)?;
+            self.chunk().store_global(index);
+        }
+        else {
+            self.generate_local_var(name, true, &def.location)?;
+            self.generate_function(def, FunctionKind::Function)?;
+        }
+        return Ok(());
+    }
+
+    /// Generates the body of `def` into a chunk of its own and emits the
+    /// `Closure` instruction that instantiates it. Local 0 holds the callee,
+    /// which is the receiver `self` in methods.
+    fn generate_function(&mut self, def: &FunctionDef, kind: FunctionKind) -> Result<(), CompileError> {
+        if def.params.len() > u8::MAX as usize {
+            return Err(CompileError::new("Too many parameters in function", def.location.clone()));
+        }
+        let chunk_index = self.chunks.len();
+        self.chunks.push(Chunk::with_interner(Rc::clone(&self.interner)));
+        self.states.push(FunctionState::new(kind, chunk_index, 1));
+        match kind {
+            FunctionKind::Method | FunctionKind::Initializer => self.add_local("self", true),
+            _ => self.add_local("", true)
+        };
+        for param in &def.params {
+            self.add_local(param, false);
+        }
+        if self.generate_stmts(&def.body)? {
+            self.generate_implicit_return();
+        }
+        let state = self.states.pop().unwrap();
+
+        let index = self.functions.len();
+        self.functions.push(FunctionProto {
+            name: def.name.clone(),
+            arity: def.params.len(),
+            chunk_index,
+            upvalue_count: state.upvalues.len()
+        });
+        let upvalues: Vec<(bool, usize)> = state.upvalues.iter().map(|u| (u.is_local, u.index)).collect();
+        self.chunk().emit_closure(index, &upvalues);
+        return Ok(());
+    }
+
+    /// Functions return `nil` when they run off their end, initialisers
+    /// return the new instance.
+    fn generate_implicit_return(&mut self) {
+        match self.state().kind {
+            FunctionKind::Initializer => self.chunk().load_local(0),
+            _ => { self.chunk().emit_const(StackSlot::Nil); }
+        }
+        self.chunk().emit_byte(OpCode::Ret as u8);
+    }
+
+    fn generate_return(&mut self, value: Option<&Expr>, loc: &Location) -> Result<(), CompileError> {
+        match self.state().kind {
+            FunctionKind::Script => return Err(CompileError::new("Cannot return from top-level code", loc.clone())),
+            FunctionKind::Initializer if value.is_some() =>
+                return Err(CompileError::new("Cannot return a value from an initialiser", loc.clone())),
+            FunctionKind::Initializer => self.chunk().load_local(0),
+            _ => match value {
+                Some(expr) => self.generate_expr(expr)?,
+                None => { self.chunk().emit_const(StackSlot::Nil); }
+            }
+        }
+        self.state_mut().temps += 1;
+        self.generate_pending_finally()?;
+        self.release_temps(1);
+        self.chunk().emit_byte(OpCode::Ret as u8);
+        return Ok(());
+    }
+
+    /// Removes the handlers of the `try` statements a `return` leaves and
+    /// runs their `finally` blocks, innermost first. A `return` inside one
+    /// of those blocks only has to handle the statements enclosing it.
+    fn generate_pending_finally(&mut self) -> Result<(), CompileError> {
+        let tries = std::mem::take(&mut self.state_mut().tries);
+        for (i, finally) in tries.iter().enumerate().rev() {
+            self.state_mut().tries = tries[..i].to_vec();
+            self.chunk().emit_byte(OpCode::PopHandler as u8);
+            if let Some(finally) = finally {
+                self.generate_stmt(finally)?;
+            }
+        }
+        self.state_mut().tries = tries;
+        return Ok(());
+    }
+
+    /// The body runs with a handler installed. When an exception reaches
+    /// it, the exception is bound to the `catch` variable, and a `finally`
+    /// block runs on every way out: after the body or handler completes,
+    /// or before an exception that escapes them is thrown again.
+    fn generate_try(&mut self, body: &Stmt, catch: Option<&(String, Box<Stmt>)>, finally: Option<&Stmt>) -> Result<(), CompileError> {
+        let handler = self.chunk().emit_push_handler();
+        self.state_mut().tries.push(finally.cloned());
+        self.generate_stmt(body)?;
+        self.state_mut().tries.pop();
+        self.chunk().emit_byte(OpCode::PopHandler as u8);
+        let mut end_jumps = vec![self.chunk().emit_jmp(0)];
+        let handler_start = self.here();
+        self.chunk().patch_jmp(handler, handler_start);
+
+        match catch {
+            Some((name, handler_body)) => {
+                self.begin_scope();
+                self.add_local(name, false);
+                let rethrow = finally.map(|finally| {
+                    self.state_mut().tries.push(Some(finally.clone()));
+                    self.chunk().emit_push_handler()
+                });
+                self.generate_stmt(handler_body)?;
+                if let Some(rethrow) = rethrow {
+                    self.state_mut().tries.pop();
+                    self.chunk().emit_byte(OpCode::PopHandler as u8);
+                    self.end_scope();
+                    end_jumps.push(self.chunk().emit_jmp(0));
+                    let rethrow_start = self.here();
+                    self.chunk().patch_jmp(rethrow, rethrow_start);
+                    self.generate_rethrow(finally.unwrap(), 1)?;
+                }
+                else {
+                    self.end_scope();
+                }
+            }
+            None => self.generate_rethrow(finally.unwrap(), 0)?
+        }
+
+        let end = self.here();
+        for jump in end_jumps {
+            self.chunk().patch_jmp(jump, end);
+        }
+        if let Some(finally) = finally {
+            self.generate_stmt(finally)?;
+        }
+        return Ok(());
+    }
+
+    /// Generates the path taken by an exception that escapes a `try` body
+    /// or `catch` block: the exception is on top of the stack, above the
+    /// `below` values the abandoned block kept there. Runs `finally` and
+    /// throws the exception again.
+    fn generate_rethrow(&mut self, finally: &Stmt, below: usize) -> Result<(), CompileError> {
+        self.begin_scope();
+        for _ in 0..below {
+            self.add_local("", true);
+        }
+        let slot = self.add_local("", true);
+        self.generate_stmt(finally)?;
+        self.chunk().load_local(slot);
+        self.chunk().emit_byte(OpCode::Throw as u8);
+        let state = self.state_mut();
+        state.locals.truncate(state.locals.len() - below - 1);
+        state.scope_depth -= 1;
+        return Ok(());
+    }
+
+    fn generate_print(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        self.generate_expr(expr)?;
+        self.chunk().emit_byte(OpCode::Print as u8);
+        return Ok(());
+    }
+
+    fn generate_assign(&mut self, target: &Expr, value: &Expr, loc: &Location) -> Result<(), CompileError> {
+        match target {
+            Expr::Var(name, var_loc) => {
+                let variable = match self.resolve(name) {
+                    Some(variable) => variable,
+                    None => return Err(CompileError::new(format!("Variable `{}` is not defined", name), var_loc.clone()))
+                };
+                if let Variable::Global(_, true) | Variable::Local(_, true) | Variable::Upvalue(_, true) = variable {
+                    return Err(CompileError::new(format!("Cannot assign to constant `{}`", name), loc.clone()));
+                }
+                self.generate_expr(value)?;
+                match variable {
+                    Variable::Global(index, _) => self.chunk().store_global(index),
+                    Variable::Local(index, _) => self.chunk().store_local(index),
+                    Variable::Upvalue(index, _) => self.chunk().store_upvalue(index)
+                }
+            }
+            Expr::Index(collection, index, _) => {
+                self.generate_operand(collection)?;
+                self.generate_operand(index)?;
+                self.generate_expr(value)?;
+                self.release_temps(2);
+                self.chunk().mark(loc);
+                self.chunk().emit_byte(OpCode::StoreIndex as u8);
+            }
+            Expr::Get(obj, name, get_loc) => {
+                if self.resolve_export(obj, name, get_loc)?.is_some() {
+                    return Err(CompileError::new(format!("Cannot assign to `{}` outside of its module", name), loc.clone()));
+                }
+                self.generate_operand(obj)?;
+                self.generate_expr(value)?;
+                self.release_temps(1);
+                self.chunk().mark(loc);
+                self.emit_set_field(name);
+            }
+            _ => return Err(CompileError::new("Invalid assignment target", loc.clone()))
+        }
+        return Ok(());
+    }
+
+    fn generate_if(&mut self, cond: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<(), CompileError> {
+        if let Expr::Bool(taken, _) = cond && self.optimize {
+            match taken {
+                true => self.generate_stmt(then_branch)?,
+                false => self.generate_unreachable(then_branch)?
+            }
+            if let Some(else_branch) = else_branch {
+                match taken {
+                    true => self.generate_unreachable(else_branch)?,
+                    false => self.generate_stmt(else_branch)?
+                }
+            }
+            return Ok(());
+        }
+        self.generate_expr(cond)?;
+        self.chunk().mark(&cond.location());
+        let jmp_else = self.chunk().emit_jmp_if_false(0);
+        self.generate_stmt(then_branch)?;
+        match else_branch {
+            Some(else_branch) => {
+                let jmp_end = self.chunk().emit_jmp(0);
+                let else_start = self.here();
+                self.chunk().patch_jmp(jmp_else, else_start);
+                self.generate_stmt(else_branch)?;
+                let end = self.here();
+                self.chunk().patch_jmp(jmp_end, end);
+            }
+            None => {
+                let end = self.here();
+                self.chunk().patch_jmp(jmp_else, end);
+            }
+        }
+        return Ok(());
+    }
+
+    fn generate_while(&mut self, cond: &Expr, body: &Stmt) -> Result<(), CompileError> {
+        let loop_start = self.here();
+        if let Expr::Bool(runs, _) = cond && self.optimize {
+            match runs {
+                true => {
+                    self.generate_stmt(body)?;
+                    self.chunk().emit_jmp(loop_start);
+                }
+                false => self.generate_unreachable(body)?
+            }
+            return Ok(());
+        }
+        self.generate_expr(cond)?;
+        self.chunk().mark(&cond.location());
+        let jmp_exit = self.chunk().emit_jmp_if_false(0);
+        self.generate_stmt(body)?;
+        self.chunk().emit_jmp(loop_start);
+        let end = self.here();
+        self.chunk().patch_jmp(jmp_exit, end);
+        return Ok(());
+    }
+
+    /// The iterable and the loop cursor are kept in two hidden locals that
+    /// `ForIter` reads and advances on every iteration.
+    fn generate_for(&mut self, name: &str, iterable: &Expr, body: &Stmt) -> Result<(), CompileError> {
+        self.begin_scope();
+        self.generate_expr(iterable)?;
+        let slot = self.add_local("(for iterable)", true);
+        self.chunk().emit_const(StackSlot::Int(0));
+        self.add_local("(for cursor)", true);
+
+        let loop_start = self.here();
+        let for_iter = self.chunk().emit_for_iter(slot);
+        self.begin_scope();
+        self.add_local(name, false);
+        self.generate_stmt(body)?;
+        self.end_scope();
+        self.chunk().emit_jmp(loop_start);
+        let end = self.here();
+        self.chunk().patch_jmp(for_iter, end);
+        self.end_scope();
+        return Ok(());
+    }
+
+    fn generate_expr(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr {
+            Expr::Int(val, _) => { self.chunk().emit_const(StackSlot::Int(*val)); }
+            Expr::BigInt(val, _) => { self.chunk().emit_const(StackSlot::from(val.clone())); }
+            Expr::Float(val, _) => { self.chunk().emit_const(StackSlot::Float(*val)); }
+            Expr::Str(val, _) => { self.chunk().emit_const(StackSlot::from(val.as_str())); }
+            Expr::Char(val, _) => { self.chunk().emit_const(StackSlot::Char(*val)); }
+            Expr::Bool(val, _) => { self.chunk().emit_const(StackSlot::Bool(*val)); }
+            Expr::Nil(_) => { self.chunk().emit_const(StackSlot::Nil); }
+            Expr::Var(name, loc) => {
+                match self.resolve(name) {
+                    Some(Variable::Global(index, _)) => { self.chunk().load_global(index); }
+                    Some(Variable::Local(index, _)) => self.chunk().load_local(index),
+                    Some(Variable::Upvalue(index, _)) => self.chunk().load_upvalue(index),
+                    None => match self.natives.iter().position(|n| n.name == *name) {
+                        Some(index) => { self.chunk().emit_const(StackSlot::Native(index)); }
+                        None if self.aliases.contains_key(name) =>
+                            return Err(CompileError::new(format!("Module `{}` can only be used to access its exports", name), loc.clone())),
+                        None => return Err(CompileError::new(format!("Variable `{}` is not defined", name), loc.clone()))
+                    }
+                }
+            }
+            Expr::Unary(op, operand, loc) => {
+                self.generate_expr(operand)?;
+                let opcode = match op {
+                    UnaryOp::Neg => OpCode::Neg,
+                    UnaryOp::Not => OpCode::Not
+                };
+                self.chunk().mark(loc);
+                self.chunk().emit_byte(opcode as u8);
+            }
+            Expr::Binary(op, lhs, rhs, loc) => {
+                self.generate_operand(lhs)?;
+                self.generate_expr(rhs)?;
+                self.release_temps(1);
+                let opcode = match op {
+                    BinaryOp::Add => match self.num_types.get(self.module).and_then(|types| types.get(loc)) {
+                        Some(NumType::Int) => OpCode::AddInt,
+                        Some(NumType::Float) => OpCode::AddFloat,
+                        None => OpCode::Add
+                    },
+                    BinaryOp::Sub => OpCode::Sub,
+                    BinaryOp::Mul => OpCode::Mul,
+                    BinaryOp::Div => OpCode::Div,
+                    BinaryOp::FloorDiv => OpCode::FloorDiv,
+                    BinaryOp::Rem => OpCode::Rem,
+                    BinaryOp::Eq => OpCode::Eq,
+                    BinaryOp::NotEq => OpCode::NotEq,
+                    BinaryOp::Lt => OpCode::Lt,
+                    BinaryOp::LtEq => OpCode::LtEq,
+                    BinaryOp::Gt => OpCode::Gt,
+                    BinaryOp::GtEq => OpCode::GtEq
+                };
+                self.chunk().mark(loc);
+                self.chunk().emit_byte(opcode as u8);
+            }
+            Expr::Logical(op, lhs, rhs, _) => self.generate_logical(*op, lhs, rhs)?,
+            Expr::Call(callee, args, loc) => self.generate_call(callee, args, loc)?,
+            Expr::MethodCall(receiver, name, args, loc) => self.generate_method_call(receiver, name, args, loc)?,
+            Expr::List(items, _) => {
+                for item in items {
+                    self.generate_operand(item)?;
+                }
+                self.release_temps(items.len());
+                self.chunk().emit_make_list(items.len());
+            }
+            Expr::Map(entries, _) => {
+                for (key, value) in entries {
+                    self.generate_operand(key)?;
+                    self.generate_operand(value)?;
+                }
+                self.release_temps(entries.len() * 2);
+                self.chunk().emit_make_map(entries.len());
+            }
+            Expr::Index(collection, index, loc) => {
+                self.generate_operand(collection)?;
+                self.generate_expr(index)?;
+                self.release_temps(1);
+                self.chunk().mark(loc);
+                self.chunk().emit_byte(OpCode::Index as u8);
+            }
+            Expr::Get(obj, name, loc) => {
+                if let Some(index) = self.resolve_export(obj, name, loc)? {
+                    self.chunk().load_global(index);
+                    return Ok(());
+                }
+                if let Some((def, variant)) = self.resolve_variant(obj, name)? {
+                    return self.generate_variant(def, variant, &[], loc);
+                }
+                self.generate_expr(obj)?;
+                self.chunk().mark(loc);
+                self.emit_get_field(name);
+            }
+            Expr::Match(value, arms, loc) => self.generate_match(value, arms, loc)?,
+            Expr::Propagate(value, loc) => self.generate_propagate(value, loc)?,
+            Expr::Interp(parts, _) => {
+                for part in parts {
+                    self.generate_operand(part)?;
+                }
+                self.release_temps(parts.len());
+                self.chunk().emit_concat(parts.len());
+            }
+            Expr::StructLit(name, fields, loc) => self.generate_struct_lit(name, fields, loc)?,
+            Expr::Function(def) => self.generate_function(def, FunctionKind::Function)?,
+            Expr::SelfRef(loc) => self.generate_self(loc)?,
+            Expr::Super(name, loc) => {
+                self.generate_self(loc)?;
+                self.generate_super(loc)?;
+                self.chunk().emit_get_super(name);
+            }
+        }
+        return Ok(());
+    }
+
+    /// `a && b` and `a || b` only evaluate `b` when `a` does not already
+    /// decide the result.
+    fn generate_logical(&mut self, op: LogicalOp, lhs: &Expr, rhs: &Expr) -> Result<(), CompileError> {
+        self.generate_expr(lhs)?;
+        let jmp_if = self.chunk().emit_jmp_if(0);
+        match op {
+            LogicalOp::And => {
+                self.chunk().emit_const(StackSlot::Bool(false));
+                let jmp_end = self.chunk().emit_jmp(0);
+                let rhs_start = self.here();
+                self.chunk().patch_jmp(jmp_if, rhs_start);
+                self.generate_expr(rhs)?;
+                let end = self.here();
+                self.chunk().patch_jmp(jmp_end, end);
+            }
+            LogicalOp::Or => {
+                self.generate_expr(rhs)?;
+                let jmp_end = self.chunk().emit_jmp(0);
+                let true_start = self.here();
+                self.chunk().patch_jmp(jmp_if, true_start);
+                self.chunk().emit_const(StackSlot::Bool(true));
+                let end = self.here();
+                self.chunk().patch_jmp(jmp_end, end);
+            }
+        }
+        return Ok(());
+    }
+
+    /// Calls to a native that is not shadowed by a variable are bound at
+    /// compile time; any other callee is evaluated and called at runtime.
+    fn generate_call(&mut self, callee: &Expr, args: &[Expr], loc: &Location) -> Result<(), CompileError> {
+        if let Expr::Var(name, _) = callee && self.resolve(name).is_none() {
+            if let Some(variant) = self.enums[RESULT_ENUM].variant_index(name) {
+                return self.generate_variant(RESULT_ENUM, variant, args, loc);
+            }
+            return self.generate_native_call(name, None, args, loc);
+        }
+        if let Expr::Super(name, super_loc) = callee {
+            self.generate_self(super_loc)?;
+            self.state_mut().temps += 1;
+            self.generate_args(args, loc)?;
+            self.generate_super(super_loc)?;
+            self.release_temps(1);
+            self.chunk().emit_super_invoke(name, args.len() as u8);
+            return Ok(());
+        }
+        self.generate_operand(callee)?;
+        self.generate_dynamic_call(args, loc)?;
+        self.release_temps(1);
+        return Ok(());
+    }
+
+    /// `obj.name(args)` is bound at compile time to the native `name` with
+    /// `obj` as its first argument when no class declares a method `name`;
+    /// otherwise `Invoke` dispatches on the receiver at runtime.
+    fn generate_method_call(&mut self, receiver: &Expr, name: &str, args: &[Expr], loc: &Location) -> Result<(), CompileError> {
+        if let Some(index) = self.resolve_export(receiver, name, loc)? {
+            self.chunk().load_global(index);
+            self.state_mut().temps += 1;
+            self.generate_dynamic_call(args, loc)?;
+            self.release_temps(1);
+            return Ok(());
+        }
+        if let Some((def, variant)) = self.resolve_variant(receiver, name)? {
+            return self.generate_variant(def, variant, args, loc);
+        }
+        if !self.methods.contains(name) && self.natives.iter().any(|n| n.name == name) {
+            return self.generate_native_call(name, Some(receiver), args, loc);
+        }
+        self.generate_operand(receiver)?;
+        self.generate_args(args, loc)?;
+        self.release_temps(1);
+        self.chunk().emit_invoke(name, args.len() as u8);
+        return Ok(());
+    }
+
+    /// `value?` evaluates to the payload of an `Ok`, and returns an `Err`
+    /// from the current function, running any pending `finally` blocks.
+    /// The result is kept in a temporary slot while it is tested.
+    fn generate_propagate(&mut self, value: &Expr, loc: &Location) -> Result<(), CompileError> {
+        match self.state().kind {
+            FunctionKind::Script => return Err(CompileError::new("`?` can only be used inside a function", loc.clone())),
+            FunctionKind::Initializer => return Err(CompileError::new("`?` cannot be used in an initialiser", loc.clone())),
+            _ => {}
+        }
+        let slot = self.state().locals.len() + self.state().temps;
+        self.generate_operand(value)?;
+        self.chunk().load_local(slot);
+        self.chunk().emit_is_variant(RESULT_ENUM, OK_VARIANT);
+        let jmp_not_ok = self.chunk().emit_jmp_if_false(0);
+        self.chunk().emit_op_index(OpCode::GetPayload, 0);
+        let jmp_end = self.chunk().emit_jmp(0);
+
+        let not_ok = self.here();
+        self.chunk().patch_jmp(jmp_not_ok, not_ok);
+        self.chunk().load_local(slot);
+        self.chunk().emit_is_variant(RESULT_ENUM, ERR_VARIANT);
+        let jmp_invalid = self.chunk().emit_jmp_if_false(0);
+        self.chunk().load_local(slot);
+        self.state_mut().temps += 1;
+        self.generate_pending_finally()?;
+        self.release_temps(1);
+        self.chunk().emit_byte(OpCode::Ret as u8);
+
+        let invalid = self.here();
+        self.chunk().patch_jmp(jmp_invalid, invalid);
+        self.chunk().emit_const(StackSlot::from("`?` expects a Result"));
+        self.chunk().mark(loc);
+        self.chunk().emit_byte(OpCode::Throw as u8);
+
+        let end = self.here();
+        self.chunk().patch_jmp(jmp_end, end);
+        self.release_temps(1);
+        return Ok(());
+    }
+
+    /// The class is bound to its name before its methods are generated so
+    /// that they can refer to it. A subclass keeps its superclass in a
+    /// hidden `super` local that the methods capture.
+    fn generate_class(&mut self, def: &ClassDef) -> Result<(), CompileError> {
+        self.chunk().emit_class(&def.name);
+        let index = self.add_global(&def.name, true);
+        self.chunk().store_global(index);
+
+        if let Some((superclass, loc)) = &def.superclass {
+            if *superclass == def.name {
+                return Err(CompileError::new("A class cannot inherit from itself", loc.clone()));
+            }
+            self.begin_scope();
+            self.generate_expr(&Expr::Var(superclass.clone(), loc.clone()))?;
+            self.add_local("super", true);
+            self.chunk().load_global(index);
+            self.chunk().emit_byte(OpCode::Inherit as u8);
+        }
+
+        self.chunk().load_global(index);
+        for method in &def.methods {
+            let name = method.name.as_deref().unwrap_or_default();
+            let kind = if name == "init" { FunctionKind::Initializer } else { FunctionKind::Method };
+            self.generate_function(method, kind)?;
+            self.chunk().emit_method(name);
+        }
+        self.chunk().emit_byte(OpCode::Pop as u8);
+
+        if def.superclass.is_some() {
+            self.end_scope();
+        }
+        return Ok(());
+    }
+
+    /// Resolves `Enum.Variant` when `obj` names an enum that is not shadowed
+    /// by a variable.
+    fn resolve_variant(&mut self, obj: &Expr, name: &str) -> Result<Option<(usize, usize)>, CompileError> {
+        let enum_name = match obj {
+            Expr::Var(enum_name, _) => enum_name,
+            _ => return Ok(None)
+        };
+        let def = match self.enums.iter().position(|e| e.name == *enum_name) {
+            Some(def) if self.resolve(enum_name).is_none() => def,
+            _ => return Ok(None)
+        };
+        return match self.enums[def].variant_index(name) {
+            Some(variant) => Ok(Some((def, variant))),
+            None => Err(CompileError::new(format!("Enum `{}` has no variant `{}`", enum_name, name), obj.location()))
+        };
+    }
+
+    fn generate_variant(&mut self, def: usize, variant: usize, payload: &[Expr], loc: &Location) -> Result<(), CompileError> {
+        let arity = self.enums[def].variants[variant].arity;
+        if payload.len() != arity {
+            let name = format!("{}.{}", self.enums[def].name, self.enums[def].variants[variant].name);
+            return Err(CompileError::new(format!("`{}` expects {} payload values, got {}", name, arity, payload.len()), loc.clone()));
+        }
+        for value in payload {
+            self.generate_operand(value)?;
+        }
+        self.release_temps(payload.len());
+        self.chunk().emit_make_variant(def, variant);
+        return Ok(());
+    }
+
+    /// The matched value is kept in a hidden local. Each arm tests its
+    /// pattern against it, jumping to the next arm on failure, then binds
+    /// its variables and stores its result over the matched value, which is
+    /// left on the stack as the value of the match. Arms that only test int
+    /// literals are dispatched through a jump table instead.
+    fn generate_match(&mut self, value: &Expr, arms: &[MatchArm], loc: &Location) -> Result<(), CompileError> {
+        self.check_exhaustive(arms, loc);
+        self.begin_scope();
+        self.generate_expr(value)?;
+        let slot = self.add_local("(match)", true);
+        let mut end_jumps = Vec::new();
+        match Self::jump_table(arms) {
+            Some(table) => self.generate_match_table(slot, arms, &table, &mut end_jumps)?,
+            None => {
+                for arm in arms {
+                    self.generate_arm(slot, arm, &mut end_jumps)?;
+                }
+                self.chunk().load_local(slot);
+                self.chunk().emit_byte(OpCode::NoMatch as u8);
+            }
+        }
+        let end = self.here();
+        for jump in end_jumps {
+            self.chunk().patch_jmp(jump, end);
+        }
+        self.state_mut().locals.pop();
+        self.state_mut().scope_depth -= 1;
+        return Ok(());
+    }
+
+    fn generate_arm(&mut self, slot: usize, arm: &MatchArm, end_jumps: &mut Vec<usize>) -> Result<(), CompileError> {
+        let mut fail_jumps = Vec::new();
+        self.generate_pattern_test(&arm.pattern, slot, &mut Vec::new(), &mut fail_jumps)?;
+        self.begin_scope();
+        self.generate_pattern_bindings(&arm.pattern, slot, &mut Vec::new(), &mut Vec::new())?;
+        let guard_jump = match &arm.guard {
+            Some(guard) => {
+                self.generate_expr(guard)?;
+                Some(self.chunk().emit_jmp_if_false(0))
+            }
+            None => None
+        };
+        self.generate_arm_body(&arm.body, slot)?;
+        if let Some(guard_jump) = guard_jump {
+            self.emit_scope_exit();
+            end_jumps.push(self.chunk().emit_jmp(0));
+            let guard_failed = self.here();
+            self.chunk().patch_jmp(guard_jump, guard_failed);
+        }
+        self.end_scope();
+        if guard_jump.is_none() {
+            end_jumps.push(self.chunk().emit_jmp(0));
+        }
+        let next_arm = self.here();
+        for jump in fail_jumps {
+            self.chunk().patch_jmp(jump, next_arm);
+        }
+        return Ok(());
+    }
+
+    fn generate_arm_body(&mut self, body: &ArmBody, slot: usize) -> Result<(), CompileError> {
+        match body {
+            ArmBody::Expr(expr) => self.generate_expr(expr)?,
+            ArmBody::Block(block) => {
+                self.generate_stmt(block)?;
+                self.chunk().emit_const(StackSlot::Nil);
+            }
+        }
+        self.chunk().store_local(slot);
+        return Ok(());
+    }
+
+    /// Returns the jump table for `arms` when every arm but an optional
+    /// catch-all last one matches unguarded int literals, and the values
+    /// are dense enough for a table to pay off.
+    fn jump_table(arms: &[MatchArm]) -> Option<JumpTable> {
+        let mut cases = Vec::new();
+        let mut default = None;
+        for (i, arm) in arms.iter().enumerate() {
+            if arm.guard.is_some() {
+                return None;
+            }
+            let alternatives = match &arm.pattern {
+                Pattern::Or(alternatives, _) => alternatives.as_slice(),
+                pattern => std::slice::from_ref(pattern)
+            };
+            let mut values = Vec::new();
+            for pattern in alternatives {
+                match pattern {
+                    Pattern::Literal(Expr::Int(val, _)) => values.push(*val),
+                    pattern if pattern.is_irrefutable() && i + 1 == arms.len() && alternatives.len() == 1 => default = Some(i),
+                    _ => return None
+                }
+            }
+            cases.push(values);
+        }
+        let values: Vec<i64> = cases.iter().flatten().copied().collect();
+        let min = *values.iter().min()?;
+        let max = *values.iter().max()?;
+        let span = max.checked_sub(min)?.checked_add(1)?;
+        if values.len() < 3 || span > 1024 || span > values.len() as i64 * 4 {
+            return None;
+        }
+        return Some(JumpTable { min, count: span as usize, cases, default });
+    }
+
+    fn generate_match_table(&mut self, slot: usize, arms: &[MatchArm], table: &JumpTable, end_jumps: &mut Vec<usize>) -> Result<(), CompileError> {
+        self.chunk().load_local(slot);
+        let default_operand = self.chunk().emit_jmp_table(table.min, table.count);
+        let mut targets = vec![None; table.count];
+        let mut default_target = None;
+        for (i, arm) in arms.iter().enumerate() {
+            let start = self.here();
+            if table.default == Some(i) {
+                default_target = Some(start);
+            }
+            for val in &table.cases[i] {
+                let target = &mut targets[(val - table.min) as usize];
+                if target.is_none() {
+                    *target = Some(start);
+                }
+            }
+            self.begin_scope();
+            self.generate_pattern_bindings(&arm.pattern, slot, &mut Vec::new(), &mut Vec::new())?;
+            self.generate_arm_body(&arm.body, slot)?;
+            self.end_scope();
+            end_jumps.push(self.chunk().emit_jmp(0));
+        }
+        let default_target = match default_target {
+            Some(target) => target,
+            None => {
+                let no_match = self.here();
+                self.chunk().load_local(slot);
+                self.chunk().emit_byte(OpCode::NoMatch as u8);
+                no_match
+            }
+        };
+        self.chunk().patch_jmp(default_operand, default_target);
+        for (i, target) in targets.into_iter().enumerate() {
+            self.chunk().patch_jmp(default_operand + MAX_WIDTH * (i + 1), target.unwrap_or(default_target));
+        }
+        return Ok(());
+    }
+
+    fn load_path(&mut self, slot: usize, path: &[PathStep]) {
+        self.chunk().load_local(slot);
+        for step in path {
+            match step {
+                PathStep::Payload(index) => { self.chunk().emit_op_index(OpCode::GetPayload, *index); }
+                PathStep::Field(name, def, offset) => { self.chunk().emit_get_field_at(name, *def, *offset); }
+                PathStep::Item(index) => {
+                    self.chunk().emit_const(StackSlot::Int(*index as i64));
+                    self.chunk().emit_byte(OpCode::Index as u8);
+                }
+            }
+        }
+    }
+
+    /// Emits the tests of `pattern` against the value at `path`, adding a
+    /// jump to `fail_jumps` for every test that can fail.
+    fn generate_pattern_test(&mut self, pattern: &Pattern, slot: usize, path: &mut Vec<PathStep>, fail_jumps: &mut Vec<usize>) -> Result<(), CompileError> {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Binding(..) => {}
+            Pattern::Literal(expr) => {
+                self.load_path(slot, path);
+                self.generate_expr(expr)?;
+                self.chunk().emit_byte(OpCode::Eq as u8);
+                fail_jumps.push(self.chunk().emit_jmp_if_false(0));
+            }
+            Pattern::Range(lo, hi, inclusive, _) => {
+                self.load_path(slot, path);
+                self.generate_expr(lo)?;
+                self.generate_expr(hi)?;
+                self.chunk().emit_in_range(*inclusive);
+                fail_jumps.push(self.chunk().emit_jmp_if_false(0));
+            }
+            Pattern::Variant(enum_name, name, payload, loc) => {
+                let (def, variant) = self.pattern_variant(enum_name, name, payload.len(), loc)?;
+                self.load_path(slot, path);
+                self.chunk().emit_is_variant(def, variant);
+                fail_jumps.push(self.chunk().emit_jmp_if_false(0));
+                for (i, sub) in payload.iter().enumerate() {
+                    path.push(PathStep::Payload(i));
+                    self.generate_pattern_test(sub, slot, path, fail_jumps)?;
+                    path.pop();
+                }
+            }
+            Pattern::Struct(name, fields, loc) => {
+                let def = match self.structs.iter().position(|s| s.name == *name) {
+                    Some(def) => def,
+                    None => return Err(CompileError::new(format!("Struct `{}` is not defined", name), loc.clone()))
+                };
+                self.load_path(slot, path);
+                self.chunk().emit_op_index(OpCode::IsStruct, def);
+                fail_jumps.push(self.chunk().emit_jmp_if_false(0));
+                for (field, sub) in fields {
+                    let offset = match self.structs[def].field_offset(field) {
+                        Some(offset) => offset,
+                        None => return Err(CompileError::new(format!("Struct `{}` has no field `{}`", name, field), sub.location()))
+                    };
+                    path.push(PathStep::Field(field.clone(), def, offset));
+                    self.generate_pattern_test(sub, slot, path, fail_jumps)?;
+                    path.pop();
+                }
+            }
+            Pattern::List(items, _) => {
+                self.load_path(slot, path);
+                self.chunk().emit_op_index(OpCode::IsList, items.len());
+                fail_jumps.push(self.chunk().emit_jmp_if_false(0));
+                for (i, sub) in items.iter().enumerate() {
+                    path.push(PathStep::Item(i));
+                    self.generate_pattern_test(sub, slot, path, fail_jumps)?;
+                    path.pop();
+                }
+            }
+            Pattern::Or(alternatives, loc) => {
+                if alternatives.iter().any(Self::has_bindings) {
+                    return Err(CompileError::new("Alternatives of a `|` pattern cannot bind variables", loc.clone()));
+                }
+                let mut matched_jumps = Vec::new();
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i + 1 == alternatives.len() {
+                        self.generate_pattern_test(alternative, slot, path, fail_jumps)?;
+                        break;
+                    }
+                    let mut next_jumps = Vec::new();
+                    self.generate_pattern_test(alternative, slot, path, &mut next_jumps)?;
+                    matched_jumps.push(self.chunk().emit_jmp(0));
+                    let next = self.here();
+                    for jump in next_jumps {
+                        self.chunk().patch_jmp(jump, next);
+                    }
+                }
+                let matched = self.here();
+                for jump in matched_jumps {
+                    self.chunk().patch_jmp(jump, matched);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Declares a local for every variable bound by `pattern`, once its
+    /// tests have passed.
+    fn generate_pattern_bindings(&mut self, pattern: &Pattern, slot: usize, path: &mut Vec<PathStep>, names: &mut Vec<String>) -> Result<(), CompileError> {
+        match pattern {
+            Pattern::Binding(name, loc) => {
+                if names.contains(name) {
+                    return Err(CompileError::new(format!("Variable `{}` is bound more than once in the pattern", name), loc.clone()));
+                }
+                names.push(name.clone());
+                self.load_path(slot, path);
+                self.add_local(name, false);
+            }
+            Pattern::Variant(enum_name, name, payload, loc) => {
+                self.pattern_variant(enum_name, name, payload.len(), loc)?;
+                for (i, sub) in payload.iter().enumerate() {
+                    path.push(PathStep::Payload(i));
+                    self.generate_pattern_bindings(sub, slot, path, names)?;
+                    path.pop();
+                }
+            }
+            Pattern::Struct(name, fields, _) => {
+                let def = self.structs.iter().position(|s| s.name == *name).unwrap();
+                for (field, sub) in fields {
+                    let offset = self.structs[def].field_offset(field).unwrap();
+                    path.push(PathStep::Field(field.clone(), def, offset));
+                    self.generate_pattern_bindings(sub, slot, path, names)?;
+                    path.pop();
+                }
+            }
+            Pattern::List(items, _) => {
+                for (i, sub) in items.iter().enumerate() {
+                    path.push(PathStep::Item(i));
+                    self.generate_pattern_bindings(sub, slot, path, names)?;
+                    path.pop();
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Literal(_) | Pattern::Range(..) | Pattern::Or(..) => {}
+        }
+        return Ok(());
+    }
+
+    fn has_bindings(pattern: &Pattern) -> bool {
+        match pattern {
+            Pattern::Binding(..) => true,
+            Pattern::Variant(_, _, payload, _) => payload.iter().any(Self::has_bindings),
+            Pattern::Struct(_, fields, _) => fields.iter().any(|(_, sub)| Self::has_bindings(sub)),
+            Pattern::List(items, _) => items.iter().any(Self::has_bindings),
+            Pattern::Or(alternatives, _) => alternatives.iter().any(Self::has_bindings),
+            Pattern::Wildcard(_) | Pattern::Literal(_) | Pattern::Range(..) => false
+        }
+    }
+
+    fn pattern_variant(&self, enum_name: &str, name: &str, arity: usize, loc: &Location) -> Result<(usize, usize), CompileError> {
+        let def = match self.enums.iter().position(|e| e.name == enum_name) {
+            Some(def) => def,
+            None => return Err(CompileError::new(format!("Enum `{}` is not defined", enum_name), loc.clone()))
+        };
+        let variant = match self.enums[def].variant_index(name) {
+            Some(variant) => variant,
+            None => return Err(CompileError::new(format!("Enum `{}` has no variant `{}`", enum_name, name), loc.clone()))
+        };
+        let expected = self.enums[def].variants[variant].arity;
+        if arity != expected {
+            return Err(CompileError::new(format!("`{}.{}` has {} payload values, the pattern has {}", enum_name, name, expected, arity), loc.clone()));
+        }
+        return Ok((def, variant));
+    }
+
+    /// Warns when a match over enum values has no catch-all arm and misses
+    /// some variants. Guarded arms and arms with refutable payload patterns
+    /// do not count as covering their variant.
+    fn check_exhaustive(&mut self, arms: &[MatchArm], loc: &Location) {
+        if arms.iter().any(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable()) {
+            return;
+        }
+        let mut patterns = Vec::new();
+        for arm in arms {
+            match &arm.pattern {
+                Pattern::Or(alternatives, _) => patterns.extend(alternatives.iter().map(|p| (p, arm.guard.is_some()))),
+                pattern => patterns.push((pattern, arm.guard.is_some()))
+            }
+        }
+        let enum_name = match patterns.iter().find_map(|(p, _)| match p {
+            Pattern::Variant(enum_name, ..) => Some(enum_name),
+            _ => None
+        }) {
+            Some(enum_name) => enum_name,
+            None => return
+        };
+        let def = match self.enums.iter().find(|e| e.name == *enum_name) {
+            Some(def) => def,
+            None => return
+        };
+        let missing: Vec<String> = def.variants.iter()
+            .filter(|variant| !patterns.iter().any(|(p, guarded)| match p {
+                Pattern::Variant(e, v, payload, _) => !guarded && e == enum_name && *v == variant.name &&
+                    payload.iter().all(Pattern::is_irrefutable),
+                _ => false
+            }))
+            .map(|variant| format!("`{}.{}`", def.name, variant.name))
+            .collect();
+        if !missing.is_empty() {
+            let message = format!("Non-exhaustive match on `{}`: {} not covered", def.name, missing.join(", "));
+            let mut warning = CompileWarning::new(message, loc.clone());
+            warning.file = self.file.clone();
+            self.warnings.push(warning);
+        }
+    }
+
+    fn generate_self(&mut self, loc: &Location) -> Result<(), CompileError> {
+        match self.resolve("self") {
+            Some(Variable::Local(index, _)) => self.chunk().load_local(index),
+            Some(Variable::Upvalue(index, _)) => self.chunk().load_upvalue(index),
+            _ => return Err(CompileError::new("`self` can only be used inside methods", loc.clone()))
+        }
+        return Ok(());
+    }
+
+    fn generate_super(&mut self, loc: &Location) -> Result<(), CompileError> {
+        match self.resolve("super") {
+            Some(Variable::Local(index, _)) => self.chunk().load_local(index),
+            Some(Variable::Upvalue(index, _)) => self.chunk().load_upvalue(index),
+            _ => return Err(CompileError::new("`super` can only be used inside methods of a subclass", loc.clone()))
+        }
+        return Ok(());
+    }
+
+    /// Field values are evaluated in source order; `MakeStruct` places each
+    /// one at the offset of its field.
+    fn generate_struct_lit(&mut self, name: &str, fields: &[(String, Expr)], loc: &Location) -> Result<(), CompileError> {
+        let def = match self.structs.iter().position(|s| s.name == name) {
+            Some(def) => def,
+            None => return Err(CompileError::new(format!("Struct `{}` is not defined", name), loc.clone()))
+        };
+        let mut offsets = Vec::new();
+        for (field, value) in fields {
+            match self.structs[def].field_offset(field) {
+                Some(offset) => offsets.push(offset),
+                None => return Err(CompileError::new(format!("Struct `{}` has no field `{}`", name, field), value.location()))
+            }
+            self.generate_operand(value)?;
+        }
+        self.release_temps(fields.len());
+        if let Some(missing) = self.structs[def].fields.iter().find(|f| !fields.iter().any(|(given, _)| given == *f)) {
+            return Err(CompileError::new(format!("Missing field `{}` in `{}` literal", missing, name), loc.clone()));
+        }
+        self.chunk().emit_make_struct(def, &offsets);
+        return Ok(());
+    }
+
+    /// Emits `args` and a `Call` of the callee already on the stack.
+    fn generate_dynamic_call(&mut self, args: &[Expr], loc: &Location) -> Result<(), CompileError> {
+        self.generate_args(args, loc)?;
+        self.chunk().emit_call(args.len() as u8);
+        return Ok(());
+    }
+
+    fn generate_args(&mut self, args: &[Expr], loc: &Location) -> Result<(), CompileError> {
+        if args.len() > u8::MAX as usize {
+            return Err(CompileError::new("Too many arguments in function call", loc.clone()));
+        }
+        for arg in args {
+            self.generate_operand(arg)?;
+        }
+        self.release_temps(args.len());
+        self.chunk().mark(loc);
+        return Ok(());
+    }
+
+    /// Method calls on built-in values resolve to the native function of
+    /// the same name with the receiver as its first argument.
+    fn generate_native_call(&mut self, name: &str, receiver: Option<&Expr>, args: &[Expr], loc: &Location) -> Result<(), CompileError> {
+        let index = match self.natives.iter().position(|n| n.name == name) {
+            Some(index) => index,
+            None => return Err(CompileError::new(format!("Function `{}` is not defined", name), loc.clone()))
+        };
+        let argc = args.len() + receiver.is_some() as usize;
+        let arity = self.natives[index].arity;
+        if arity != VARIADIC && argc != arity {
+            return Err(CompileError::new(format!("`{}` expects {} arguments, got {}", name, arity, argc), loc.clone()));
+        }
+        if argc > u8::MAX as usize {
+            return Err(CompileError::new("Too many arguments in function call", loc.clone()));
+        }
+
+        if let Some(receiver) = receiver {
+            self.generate_operand(receiver)?;
+        }
+        for arg in args {
+            self.generate_operand(arg)?;
+        }
+        self.release_temps(argc);
+        self.chunk().mark(loc);
+        self.chunk().emit_call_native(index, argc as u8);
+        return Ok(());
+    }
+}
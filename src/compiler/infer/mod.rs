@@ -0,0 +1,796 @@
+use std::{collections::{HashMap, HashSet}, fmt};
+
+use crate::compiler::{
+    error::CompileError,
+    location::Location,
+    parser::ast::{ArmBody, BinaryOp, Expr, FunctionDef, Pattern, Stmt, TypeAnn, UnaryOp}
+};
+
+/// A monotype. `Var` is a type variable, resolved through the substitution
+/// held by `Inferer`.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Var(usize),
+    Int,
+    BigInt,
+    Float,
+    Bool,
+    Str,
+    Char,
+    List(Box<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    Fn(Vec<Ty>, Box<Ty>),
+    Struct(String)
+}
+
+/// The concrete type an arithmetic operator was inferred to work on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumType {
+    Int,
+    Float
+}
+
+// Sets of the base types an operator accepts, as bit masks.
+const INT: u8 = 1;
+const FLOAT: u8 = 2;
+const STR: u8 = 4;
+const CHAR: u8 = 8;
+const BIGINT: u8 = 16;
+const NUMERIC: u8 = INT | FLOAT | BIGINT;
+const ADDABLE: u8 = NUMERIC | STR;
+const ORDERED: u8 = ADDABLE | CHAR;
+
+/// A type variable: its binding, if unified with a type, and the base types
+/// it is restricted to by the operators applied to it.
+#[derive(Clone)]
+struct TyVar {
+    bound: Option<Ty>,
+    allowed: Option<u8>
+}
+
+/// A type generalised over `vars`, as given to named functions.
+#[derive(Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Ty
+}
+
+impl Scheme {
+    fn mono(ty: Ty) -> Self {
+        Self { vars: Vec::new(), ty }
+    }
+}
+
+/// Hindley-Milner type inference over the whole program, run in strict
+/// mode. Named functions are generalised, so that they can be used at
+/// several types. The built-in list, map and string functions have fixed
+/// signatures, whether called directly or as methods. Values the inference
+/// does not model, such as class instances, enum values and the results of
+/// other host functions, get a fresh type variable at every use.
+pub struct Inferer {
+    vars: Vec<TyVar>,
+    scopes: Vec<HashMap<String, Scheme>>,
+    structs: HashMap<String, Vec<(String, Ty)>>,
+    return_types: Vec<Ty>,
+    additions: Vec<(Location, Ty, Ty)>,
+    methods: HashSet<String>
+}
+
+impl Default for Inferer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inferer {
+    pub fn new() -> Self {
+        Self {
+            vars: Vec::new(),
+            scopes: vec![HashMap::new()],
+            structs: HashMap::new(),
+            return_types: Vec::new(),
+            additions: Vec::new(),
+            methods: HashSet::new()
+        }
+    }
+
+    /// The names of the methods declared by classes. `obj.name()` may call
+    /// one of them at runtime, so it is not checked against a built-in.
+    pub fn with_methods(mut self, methods: HashSet<String>) -> Self {
+        self.methods = methods;
+        return self;
+    }
+
+    /// Infers the types of `stmts`, returning the operand type of every `+`
+    /// whose operands are known to be both ints or both floats, keyed by the
+    /// location of the operator.
+    pub fn infer(mut self, stmts: &[Stmt]) -> Result<HashMap<Location, NumType>, CompileError> {
+        for stmt in stmts {
+            match stmt.unexported() {
+                Stmt::Struct(name, fields, _) => {
+                    let fields = fields.iter().map(|field| (field.clone(), self.fresh())).collect();
+                    self.structs.insert(name.clone(), fields);
+                }
+                Stmt::Fn(def) => {
+                    let ty = self.fresh();
+                    self.declare(def.name.as_ref().unwrap(), Scheme::mono(ty));
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts {
+            self.infer_stmt(stmt)?;
+        }
+        let mut additions = HashMap::new();
+        for (loc, lhs, rhs) in std::mem::take(&mut self.additions) {
+            match (self.resolve(&lhs), self.resolve(&rhs)) {
+                (Ty::Int, Ty::Int) => { additions.insert(loc, NumType::Int); }
+                (Ty::Float, Ty::Float) => { additions.insert(loc, NumType::Float); }
+                _ => {}
+            }
+        }
+        return Ok(additions);
+    }
+
+    fn infer_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::VarDef(name, ann, value, _, loc) => {
+                let ty = match value {
+                    Some(value) => self.infer_expr(value)?,
+                    None => self.fresh()
+                };
+                if let Some(ann) = ann {
+                    let expected = self.annotation(ann);
+                    let loc = value.as_ref().map_or(loc.clone(), Expr::location);
+                    self.unify(&expected, &ty, &loc)?;
+                }
+                self.declare(name, Scheme::mono(ty));
+            }
+            Stmt::Print(expr, _) | Stmt::Expr(expr, _) => { self.infer_expr(expr)?; }
+            Stmt::Assign(target, value, _) => {
+                let found = self.infer_expr(value)?;
+                let expected = match target {
+                    Expr::Var(name, _) => match self.lookup(name) {
+                        Some(scheme) => self.instantiate(&scheme),
+                        None => return Ok(())
+                    },
+                    target => self.infer_expr(target)?
+                };
+                self.unify(&expected, &found, &value.location())?;
+            }
+            Stmt::Block(stmts, _) => {
+                self.scopes.push(HashMap::new());
+                let result = stmts.iter().try_for_each(|stmt| self.infer_stmt(stmt));
+                self.scopes.pop();
+                result?;
+            }
+            Stmt::If(cond, then_branch, else_branch, _) => {
+                self.infer_condition(cond)?;
+                self.infer_stmt(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.infer_stmt(else_branch)?;
+                }
+            }
+            Stmt::While(cond, body, _) => {
+                self.infer_condition(cond)?;
+                self.infer_stmt(body)?;
+            }
+            Stmt::For(name, iterable, body, _) => {
+                let iterable = self.infer_expr(iterable)?;
+                let item = match self.resolve(&iterable) {
+                    Ty::List(item) => *item,
+                    Ty::Map(key, _) => *key,
+                    Ty::Str => Ty::Char,
+                    _ => self.fresh()
+                };
+                self.scopes.push(HashMap::new());
+                self.declare(name, Scheme::mono(item));
+                let result = self.infer_stmt(body);
+                self.scopes.pop();
+                result?;
+            }
+            Stmt::Fn(def) => {
+                let name = def.name.as_ref().unwrap();
+                let declared = match self.scopes.last().unwrap().get(name) {
+                    Some(scheme) if scheme.vars.is_empty() => scheme.ty.clone(),
+                    _ => {
+                        let ty = self.fresh();
+                        self.declare(name, Scheme::mono(ty.clone()));
+                        ty
+                    }
+                };
+                let ty = self.infer_function(def)?;
+                self.unify(&declared, &ty, &def.location)?;
+                self.scopes.last_mut().unwrap().remove(name);
+                let scheme = self.generalize(&ty);
+                self.declare(name, scheme);
+            }
+            Stmt::Return(value, loc) => {
+                let found = match value {
+                    Some(value) => self.infer_expr(value)?,
+                    None => self.fresh()
+                };
+                if let Some(expected) = self.return_types.last().cloned() {
+                    let loc = value.as_ref().map_or(loc.clone(), Expr::location);
+                    self.unify(&expected, &found, &loc)?;
+                }
+            }
+            Stmt::Class(def) => {
+                for method in &def.methods {
+                    self.infer_function(method)?;
+                }
+            }
+            Stmt::Export(decl, _) => self.infer_stmt(decl)?,
+            Stmt::Throw(value, _) => { self.infer_expr(value)?; }
+            Stmt::Try(body, catch, finally, _) => {
+                self.infer_stmt(body)?;
+                if let Some((name, handler)) = catch {
+                    let ty = self.fresh();
+                    self.scopes.push(HashMap::new());
+                    self.declare(name, Scheme::mono(ty));
+                    let result = self.infer_stmt(handler);
+                    self.scopes.pop();
+                    result?;
+                }
+                if let Some(finally) = finally {
+                    self.infer_stmt(finally)?;
+                }
+            }
+            Stmt::Struct(..) | Stmt::Enum(..) | Stmt::Import(..) => {}
+        }
+        return Ok(());
+    }
+
+    fn infer_function(&mut self, def: &FunctionDef) -> Result<Ty, CompileError> {
+        let mut params = Vec::new();
+        let mut scope = HashMap::new();
+        for (param, ann) in def.params.iter().zip(&def.param_types) {
+            let ty = match ann {
+                Some(ann) => self.annotation(ann),
+                None => self.fresh()
+            };
+            scope.insert(param.clone(), Scheme::mono(ty.clone()));
+            params.push(ty);
+        }
+        let ret = match &def.return_type {
+            Some(ann) => self.annotation(ann),
+            None => self.fresh()
+        };
+        self.scopes.push(scope);
+        self.return_types.push(ret.clone());
+        let result = def.body.iter().try_for_each(|stmt| self.infer_stmt(stmt));
+        self.return_types.pop();
+        self.scopes.pop();
+        result?;
+        return Ok(Ty::Fn(params, Box::new(ret)));
+    }
+
+    fn infer_condition(&mut self, cond: &Expr) -> Result<(), CompileError> {
+        let ty = self.infer_expr(cond)?;
+        return self.unify(&Ty::Bool, &ty, &cond.location());
+    }
+
+    fn infer_expr(&mut self, expr: &Expr) -> Result<Ty, CompileError> {
+        let ty = match expr {
+            Expr::Int(..) => Ty::Int,
+            Expr::BigInt(..) => Ty::BigInt,
+            Expr::Float(..) => Ty::Float,
+            Expr::Str(..) => Ty::Str,
+            Expr::Char(..) => Ty::Char,
+            Expr::Bool(..) => Ty::Bool,
+            Expr::Nil(_) | Expr::SelfRef(_) | Expr::Super(..) => self.fresh(),
+            Expr::Var(name, _) => match self.lookup(name) {
+                Some(scheme) => self.instantiate(&scheme),
+                None => match self.builtin(name) {
+                    Some(ty) => ty,
+                    None => self.fresh()
+                }
+            },
+            Expr::Unary(UnaryOp::Neg, operand, loc) => {
+                let ty = self.infer_expr(operand)?;
+                let message = format!("Cannot apply `-` to {}", self.display(&ty));
+                self.restrict(&ty, NUMERIC, loc, || message.clone())?;
+                ty
+            }
+            Expr::Unary(UnaryOp::Not, operand, _) => {
+                let ty = self.infer_expr(operand)?;
+                self.unify(&Ty::Bool, &ty, &operand.location())?;
+                Ty::Bool
+            }
+            Expr::Binary(op, lhs, rhs, loc) => {
+                let lhs = self.infer_expr(lhs)?;
+                let rhs = self.infer_expr(rhs)?;
+                self.infer_binary(*op, lhs, rhs, loc)?
+            }
+            Expr::Logical(_, lhs, rhs, _) => {
+                for operand in [lhs, rhs] {
+                    let ty = self.infer_expr(operand)?;
+                    self.unify(&Ty::Bool, &ty, &operand.location())?;
+                }
+                Ty::Bool
+            }
+            Expr::Call(callee, args, loc) => self.infer_call(callee, args, loc)?,
+            Expr::MethodCall(receiver, name, args, loc) if !self.is_unbound_var(receiver) && !self.methods.contains(name) => {
+                let callee_ty = match self.builtin(name) {
+                    Some(ty) => ty,
+                    None => self.fresh()
+                };
+                let operands: Vec<&Expr> = std::iter::once(receiver.as_ref()).chain(args).collect();
+                self.apply(name, callee_ty, &operands, loc)?
+            }
+            Expr::MethodCall(receiver, _, args, _) => {
+                if !self.is_unbound_var(receiver) {
+                    self.infer_expr(receiver)?;
+                }
+                for arg in args {
+                    self.infer_expr(arg)?;
+                }
+                self.fresh()
+            }
+            Expr::List(items, _) => {
+                let item = self.fresh();
+                for expr in items {
+                    let ty = self.infer_expr(expr)?;
+                    self.unify(&item, &ty, &expr.location())?;
+                }
+                Ty::List(Box::new(item))
+            }
+            Expr::Map(entries, _) => {
+                let (key, value) = (self.fresh(), self.fresh());
+                for (key_expr, value_expr) in entries {
+                    let ty = self.infer_expr(key_expr)?;
+                    self.unify(&key, &ty, &key_expr.location())?;
+                    let ty = self.infer_expr(value_expr)?;
+                    self.unify(&value, &ty, &value_expr.location())?;
+                }
+                Ty::Map(Box::new(key), Box::new(value))
+            }
+            Expr::Index(obj, index, loc) => {
+                let obj = self.infer_expr(obj)?;
+                let index_ty = self.infer_expr(index)?;
+                match self.resolve(&obj) {
+                    Ty::List(item) => {
+                        self.unify(&Ty::Int, &index_ty, &index.location())?;
+                        *item
+                    }
+                    Ty::Str => {
+                        self.unify(&Ty::Int, &index_ty, &index.location())?;
+                        Ty::Char
+                    }
+                    Ty::Map(key, value) => {
+                        self.unify(&key, &index_ty, &index.location())?;
+                        *value
+                    }
+                    Ty::Var(_) => self.fresh(),
+                    ty => return Err(CompileError::new(format!("Cannot index into a value of type {}", self.display(&ty)), loc.clone()))
+                }
+            }
+            Expr::Get(obj, name, loc) => {
+                if self.is_unbound_var(obj) {
+                    return Ok(self.fresh());
+                }
+                let obj = self.infer_expr(obj)?;
+                self.infer_field(&obj, name, loc)?
+            }
+            Expr::Function(def) => self.infer_function(def)?,
+            Expr::StructLit(name, fields, _) => {
+                for (field, value) in fields {
+                    let ty = self.infer_expr(value)?;
+                    if let Some(expected) = self.field_type(name, field) {
+                        self.unify(&expected, &ty, &value.location())?;
+                    }
+                }
+                Ty::Struct(name.clone())
+            }
+            Expr::Match(value, arms, _) => {
+                self.infer_expr(value)?;
+                let result = self.fresh();
+                for arm in arms {
+                    let mut scope = HashMap::new();
+                    self.declare_bindings(&arm.pattern, &mut scope);
+                    self.scopes.push(scope);
+                    let outcome = self.infer_arm(arm.guard.as_ref(), &arm.body, &result);
+                    self.scopes.pop();
+                    outcome?;
+                }
+                result
+            }
+            Expr::Propagate(value, _) => {
+                self.infer_expr(value)?;
+                self.fresh()
+            }
+            Expr::Interp(parts, _) => {
+                for part in parts {
+                    self.infer_expr(part)?;
+                }
+                Ty::Str
+            }
+        };
+        return Ok(ty);
+    }
+
+    fn infer_arm(&mut self, guard: Option<&Expr>, body: &ArmBody, result: &Ty) -> Result<(), CompileError> {
+        if let Some(guard) = guard {
+            self.infer_condition(guard)?;
+        }
+        match body {
+            ArmBody::Expr(expr) => {
+                let ty = self.infer_expr(expr)?;
+                self.unify(result, &ty, &expr.location())?;
+            }
+            ArmBody::Block(block) => self.infer_stmt(block)?
+        }
+        return Ok(());
+    }
+
+    fn declare_bindings(&mut self, pattern: &Pattern, scope: &mut HashMap<String, Scheme>) {
+        match pattern {
+            Pattern::Binding(name, _) => { scope.insert(name.clone(), Scheme::mono(self.fresh())); }
+            Pattern::Variant(_, _, items, _) | Pattern::List(items, _) | Pattern::Or(items, _) =>
+                items.iter().for_each(|item| self.declare_bindings(item, scope)),
+            Pattern::Struct(_, fields, _) => fields.iter().for_each(|(_, sub)| self.declare_bindings(sub, scope)),
+            Pattern::Wildcard(_) | Pattern::Literal(_) | Pattern::Range(..) => {}
+        }
+    }
+
+    fn infer_binary(&mut self, op: BinaryOp, lhs: Ty, rhs: Ty, loc: &Location) -> Result<Ty, CompileError> {
+        let allowed = match op {
+            BinaryOp::Eq | BinaryOp::NotEq => return Ok(Ty::Bool),
+            BinaryOp::Add => ADDABLE,
+            BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Rem => NUMERIC,
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => ORDERED
+        };
+        let symbol = Self::op_symbol(op);
+        let mismatch = |this: &Self| format!("Cannot apply `{}` to {} and {}", symbol, this.display(&lhs), this.display(&rhs));
+        let message = mismatch(self);
+        self.restrict(&lhs, allowed, loc, || message.clone())?;
+        self.restrict(&rhs, allowed, loc, || message.clone())?;
+        if op == BinaryOp::Add {
+            self.additions.push((loc.clone(), lhs.clone(), rhs.clone()));
+        }
+        let result = if allowed == ORDERED { Ty::Bool } else { lhs.clone() };
+        // numbers mix as they do at runtime: ints with floats give a float,
+        // ints with big ints a big int, and any two numbers compare
+        let (resolved_lhs, resolved_rhs) = (self.resolve(&lhs), self.resolve(&rhs));
+        let mixed = match (&resolved_lhs, &resolved_rhs) {
+            _ if resolved_lhs == resolved_rhs => None,
+            (Ty::Int | Ty::Float, Ty::Int | Ty::Float) => Some(Ty::Float),
+            (Ty::Int | Ty::BigInt, Ty::Int | Ty::BigInt) => Some(Ty::BigInt),
+            (Ty::Float | Ty::BigInt, Ty::Float | Ty::BigInt) if allowed == ORDERED => Some(Ty::Bool),
+            _ => None
+        };
+        if let Some(ty) = mixed {
+            return Ok(if allowed == ORDERED { Ty::Bool } else { ty });
+        }
+        if self.unify_types(&lhs, &rhs) {
+            return Ok(result);
+        }
+        return Err(CompileError::new(message, loc.clone()));
+    }
+
+    fn infer_call(&mut self, callee: &Expr, args: &[Expr], loc: &Location) -> Result<Ty, CompileError> {
+        let callee_ty = self.infer_expr(callee)?;
+        let name = match callee {
+            Expr::Var(name, _) => name.as_str(),
+            _ => "<fn>"
+        };
+        return self.apply(name, callee_ty, &args.iter().collect::<Vec<_>>(), loc);
+    }
+
+    /// Unifies the parameters of `callee_ty` with the types of `args` and
+    /// returns the type of the call. A method call passes its receiver as
+    /// the first argument.
+    fn apply(&mut self, name: &str, callee_ty: Ty, args: &[&Expr], loc: &Location) -> Result<Ty, CompileError> {
+        let mut arg_types = Vec::new();
+        for arg in args {
+            arg_types.push(self.infer_expr(arg)?);
+        }
+        match self.resolve(&callee_ty) {
+            Ty::Fn(params, ret) => {
+                if params.len() != args.len() {
+                    let message = format!("`{}` expects {} arguments, got {}", name, params.len(), args.len());
+                    return Err(CompileError::new(message, loc.clone()));
+                }
+                for ((param, arg_ty), arg) in params.iter().zip(&arg_types).zip(args) {
+                    self.unify(param, arg_ty, &arg.location())?;
+                }
+                return Ok(*ret);
+            }
+            Ty::Var(_) => {
+                let ret = self.fresh();
+                let ty = Ty::Fn(arg_types, Box::new(ret.clone()));
+                self.unify(&callee_ty, &ty, loc)?;
+                return Ok(ret);
+            }
+            ty => Err(CompileError::new(format!("Cannot call a value of type {}", self.display(&ty)), loc.clone()))
+        }
+    }
+
+    fn infer_field(&mut self, obj: &Ty, name: &str, loc: &Location) -> Result<Ty, CompileError> {
+        match self.resolve(obj) {
+            Ty::Struct(struct_name) => match self.field_type(&struct_name, name) {
+                Some(ty) => Ok(ty),
+                None => Err(CompileError::new(format!("Struct `{}` has no field `{}`", struct_name, name), loc.clone()))
+            },
+            Ty::Map(key, value) => {
+                self.unify(&key, &Ty::Str, loc)?;
+                Ok(*value)
+            }
+            Ty::Var(_) => {
+                let mut owners = self.structs.iter().filter(|(_, fields)| fields.iter().any(|(field, _)| field == name));
+                match (owners.next(), owners.next()) {
+                    (Some((struct_name, _)), None) => {
+                        let struct_name = struct_name.clone();
+                        self.unify(obj, &Ty::Struct(struct_name.clone()), loc)?;
+                        Ok(self.field_type(&struct_name, name).unwrap())
+                    }
+                    _ => Ok(self.fresh())
+                }
+            }
+            _ => Ok(self.fresh())
+        }
+    }
+
+    /// The signature of a built-in list, map or string function, with
+    /// fresh variables for the types of items, keys and values.
+    fn builtin(&mut self, name: &str) -> Option<Ty> {
+        let list = |item: Ty| Ty::List(Box::new(item));
+        let (params, ret) = match name {
+            "push" => {
+                let item = self.fresh();
+                (vec![list(item.clone()), item], self.fresh())
+            }
+            "pop" => {
+                let item = self.fresh();
+                (vec![list(item.clone())], item)
+            }
+            "substr" => (vec![Ty::Str, Ty::Int, Ty::Int], Ty::Str),
+            "split" => (vec![Ty::Str, Ty::Str], list(Ty::Str)),
+            "trim" | "to_upper" => (vec![Ty::Str], Ty::Str),
+            "keys" | "has" | "get" | "remove" => {
+                let (key, value) = (self.fresh(), self.fresh());
+                let map = Ty::Map(Box::new(key.clone()), Box::new(value.clone()));
+                match name {
+                    "keys" => (vec![map], list(key)),
+                    "has" => (vec![map, key], Ty::Bool),
+                    "get" => (vec![map, key, value.clone()], value),
+                    _ => (vec![map, key], value)
+                }
+            }
+            _ => return None
+        };
+        return Some(Ty::Fn(params, Box::new(ret)));
+    }
+
+    fn field_type(&self, struct_name: &str, field: &str) -> Option<Ty> {
+        return self.structs.get(struct_name)?.iter().find(|(name, _)| name == field).map(|(_, ty)| ty.clone());
+    }
+
+    /// Whether `expr` names something the inference knows nothing about,
+    /// such as an enum in `Enum.Variant`.
+    fn is_unbound_var(&self, expr: &Expr) -> bool {
+        return matches!(expr, Expr::Var(name, _) if self.lookup(name).is_none());
+    }
+
+    fn annotation(&mut self, ann: &TypeAnn) -> Ty {
+        match ann.name.as_str() {
+            "int" => Ty::Int,
+            "bigint" => Ty::BigInt,
+            "float" => Ty::Float,
+            "str" => Ty::Str,
+            "char" => Ty::Char,
+            "bool" => Ty::Bool,
+            "list" => Ty::List(Box::new(self.fresh())),
+            "map" => Ty::Map(Box::new(self.fresh()), Box::new(self.fresh())),
+            name if self.structs.contains_key(name) => Ty::Struct(name.to_string()),
+            _ => self.fresh()
+        }
+    }
+
+    fn fresh(&mut self) -> Ty {
+        self.vars.push(TyVar { bound: None, allowed: None });
+        return Ty::Var(self.vars.len() - 1);
+    }
+
+    /// Follows the bindings of type variables at the top of `ty`.
+    fn resolve(&self, ty: &Ty) -> Ty {
+        let mut ty = ty.clone();
+        while let Ty::Var(var) = ty && let Some(bound) = &self.vars[var].bound {
+            ty = bound.clone();
+        }
+        return ty;
+    }
+
+    /// Restricts `ty` to the base types in `allowed`.
+    fn restrict(&mut self, ty: &Ty, allowed: u8, loc: &Location, message: impl Fn() -> String) -> Result<(), CompileError> {
+        let ok = match self.resolve(ty) {
+            Ty::Var(var) => {
+                let allowed = self.vars[var].allowed.map_or(allowed, |current| current & allowed);
+                self.vars[var].allowed = Some(allowed);
+                allowed != 0
+            }
+            ty => Self::base_bit(&ty).is_some_and(|bit| bit & allowed != 0)
+        };
+        if !ok {
+            return Err(CompileError::new(message(), loc.clone()));
+        }
+        return Ok(());
+    }
+
+    fn base_bit(ty: &Ty) -> Option<u8> {
+        return match ty {
+            Ty::Int => Some(INT),
+            Ty::BigInt => Some(BIGINT),
+            Ty::Float => Some(FLOAT),
+            Ty::Str => Some(STR),
+            Ty::Char => Some(CHAR),
+            _ => None
+        };
+    }
+
+    fn unify(&mut self, expected: &Ty, found: &Ty, loc: &Location) -> Result<(), CompileError> {
+        if self.unify_types(expected, found) {
+            return Ok(());
+        }
+        let message = format!("Type mismatch: expected {}, found {}", self.display(expected), self.display(found));
+        return Err(CompileError::new(message, loc.clone()));
+    }
+
+    fn unify_types(&mut self, a: &Ty, b: &Ty) -> bool {
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        match (a, b) {
+            (Ty::Var(a), Ty::Var(b)) if a == b => true,
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) => self.bind(var, ty),
+            (Ty::List(a), Ty::List(b)) => self.unify_types(&a, &b),
+            (Ty::Map(ak, av), Ty::Map(bk, bv)) => self.unify_types(&ak, &bk) && self.unify_types(&av, &bv),
+            (Ty::Fn(ap, ar), Ty::Fn(bp, br)) =>
+                ap.len() == bp.len() && ap.iter().zip(&bp).all(|(a, b)| self.unify_types(a, b)) && self.unify_types(&ar, &br),
+            (a, b) => a == b
+        }
+    }
+
+    fn bind(&mut self, var: usize, ty: Ty) -> bool {
+        if self.occurs(var, &ty) {
+            return false;
+        }
+        if let Some(allowed) = self.vars[var].allowed {
+            let ok = match &ty {
+                Ty::Var(other) => {
+                    let merged = self.vars[*other].allowed.map_or(allowed, |current| current & allowed);
+                    self.vars[*other].allowed = Some(merged);
+                    merged != 0
+                }
+                ty => Self::base_bit(ty).is_some_and(|bit| bit & allowed != 0)
+            };
+            if !ok {
+                return false;
+            }
+        }
+        self.vars[var].bound = Some(ty);
+        return true;
+    }
+
+    fn occurs(&self, var: usize, ty: &Ty) -> bool {
+        return match self.resolve(ty) {
+            Ty::Var(other) => other == var,
+            Ty::List(item) => self.occurs(var, &item),
+            Ty::Map(key, value) => self.occurs(var, &key) || self.occurs(var, &value),
+            Ty::Fn(params, ret) => params.iter().any(|param| self.occurs(var, param)) || self.occurs(var, &ret),
+            _ => false
+        };
+    }
+
+    fn free_vars(&self, ty: &Ty, out: &mut Vec<usize>) {
+        match self.resolve(ty) {
+            Ty::Var(var) if !out.contains(&var) => out.push(var),
+            Ty::List(item) => self.free_vars(&item, out),
+            Ty::Map(key, value) => {
+                self.free_vars(&key, out);
+                self.free_vars(&value, out);
+            }
+            Ty::Fn(params, ret) => {
+                params.iter().for_each(|param| self.free_vars(param, out));
+                self.free_vars(&ret, out);
+            }
+            _ => {}
+        }
+    }
+
+    /// Quantifies `ty` over the type variables that are not free in the
+    /// environment.
+    fn generalize(&self, ty: &Ty) -> Scheme {
+        let mut env_vars = Vec::new();
+        for scheme in self.scopes.iter().flat_map(|scope| scope.values()) {
+            let mut vars = Vec::new();
+            self.free_vars(&scheme.ty, &mut vars);
+            env_vars.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+        }
+        let mut vars = Vec::new();
+        self.free_vars(ty, &mut vars);
+        vars.retain(|var| !env_vars.contains(var));
+        return Scheme { vars, ty: ty.clone() };
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let mut mapping = HashMap::new();
+        for var in &scheme.vars {
+            if let Ty::Var(var) = self.resolve(&Ty::Var(*var)) {
+                let fresh = self.fresh();
+                if let Ty::Var(fresh_var) = fresh {
+                    self.vars[fresh_var].allowed = self.vars[var].allowed;
+                }
+                mapping.insert(var, fresh);
+            }
+        }
+        return self.substitute(&scheme.ty, &mapping);
+    }
+
+    fn substitute(&self, ty: &Ty, mapping: &HashMap<usize, Ty>) -> Ty {
+        return match self.resolve(ty) {
+            Ty::Var(var) => mapping.get(&var).cloned().unwrap_or(Ty::Var(var)),
+            Ty::List(item) => Ty::List(Box::new(self.substitute(&item, mapping))),
+            Ty::Map(key, value) => Ty::Map(Box::new(self.substitute(&key, mapping)), Box::new(self.substitute(&value, mapping))),
+            Ty::Fn(params, ret) => Ty::Fn(
+                params.iter().map(|param| self.substitute(param, mapping)).collect(),
+                Box::new(self.substitute(&ret, mapping))
+            ),
+            ty => ty
+        };
+    }
+
+    fn declare(&mut self, name: &str, scheme: Scheme) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), scheme);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Scheme> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned();
+    }
+
+    fn op_symbol(op: BinaryOp) -> &'static str {
+        match op {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::FloorDiv => "//",
+            BinaryOp::Rem => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">="
+        }
+    }
+
+    fn display(&self, ty: &Ty) -> Display<'_> {
+        return Display { inferer: self, ty: ty.clone() };
+    }
+}
+
+/// Formats a type with its variables resolved.
+struct Display<'a> {
+    inferer: &'a Inferer,
+    ty: Ty
+}
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.inferer.resolve(&self.ty) {
+            Ty::Var(var) => write!(f, "t{}", var),
+            Ty::Int => write!(f, "int"),
+            Ty::BigInt => write!(f, "bigint"),
+            Ty::Float => write!(f, "float"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Str => write!(f, "str"),
+            Ty::Char => write!(f, "char"),
+            Ty::List(item) => write!(f, "list<{}>", self.inferer.display(&item)),
+            Ty::Map(key, value) => write!(f, "map<{}, {}>", self.inferer.display(&key), self.inferer.display(&value)),
+            Ty::Fn(params, ret) => {
+                let params: Vec<String> = params.iter().map(|param| self.inferer.display(param).to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), self.inferer.display(&ret))
+            }
+            Ty::Struct(name) => write!(f, "{}", name)
+        }
+    }
+}
@@ -1,172 +1,311 @@
+use std::num::IntErrorKind;
+
 pub mod token;
-use token::Token;
+use token::{StrPart, Token};
 
-use crate::compiler::location::Location;
+use crate::{
+    compiler::{
+        error::CompileError,
+        location::Location
+    },
+    vm::bigint::BigInt
+};
 
 pub struct Lexer {
-    src: String,
+    src: Vec<char>,
     pos: usize,
     line: u64,
     col: u64
 }
 
 impl Lexer {
-    pub fn new(src: String) -> Self {
-        Self { src: src, pos: 0, line: 1, col: 1 }
+    pub fn new(src: &str) -> Self {
+        Self { src: src.chars().collect(), pos: 0, line: 1, col: 1 }
     }
 
-    pub fn tokenize(&mut self) -> Vec<Option<Token>> {
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, CompileError> {
         let mut tokens = Vec::new();
-        while self.pos < self.src.len() {
-            tokens.push(self.next_token());
+        loop {
+            let tok = self.next_token()?;
+            let is_eof = matches!(tok, Token::Eof(_));
+            tokens.push(tok);
+            if is_eof {
+                break;
+            }
         }
-        return tokens;
+        return Ok(tokens);
     }
 
-    fn next_token(&mut self) -> Option<Token> {
-        while self.pos < self.src.len() && (self.peek(0) == ' ' || self.peek(0) == '\n') {
+    fn next_token(&mut self) -> Result<Token, CompileError> {
+        while self.pos < self.src.len() && self.peek(0).is_whitespace() {
             self.advance();
         }
 
-        let c = self.src.chars().nth(self.pos);
+        if self.pos >= self.src.len() {
+            return Ok(Token::Eof(self.location()));
+        }
+
+        let c = self.peek(0);
         match c {
-            Some(c) if c.is_digit(10)     => Some(self.tokenize_num_lit()),
-            Some(c) if c.is_alphabetic()  => Some(self.tokenize_id()),
-            Some(c) if c == '"'           => Some(self.tokenize_str_lit()),
-            Some(c) if c == '\''          => Some(self.tokenize_char_lit()),
-            Some(_c)                      => self.tokenize_op(),
-            None => None
+            c if c.is_ascii_digit()       => self.tokenize_num_lit(),
+            c if c.is_alphabetic() ||
+                c == '_'                  => Ok(self.tokenize_id()),
+            '"'                           => self.tokenize_str_lit(),
+            '\''                          => self.tokenize_char_lit(),
+            _                             => self.tokenize_op()
         }
     }
 
     fn tokenize_id(&mut self) -> Token {
-        let tmp_l = self.line;
-        let tmp_c = self.col;
+        let location = self.location();
         let mut val = String::new();
         while self.pos < self.src.len() && (self.peek(0).is_alphanumeric() || self.peek(0) == '_') {
             val.push(self.advance());
         }
 
         match val.as_str() {
-            "var" => Token::Var(Location { line: tmp_l, col: tmp_c }),
-            "const" => Token::Const(Location { line: tmp_l, col: tmp_c }),
-            "print" => Token::Print(Location { line: tmp_l, col: tmp_c }),
-            _ => Token::Id(val, Location { line: tmp_l, col: tmp_c })
+            "var" => Token::Var(location),
+            "const" => Token::Const(location),
+            "print" => Token::Print(location),
+            "if" => Token::If(location),
+            "else" => Token::Else(location),
+            "while" => Token::While(location),
+            "for" => Token::For(location),
+            "in" => Token::In(location),
+            "fn" => Token::Fn(location),
+            "return" => Token::Return(location),
+            "struct" => Token::Struct(location),
+            "class" => Token::Class(location),
+            "self" => Token::SelfRef(location),
+            "super" => Token::Super(location),
+            "enum" => Token::Enum(location),
+            "match" => Token::Match(location),
+            "import" => Token::Import(location),
+            "as" => Token::As(location),
+            "export" => Token::Export(location),
+            "try" => Token::Try(location),
+            "catch" => Token::Catch(location),
+            "finally" => Token::Finally(location),
+            "throw" => Token::Throw(location),
+            "true" => Token::Bool(true, location),
+            "false" => Token::Bool(false, location),
+            "nil" => Token::Nil(location),
+            _ => Token::Id(val, location)
         }
     }
 
-    fn tokenize_num_lit(&mut self) -> Token {
-        let tmp_l = self.line;
-        let tmp_c = self.col;
+    fn tokenize_num_lit(&mut self) -> Result<Token, CompileError> {
+        let location = self.location();
         let mut val = String::new();
         let mut has_dot = false;
-        while self.pos < self.src.len() && (self.peek(0).is_digit(10) || self.peek(0) == '.' || self.peek(0) == '_') {
+        while self.pos < self.src.len() && (self.peek(0).is_ascii_digit() || self.peek(0) == '.' || self.peek(0) == '_') {
             if self.peek(0) == '.' {
+                // `1..5` is a range, not a float
+                if self.pos + 1 >= self.src.len() || !self.peek(1).is_ascii_digit() {
+                    break;
+                }
                 if has_dot {
-                    panic!("Twice dot");
+                    return Err(CompileError::new("Twice dot in number literal", self.location()));
                 }
                 has_dot = true;
             }
             else if self.peek(0) == '_' {
+                self.advance();
                 continue;
             }
             val.push(self.advance());
         }
 
-        if (has_dot) {
-            let num = val.parse::<f64>();
-            match num {
-                Ok(num) => return Token::Float(num, Location { line: tmp_l, col: tmp_c }),
-                Err(err) => panic!("Error: {}", err)
-            }
+        if has_dot {
+            return match val.parse::<f64>() {
+                Ok(num) => Ok(Token::Float(num, location)),
+                Err(err) => Err(CompileError::new(err.to_string(), location))
+            };
         }
-        let num = val.parse::<i64>();
-        match num {
-            Ok(num) => return Token::Int(num, Location { line: tmp_l, col: tmp_c }),
-            Err(err) => panic!("Error: {}", err)
+        // `123n` is a big int
+        if self.pos < self.src.len() && self.peek(0) == 'n'
+            && (self.pos + 1 >= self.src.len() || !(self.peek(1).is_alphanumeric() || self.peek(1) == '_')) {
+            self.advance();
+            return Ok(Token::BigInt(BigInt::parse(&val).unwrap(), location));
         }
+        return match val.parse::<i64>() {
+            Ok(num) => Ok(Token::Int(num, location)),
+            Err(err) if *err.kind() == IntErrorKind::PosOverflow =>
+                Err(CompileError::new(format!("Int literal does not fit in 64 bits, write `{}n` for a big int", val), location)),
+            Err(err) => Err(CompileError::new(err.to_string(), location))
+        };
     }
 
-    fn tokenize_char_lit(&mut self) -> Token {
-        let tmp_l = self.line;
-        let tmp_c = self.col;
+    fn tokenize_char_lit(&mut self) -> Result<Token, CompileError> {
+        let location = self.location();
         let mut val = String::new();
         self.advance();
         while self.pos < self.src.len() && self.peek(0) != '\'' {
-            if val.len() == 1 {
-                panic!("To many symbols in character literal");
+            if !val.is_empty() {
+                return Err(CompileError::new("Too many symbols in character literal", location));
             }
-            val.push(self.advance());
+            let c = self.tokenize_char()?;
+            val.push(c);
         }
-        if val.len() == 0 {
-            panic!("The character constant must have a length of 1");
+        if self.pos >= self.src.len() {
+            return Err(CompileError::new("Unterminated character literal", location));
+        }
+        if val.is_empty() {
+            return Err(CompileError::new("The character constant must have a length of 1", location));
         }
         self.advance();
 
-        return Token::Char(val.chars().nth(0).unwrap(), Location { line: tmp_l, col: tmp_c });
+        return Ok(Token::Char(val.chars().next().unwrap(), location));
     }
 
-    fn tokenize_str_lit(&mut self) -> Token {
-        let tmp_l = self.line;
-        let tmp_c = self.col;
+    /// A string containing `${expr}` becomes an `InterpStr` that holds the
+    /// text around each interpolation and the tokens of its expression.
+    fn tokenize_str_lit(&mut self) -> Result<Token, CompileError> {
+        let location = self.location();
         let mut val = String::new();
+        let mut parts = Vec::new();
         self.advance();
         while self.pos < self.src.len() && self.peek(0) != '"' {
-            val.push(self.advance());
+            if self.peek(0) == '$' && self.pos + 1 < self.src.len() && self.peek(1) == '{' {
+                self.advance();
+                self.advance();
+                if !val.is_empty() {
+                    parts.push(StrPart::Lit(std::mem::take(&mut val)));
+                }
+                parts.push(StrPart::Expr(self.tokenize_interpolation()?));
+                continue;
+            }
+            let c = self.tokenize_char()?;
+            val.push(c);
+        }
+        if self.pos >= self.src.len() {
+            return Err(CompileError::new("Unterminated string literal", location));
         }
         self.advance();
 
-        return Token::Str(val, Location { line: tmp_l, col: tmp_c });
+        if parts.is_empty() {
+            return Ok(Token::Str(val, location));
+        }
+        if !val.is_empty() {
+            parts.push(StrPart::Lit(val));
+        }
+        return Ok(Token::InterpStr(parts, location));
+    }
+
+    /// Reads the tokens of an interpolated expression up to the `}` that
+    /// closes it, which is replaced by `Eof`.
+    fn tokenize_interpolation(&mut self) -> Result<Vec<Token>, CompileError> {
+        let location = self.location();
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            let tok = self.next_token()?;
+            match tok {
+                Token::Eof(_) => return Err(CompileError::new("Unterminated interpolation in string literal", location)),
+                Token::RBrace(loc) if depth == 0 => {
+                    tokens.push(Token::Eof(loc));
+                    return Ok(tokens);
+                }
+                Token::LBrace(_) => depth += 1,
+                Token::RBrace(_) => depth -= 1,
+                _ => {}
+            }
+            tokens.push(tok);
+        }
     }
 
-    fn tokenize_op(&mut self) -> Option<Token> {
-        let tmp_l = self.line;
-        let tmp_c = self.col;
-        let location = Location { line: tmp_l, col: tmp_c };
+    /// Reads one character of a string or character literal, resolving
+    /// backslash escapes.
+    fn tokenize_char(&mut self) -> Result<char, CompileError> {
+        let location = self.location();
+        let c = self.advance();
+        if c != '\\' {
+            return Ok(c);
+        }
+        if self.pos >= self.src.len() {
+            return Err(CompileError::new("Unterminated escape sequence", location));
+        }
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '\'' => Ok('\''),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            c => Err(CompileError::new(format!("Unknown escape sequence `\\{}`", c), location))
+        }
+    }
+
+    fn tokenize_op(&mut self) -> Result<Token, CompileError> {
+        let location = self.location();
         let c = self.advance();
         match c {
             '=' if self.pos < self.src.len() &&
-                self.peek(0) == '='                 => { self.advance(); Some(Token::Eq(location)) },
-            '='                                     => Some(Token::Assign(location)),
+                self.peek(0) == '='                 => { self.advance(); Ok(Token::Eq(location)) },
+            '=' if self.pos < self.src.len() &&
+                self.peek(0) == '>'                 => { self.advance(); Ok(Token::FatArrow(location)) },
+            '='                                     => Ok(Token::Assign(location)),
 
             '>' if self.pos < self.src.len() &&
-                self.peek(0) == '='                 => { self.advance(); Some(Token::GtEq(location))},
-            '>'                                     => Some(Token::Gt(location)),
+                self.peek(0) == '='                 => { self.advance(); Ok(Token::GtEq(location)) },
+            '>'                                     => Ok(Token::Gt(location)),
 
             '<' if self.pos < self.src.len() &&
-                self.peek(0) == '='                 => { self.advance(); Some(Token::LtEq(location)) },
-            '<'                                     => Some(Token::Lt(location)),
+                self.peek(0) == '='                 => { self.advance(); Ok(Token::LtEq(location)) },
+            '<'                                     => Ok(Token::Lt(location)),
 
             '!' if self.pos < self.src.len() &&
-                self.peek(0) == '='                 => { self.advance(); Some(Token::NotEq(location)) },
-            '!'                                     => Some(Token::Not(location)),
+                self.peek(0) == '='                 => { self.advance(); Ok(Token::NotEq(location)) },
+            '!'                                     => Ok(Token::Not(location)),
 
             '&' if self.pos < self.src.len() &&
-                self.peek(0) == '&'                 => { self.advance(); Some(Token::LogicalAnd(location)) },
-            '&'                                     => Some(Token::And(location)),
+                self.peek(0) == '&'                 => { self.advance(); Ok(Token::LogicalAnd(location)) },
+            '&'                                     => Ok(Token::And(location)),
 
             '|' if self.pos < self.src.len() &&
-                self.peek(0) == '|'                 => { self.advance(); Some(Token::LogicalOr(location)) },
-            '|'                                     => Some(Token::Or(Location { line: tmp_l, col: tmp_c })),
-            
-            '+'                                     => Some(Token::Plus(location)),
-            '-'                                     => Some(Token::Minus(location)),
-            '*'                                     => Some(Token::Star(location)),
-            '/'                                     => Some(Token::Slash(location)),
-            '%'                                     => Some(Token::Percent(location)),
-            ';'                                     => Some(Token::Semi(location)),
-            ':'                                     => Some(Token::Colon(location)),
-            '.'                                     => Some(Token::Dot(location)),
-            ','                                     => Some(Token::Comma(location)),
-            _                                       => None
+                self.peek(0) == '|'                 => { self.advance(); Ok(Token::LogicalOr(location)) },
+            '|'                                     => Ok(Token::Or(location)),
+
+            '+'                                     => Ok(Token::Plus(location)),
+            '-' if self.pos < self.src.len() &&
+                self.peek(0) == '>'                 => { self.advance(); Ok(Token::Arrow(location)) },
+            '-'                                     => Ok(Token::Minus(location)),
+            '*'                                     => Ok(Token::Star(location)),
+            '/' if self.pos < self.src.len() &&
+                self.peek(0) == '/'                 => { self.advance(); Ok(Token::SlashSlash(location)) },
+            '/'                                     => Ok(Token::Slash(location)),
+            '%'                                     => Ok(Token::Percent(location)),
+            ';'                                     => Ok(Token::Semi(location)),
+            ':'                                     => Ok(Token::Colon(location)),
+            '.' if self.pos + 1 < self.src.len() &&
+                self.peek(0) == '.' &&
+                self.peek(1) == '='                 => { self.advance(); self.advance(); Ok(Token::DotDotEq(location)) },
+            '.' if self.pos < self.src.len() &&
+                self.peek(0) == '.'                 => { self.advance(); Ok(Token::DotDot(location)) },
+            '.'                                     => Ok(Token::Dot(location)),
+            ','                                     => Ok(Token::Comma(location)),
+            '?'                                     => Ok(Token::Question(location)),
+            '('                                     => Ok(Token::LParen(location)),
+            ')'                                     => Ok(Token::RParen(location)),
+            '['                                     => Ok(Token::LBracket(location)),
+            ']'                                     => Ok(Token::RBracket(location)),
+            '{'                                     => Ok(Token::LBrace(location)),
+            '}'                                     => Ok(Token::RBrace(location)),
+            _                                       => Err(CompileError::new(format!("Unexpected symbol `{}`", c), location))
         }
     }
 
-    fn peek(&mut self, rpos: usize) -> char {
+    fn location(&self) -> Location {
+        return Location { line: self.line, col: self.col };
+    }
+
+    fn peek(&self, rpos: usize) -> char {
         if self.pos + rpos >= self.src.len() {
             panic!("Index passed to lexer out of bounds: {} + {} / {}", self.pos, rpos, self.src.len());
         }
-        return self.src.chars().nth(self.pos + rpos).unwrap();
+        return self.src[self.pos + rpos];
     }
 
     fn advance(&mut self) -> char {
@@ -181,4 +320,4 @@ impl Lexer {
         }
         return c;
     }
-}
\ No newline at end of file
+}
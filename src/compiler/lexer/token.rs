@@ -1,4 +1,4 @@
-use crate::compiler::location::Location;
+use crate::{compiler::location::Location, vm::bigint::BigInt};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -8,12 +8,36 @@ pub enum Token {
     Var(Location),
     Const(Location),
     Print(Location),
+    If(Location),
+    Else(Location),
+    While(Location),
+    For(Location),
+    In(Location),
+    Fn(Location),
+    Return(Location),
+    Struct(Location),
+    Class(Location),
+    SelfRef(Location),
+    Super(Location),
+    Enum(Location),
+    Match(Location),
+    Import(Location),
+    As(Location),
+    Export(Location),
+    Try(Location),
+    Catch(Location),
+    Finally(Location),
+    Throw(Location),
     
     // literals
     Int(i64, Location),
+    BigInt(BigInt, Location),
     Float(f64, Location),
     Str(String, Location),
+    InterpStr(Vec<StrPart>, Location),
     Char(char, Location),
+    Bool(bool, Location),
+    Nil(Location),
 
     // operators
     Assign(Location),
@@ -32,9 +56,54 @@ pub enum Token {
     Minus(Location),
     Star(Location),
     Slash(Location),
+    SlashSlash(Location),
     Percent(Location),
     Semi(Location),
     Colon(Location),
     Dot(Location),
+    DotDot(Location),
+    DotDotEq(Location),
+    FatArrow(Location),
+    Arrow(Location),
     Comma(Location),
-}
\ No newline at end of file
+    Question(Location),
+    LParen(Location),
+    RParen(Location),
+    LBracket(Location),
+    RBracket(Location),
+    LBrace(Location),
+    RBrace(Location),
+
+    Eof(Location),
+}
+/// A piece of an interpolated string: literal text, or the tokens of an
+/// interpolated expression terminated by `Eof`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrPart {
+    Lit(String),
+    Expr(Vec<Token>)
+}
+
+impl Token {
+    pub fn location(&self) -> Location {
+        match self {
+            Token::Id(_, loc) | Token::Int(_, loc) | Token::BigInt(_, loc) | Token::Float(_, loc) |
+            Token::Str(_, loc) | Token::InterpStr(_, loc) | Token::Char(_, loc) | Token::Bool(_, loc) => loc.clone(),
+            Token::Nil(loc) | Token::Var(loc) | Token::Const(loc) | Token::Print(loc) |
+            Token::If(loc) | Token::Else(loc) | Token::While(loc) | Token::For(loc) | Token::In(loc) |
+            Token::Fn(loc) | Token::Return(loc) | Token::Struct(loc) |
+            Token::Class(loc) | Token::SelfRef(loc) | Token::Super(loc) |
+            Token::Enum(loc) | Token::Match(loc) | Token::Import(loc) | Token::As(loc) | Token::Export(loc) |
+            Token::Try(loc) | Token::Catch(loc) | Token::Finally(loc) | Token::Throw(loc) |
+            Token::Assign(loc) | Token::Gt(loc) | Token::GtEq(loc) | Token::Lt(loc) |
+            Token::LtEq(loc) | Token::Eq(loc) | Token::NotEq(loc) | Token::Not(loc) |
+            Token::And(loc) | Token::Or(loc) | Token::LogicalAnd(loc) | Token::LogicalOr(loc) |
+            Token::Plus(loc) | Token::Minus(loc) | Token::Star(loc) | Token::Slash(loc) | Token::SlashSlash(loc) |
+            Token::Percent(loc) | Token::Semi(loc) | Token::Colon(loc) | Token::Dot(loc) |
+            Token::DotDot(loc) | Token::DotDotEq(loc) | Token::FatArrow(loc) | Token::Arrow(loc) |
+            Token::Comma(loc) | Token::Question(loc) | Token::LParen(loc) | Token::RParen(loc) |
+            Token::LBracket(loc) | Token::RBracket(loc) | Token::LBrace(loc) | Token::RBrace(loc) |
+            Token::Eof(loc) => loc.clone()
+        }
+    }
+}
@@ -1,3 +1,116 @@
 pub mod location;
+pub mod module;
+pub mod error;
 pub mod lexer;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod checker;
+pub mod infer;
+pub mod optimizer;
+pub mod generator;
+pub mod register;
+
+use checker::Checker;
+use error::CompileError;
+use generator::Generator;
+use infer::{Inferer, NumType};
+use location::Location;
+use module::{Loader, Module};
+use optimizer::Optimizer;
+use parser::ast::Stmt;
+use register::RegisterGenerator;
+
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
+
+use crate::vm::{
+    native::NativeFunction,
+    program::Program,
+    register::program::RegisterProgram
+};
+
+/// The operand types type inference found in one module.
+type NumTypes = HashMap<Location, NumType>;
+
+/// Settings that change how scripts are compiled.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Infers the types of the whole program, rejecting it on any type error
+    /// and specialising arithmetic whose operand types are known.
+    pub strict: bool,
+    /// Directories searched for imported modules that are not found next to
+    /// the importing file.
+    pub search_paths: Vec<PathBuf>,
+    /// 0 compiles the program as written; 1 folds constant expressions and
+    /// leaves out code that can never run; 2, the default, also fuses common
+    /// instruction sequences into superinstructions.
+    pub opt_level: u8
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { strict: false, search_paths: Vec::new(), opt_level: 2 }
+    }
+}
+
+/// Compiles `src`, resolving calls against the host functions in `natives`.
+pub fn compile(src: &str, natives: &[NativeFunction], options: &Options) -> Result<Program, CompileError> {
+    return compile_at(src, None, natives, options);
+}
+
+/// Compiles `src` together with the modules it imports. Imports are resolved
+/// relative to `path`, the file `src` was read from, or to the working
+/// directory if there is none.
+pub fn compile_at(src: &str, path: Option<&Path>, natives: &[NativeFunction], options: &Options) -> Result<Program, CompileError> {
+    let (modules, num_types) = analyse(src, path, options)?;
+    let mut generator = Generator::new(natives);
+    if options.strict {
+        generator = generator.with_num_types(num_types);
+    }
+    if options.opt_level >= 1 {
+        generator = generator.with_optimizations();
+    }
+    let mut program = generator.generate(&modules)?;
+    if options.opt_level >= 2 {
+        optimizer::peephole::optimize(&mut program);
+    }
+    return Ok(program);
+}
+
+/// Compiles `src` for the register machine. Only a core subset of the
+/// language is supported, and imports are not.
+pub fn compile_registers_at(src: &str, path: Option<&Path>, natives: &[NativeFunction], options: &Options) -> Result<RegisterProgram, CompileError> {
+    let (modules, _) = analyse(src, path, options)?;
+    let script = modules.last().unwrap();
+    return RegisterGenerator::new(natives).generate(&script.stmts);
+}
+
+/// Loads `src` and its imports, then checks, infers and optimizes every
+/// module as `options` ask. Returns the modules with the operand types
+/// inferred for each.
+fn analyse(src: &str, path: Option<&Path>, options: &Options) -> Result<(Vec<Module>, Vec<NumTypes>), CompileError> {
+    let mut modules = Loader::new(&options.search_paths).load(src, path)?;
+    let methods = method_names(&modules);
+    let mut num_types = Vec::new();
+    for module in &mut modules {
+        let file = module.name.as_deref();
+        Checker::new().check(&module.stmts).map_err(|err| err.in_file(file))?;
+        if options.strict {
+            num_types.push(Inferer::new().with_methods(methods.clone()).infer(&module.stmts).map_err(|err| err.in_file(file))?);
+        }
+        if options.opt_level >= 1 {
+            Optimizer::new().optimize(&mut module.stmts);
+        }
+    }
+    return Ok((modules, num_types));
+}
+
+/// The names of the methods declared by the classes of every module.
+fn method_names(modules: &[Module]) -> HashSet<String> {
+    return modules.iter()
+        .flat_map(|module| &module.stmts)
+        .filter_map(|stmt| match stmt.unexported() {
+            Stmt::Class(def) => Some(def),
+            _ => None
+        })
+        .flat_map(|def| def.methods.iter().filter_map(|method| method.name.clone()))
+        .collect();
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf}
+};
+
+use crate::compiler::{
+    error::CompileError,
+    lexer::Lexer,
+    location::Location,
+    parser::{Parser, ast::Stmt}
+};
+
+/// A parsed source file. `name` is the path the module was found at, used
+/// in diagnostics; it is `None` for the script being compiled. `imports`
+/// maps every path imported by the module to the index of that module.
+pub struct Module {
+    pub name: Option<String>,
+    pub stmts: Vec<Stmt>,
+    pub imports: HashMap<String, usize>
+}
+
+/// Parses a script and, recursively, the modules it imports.
+pub struct Loader<'a> {
+    search_paths: &'a [PathBuf],
+    modules: Vec<Module>,
+    paths: Vec<PathBuf>,
+    loading: Vec<(PathBuf, String)>
+}
+
+impl<'a> Loader<'a> {
+    pub fn new(search_paths: &'a [PathBuf]) -> Self {
+        Self { search_paths, modules: Vec::new(), paths: Vec::new(), loading: Vec::new() }
+    }
+
+    /// Loads the script `src`, read from `path` if it comes from a file.
+    /// Every module comes after the modules it imports, so the script
+    /// itself is last.
+    pub fn load(mut self, src: &str, path: Option<&Path>) -> Result<Vec<Module>, CompileError> {
+        let dir = match path {
+            Some(path) => {
+                if let Ok(canonical) = path.canonicalize() {
+                    self.loading.push((canonical, path.display().to_string()));
+                }
+                path.parent().map(Path::to_path_buf).unwrap_or_default()
+            }
+            None => PathBuf::new()
+        };
+        let stmts = Parser::new(Lexer::new(src).tokenize()?).parse()?;
+        self.load_module(stmts, None, &dir)?;
+        return Ok(self.modules);
+    }
+
+    fn load_module(&mut self, stmts: Vec<Stmt>, name: Option<String>, dir: &Path) -> Result<usize, CompileError> {
+        let mut imports = HashMap::new();
+        for stmt in &stmts {
+            if let Stmt::Import(path, _, loc) = stmt && !imports.contains_key(path) {
+                let index = self.import(path, dir, loc).map_err(|err| err.in_file(name.as_deref()))?;
+                imports.insert(path.clone(), index);
+            }
+        }
+        self.modules.push(Module { name, stmts, imports });
+        return Ok(self.modules.len() - 1);
+    }
+
+    /// Returns the index of the module at `path`, loading it first unless an
+    /// earlier import already did.
+    fn import(&mut self, path: &str, dir: &Path, loc: &Location) -> Result<usize, CompileError> {
+        let (found, canonical) = match self.resolve(path, dir) {
+            Some(resolved) => resolved,
+            None => return Err(CompileError::new(format!("Module `{}` not found", path), loc.clone()))
+        };
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == canonical) {
+            let mut cycle: Vec<&str> = self.loading[start..].iter().map(|(_, name)| name.as_str()).collect();
+            cycle.push(&self.loading[start].1);
+            return Err(CompileError::new(format!("Import cycle: {}", cycle.join(" -> ")), loc.clone()));
+        }
+        if let Some(index) = self.paths.iter().position(|loaded| *loaded == canonical) {
+            return Ok(index);
+        }
+        let name = found.display().to_string();
+        let src = fs::read_to_string(&found)
+            .map_err(|err| CompileError::new(format!("Cannot read module `{}`: {}", path, err), loc.clone()))?;
+        let stmts = Lexer::new(&src).tokenize()
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .map_err(|err| err.in_file(Some(&name)))?;
+        self.loading.push((canonical.clone(), name.clone()));
+        let dir = found.parent().map(Path::to_path_buf).unwrap_or_default();
+        let index = self.load_module(stmts, Some(name), &dir)?;
+        self.loading.pop();
+        self.paths.resize(index + 1, PathBuf::new());
+        self.paths[index] = canonical;
+        return Ok(index);
+    }
+
+    /// Looks for `path` relative to the importing module, then in each
+    /// directory of the search path.
+    fn resolve(&self, path: &str, dir: &Path) -> Option<(PathBuf, PathBuf)> {
+        return std::iter::once(dir).chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|base| base.join(path))
+            .find_map(|candidate| {
+                let canonical = candidate.canonicalize().ok()?;
+                canonical.is_file().then_some((candidate, canonical))
+            });
+    }
+}
@@ -0,0 +1,356 @@
+pub mod peephole;
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    compiler::{
+        location::Location,
+        parser::ast::{ArmBody, BinaryOp, Expr, FunctionDef, LogicalOp, Pattern, Stmt, UnaryOp}
+    },
+    vm::{bigint::BigInt, stack_slot::{StackSlot, floor_div}}
+};
+
+/// Rewrites a checked module before code generation: folds operators whose
+/// operands are literals and replaces reads of `const` bindings initialised
+/// with a literal by that literal. Expressions that would fail at runtime,
+/// such as an overflowing addition or a division by zero, are left alone so
+/// that they still fail there.
+pub struct Optimizer {
+    scopes: Vec<HashMap<String, Option<Expr>>>
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimizer {
+    pub fn new() -> Self {
+        Self { scopes: vec![HashMap::new()] }
+    }
+
+    pub fn optimize(mut self, stmts: &mut [Stmt]) {
+        self.optimize_stmts(stmts);
+    }
+
+    fn optimize_stmts(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.optimize_stmt(stmt);
+        }
+    }
+
+    fn optimize_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::VarDef(name, _, value, is_const, _) => {
+                if let Some(value) = value {
+                    self.optimize_expr(value);
+                }
+                let literal = value.as_ref().filter(|value| *is_const && Self::is_literal(value)).cloned();
+                self.declare(name, literal);
+            }
+            Stmt::Print(expr, _) | Stmt::Expr(expr, _) | Stmt::Throw(expr, _) => self.optimize_expr(expr),
+            Stmt::Return(expr, _) => {
+                if let Some(expr) = expr {
+                    self.optimize_expr(expr);
+                }
+            }
+            Stmt::Assign(target, value, _) => {
+                self.optimize_target(target);
+                self.optimize_expr(value);
+            }
+            Stmt::Block(stmts, _) => {
+                self.scopes.push(HashMap::new());
+                self.optimize_stmts(stmts);
+                self.scopes.pop();
+            }
+            Stmt::If(cond, then_branch, else_branch, _) => {
+                self.optimize_expr(cond);
+                self.optimize_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.optimize_stmt(else_branch);
+                }
+            }
+            Stmt::While(cond, body, _) => {
+                self.optimize_expr(cond);
+                self.optimize_stmt(body);
+            }
+            Stmt::For(name, iterable, body, _) => {
+                self.optimize_expr(iterable);
+                self.scopes.push(HashMap::new());
+                self.declare(name, None);
+                self.optimize_stmt(body);
+                self.scopes.pop();
+            }
+            Stmt::Fn(def) => {
+                if let Some(name) = &def.name {
+                    self.declare(name, None);
+                }
+                self.optimize_function(def);
+            }
+            Stmt::Class(def) => {
+                self.declare(&def.name, None);
+                for method in &mut def.methods {
+                    self.optimize_function(method);
+                }
+            }
+            Stmt::Struct(name, _, _) | Stmt::Enum(name, _, _) | Stmt::Import(_, name, _) => self.declare(name, None),
+            Stmt::Export(decl, _) => self.optimize_stmt(decl),
+            Stmt::Try(body, catch, finally, _) => {
+                self.optimize_stmt(body);
+                if let Some((name, handler)) = catch {
+                    self.scopes.push(HashMap::new());
+                    self.declare(name, None);
+                    self.optimize_stmt(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.optimize_stmt(finally);
+                }
+            }
+        }
+    }
+
+    fn optimize_function(&mut self, def: &mut FunctionDef) {
+        self.scopes.push(HashMap::new());
+        for param in &def.params {
+            self.declare(param, None);
+        }
+        self.optimize_stmts(&mut def.body);
+        self.scopes.pop();
+    }
+
+    /// Optimizes the subexpressions of an assignment target, but never
+    /// replaces the assigned variable itself.
+    fn optimize_target(&mut self, target: &mut Expr) {
+        match target {
+            Expr::Index(collection, index, _) => {
+                self.optimize_expr(collection);
+                self.optimize_expr(index);
+            }
+            Expr::Get(obj, _, _) => self.optimize_expr(obj),
+            _ => {}
+        }
+    }
+
+    fn optimize_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Int(..) | Expr::BigInt(..) | Expr::Float(..) | Expr::Str(..) | Expr::Char(..) | Expr::Bool(..) | Expr::Nil(_) |
+            Expr::SelfRef(_) | Expr::Super(..) => {}
+            Expr::Var(name, loc) => {
+                if let Some(literal) = self.lookup(name) {
+                    *expr = Self::relocate(literal, loc.clone());
+                }
+            }
+            Expr::Unary(op, operand, loc) => {
+                self.optimize_expr(operand);
+                if let Some(folded) = Self::to_value(operand).and_then(|val| Self::fold_unary(*op, val))
+                    && let Some(folded) = Self::to_expr(folded, loc.clone()) {
+                    *expr = folded;
+                }
+            }
+            Expr::Binary(op, lhs, rhs, loc) => {
+                self.optimize_expr(lhs);
+                self.optimize_expr(rhs);
+                if let (Some(a), Some(b)) = (Self::to_value(lhs), Self::to_value(rhs))
+                    && let Some(folded) = Self::fold_binary(*op, a, b)
+                    && let Some(folded) = Self::to_expr(folded, loc.clone()) {
+                    *expr = folded;
+                }
+            }
+            Expr::Logical(op, lhs, rhs, loc) => {
+                self.optimize_expr(lhs);
+                self.optimize_expr(rhs);
+                if let Expr::Bool(val, _) = **lhs {
+                    *expr = match (op, val) {
+                        (LogicalOp::And, true) | (LogicalOp::Or, false) => std::mem::replace(&mut **rhs, Expr::Nil(loc.clone())),
+                        (LogicalOp::And, false) | (LogicalOp::Or, true) => Expr::Bool(val, loc.clone())
+                    };
+                }
+            }
+            Expr::Call(callee, args, _) => {
+                self.optimize_expr(callee);
+                self.optimize_exprs(args);
+            }
+            Expr::MethodCall(receiver, _, args, _) => {
+                self.optimize_expr(receiver);
+                self.optimize_exprs(args);
+            }
+            Expr::List(items, _) => self.optimize_exprs(items),
+            Expr::Map(entries, _) => {
+                for (key, value) in entries {
+                    self.optimize_expr(key);
+                    self.optimize_expr(value);
+                }
+            }
+            Expr::Index(collection, index, _) => {
+                self.optimize_expr(collection);
+                self.optimize_expr(index);
+            }
+            Expr::Get(obj, _, _) | Expr::Propagate(obj, _) => self.optimize_expr(obj),
+            Expr::Function(def) => self.optimize_function(def),
+            Expr::StructLit(_, fields, _) => {
+                for (_, value) in fields {
+                    self.optimize_expr(value);
+                }
+            }
+            Expr::Match(value, arms, _) => {
+                self.optimize_expr(value);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    self.declare_bindings(&arm.pattern);
+                    if let Some(guard) = &mut arm.guard {
+                        self.optimize_expr(guard);
+                    }
+                    match &mut arm.body {
+                        ArmBody::Expr(body) => self.optimize_expr(body),
+                        ArmBody::Block(body) => self.optimize_stmt(body)
+                    }
+                    self.scopes.pop();
+                }
+            }
+            Expr::Interp(parts, loc) => {
+                self.optimize_exprs(parts);
+                let values: Option<Vec<StackSlot>> = parts.iter().map(Self::to_value).collect();
+                if let Some(values) = values {
+                    *expr = Expr::Str(values.iter().map(StackSlot::to_string).collect(), loc.clone());
+                }
+            }
+        }
+    }
+
+    fn optimize_exprs(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.optimize_expr(expr);
+        }
+    }
+
+    fn declare(&mut self, name: &str, literal: Option<Expr>) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), literal);
+    }
+
+    fn declare_bindings(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding(name, _) => self.declare(name, None),
+            Pattern::Variant(_, _, items, _) | Pattern::List(items, _) | Pattern::Or(items, _) =>
+                items.iter().for_each(|item| self.declare_bindings(item)),
+            Pattern::Struct(_, fields, _) => fields.iter().for_each(|(_, sub)| self.declare_bindings(sub)),
+            Pattern::Wildcard(_) | Pattern::Literal(_) | Pattern::Range(..) => {}
+        }
+    }
+
+    /// The literal a name is bound to, if the innermost binding of the name
+    /// is a `const` initialised with one.
+    fn lookup(&self, name: &str) -> Option<Expr> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned().flatten();
+    }
+
+    fn is_literal(expr: &Expr) -> bool {
+        return Self::to_value(expr).is_some();
+    }
+
+    fn relocate(literal: Expr, loc: Location) -> Expr {
+        return Self::to_value(&literal).and_then(|val| Self::to_expr(val, loc)).unwrap_or(literal);
+    }
+
+    fn to_value(expr: &Expr) -> Option<StackSlot> {
+        return match expr {
+            Expr::Int(val, _) => Some(StackSlot::Int(*val)),
+            Expr::BigInt(val, _) => Some(StackSlot::from(val.clone())),
+            Expr::Float(val, _) => Some(StackSlot::Float(*val)),
+            Expr::Str(val, _) => Some(StackSlot::Str(Rc::from(val.as_str()))),
+            Expr::Char(val, _) => Some(StackSlot::Char(*val)),
+            Expr::Bool(val, _) => Some(StackSlot::Bool(*val)),
+            Expr::Nil(_) => Some(StackSlot::Nil),
+            _ => None
+        };
+    }
+
+    fn to_expr(val: StackSlot, loc: Location) -> Option<Expr> {
+        return match val {
+            StackSlot::Int(val) => Some(Expr::Int(val, loc)),
+            StackSlot::BigInt(val) => Some(Expr::BigInt((*val).clone(), loc)),
+            StackSlot::Float(val) => Some(Expr::Float(val, loc)),
+            StackSlot::Str(val) => Some(Expr::Str(val.to_string(), loc)),
+            StackSlot::Char(val) => Some(Expr::Char(val, loc)),
+            StackSlot::Bool(val) => Some(Expr::Bool(val, loc)),
+            StackSlot::Nil => Some(Expr::Nil(loc)),
+            _ => None
+        };
+    }
+
+    fn fold_unary(op: UnaryOp, val: StackSlot) -> Option<StackSlot> {
+        return match (op, val) {
+            (UnaryOp::Neg, StackSlot::Int(val)) => val.checked_neg().map(StackSlot::Int),
+            (UnaryOp::Neg, StackSlot::Float(val)) => Some(StackSlot::Float(-val)),
+            (UnaryOp::Neg, StackSlot::BigInt(val)) => Some(StackSlot::from(-&*val)),
+            (UnaryOp::Not, StackSlot::Bool(val)) => Some(StackSlot::Bool(!val)),
+            _ => None
+        };
+    }
+
+    /// Evaluates `a op b` as the VM would, or gives `None` if the VM would
+    /// raise an error.
+    fn fold_binary(op: BinaryOp, a: StackSlot, b: StackSlot) -> Option<StackSlot> {
+        return match op {
+            BinaryOp::Add => match (&a, &b) {
+                (StackSlot::Int(x), StackSlot::Int(y)) => x.checked_add(*y).map(StackSlot::Int),
+                (StackSlot::Str(x), StackSlot::Str(y)) => Some(StackSlot::from(format!("{}{}", x, y))),
+                (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) => Self::fold_big(&a, &b, |x, y| Some(x + y)),
+                _ => Self::fold_float(a, b, |x, y| x + y)
+            },
+            BinaryOp::Sub => match (&a, &b) {
+                (StackSlot::Int(x), StackSlot::Int(y)) => x.checked_sub(*y).map(StackSlot::Int),
+                (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) => Self::fold_big(&a, &b, |x, y| Some(x - y)),
+                _ => Self::fold_float(a, b, |x, y| x - y)
+            },
+            BinaryOp::Mul => match (&a, &b) {
+                (StackSlot::Int(x), StackSlot::Int(y)) => x.checked_mul(*y).map(StackSlot::Int),
+                (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) => Self::fold_big(&a, &b, |x, y| Some(x * y)),
+                _ => Self::fold_float(a, b, |x, y| x * y)
+            },
+            BinaryOp::Div => match (&a, &b) {
+                (StackSlot::Int(x), StackSlot::Int(y)) => x.checked_div(*y).map(StackSlot::Int),
+                (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) =>
+                    Self::fold_big(&a, &b, |x, y| x.div_rem(y).map(|(quotient, _)| quotient)),
+                _ => Self::fold_float(a, b, |x, y| x / y)
+            },
+            BinaryOp::FloorDiv => match (&a, &b) {
+                (StackSlot::Int(x), StackSlot::Int(y)) => floor_div(*x, *y).map(StackSlot::Int),
+                (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) => Self::fold_big(&a, &b, BigInt::div_floor),
+                _ => Self::fold_float(a, b, |x, y| (x / y).floor())
+            },
+            BinaryOp::Rem => match (&a, &b) {
+                (StackSlot::Int(_), StackSlot::Int(0)) => None,
+                (StackSlot::Int(x), StackSlot::Int(y)) => Some(StackSlot::Int(x.wrapping_rem(*y))),
+                (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) =>
+                    Self::fold_big(&a, &b, |x, y| x.div_rem(y).map(|(_, remainder)| remainder)),
+                _ => Self::fold_float(a, b, |x, y| x % y)
+            },
+            BinaryOp::Eq => Some(StackSlot::Bool(a.equals(&b))),
+            BinaryOp::NotEq => Some(StackSlot::Bool(!a.equals(&b))),
+            BinaryOp::Lt => a.compare(&b).map(|ord| StackSlot::Bool(ord.is_lt())),
+            BinaryOp::LtEq => a.compare(&b).map(|ord| StackSlot::Bool(ord.is_le())),
+            BinaryOp::Gt => a.compare(&b).map(|ord| StackSlot::Bool(ord.is_gt())),
+            BinaryOp::GtEq => a.compare(&b).map(|ord| StackSlot::Bool(ord.is_ge()))
+        };
+    }
+
+    /// Folds an operation on a big int and an int or a big int. A float
+    /// operand is an error at runtime, so it is not folded.
+    fn fold_big(a: &StackSlot, b: &StackSlot, op: fn(&BigInt, &BigInt) -> Option<BigInt>) -> Option<StackSlot> {
+        return match (a.as_bigint(), b.as_bigint()) {
+            (Some(x), Some(y)) => op(&x, &y).map(StackSlot::from),
+            _ => None
+        };
+    }
+
+    fn fold_float(a: StackSlot, b: StackSlot, op: fn(f64, f64) -> f64) -> Option<StackSlot> {
+        return match (a, b) {
+            (StackSlot::Float(x), StackSlot::Float(y)) => Some(StackSlot::Float(op(x, y))),
+            (StackSlot::Float(x), StackSlot::Int(y)) => Some(StackSlot::Float(op(x, y as f64))),
+            (StackSlot::Int(x), StackSlot::Float(y)) => Some(StackSlot::Float(op(x as f64, y))),
+            _ => None
+        };
+    }
+}
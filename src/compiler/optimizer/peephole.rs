@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use crate::vm::{
+    chunk::{Chunk, Instruction, Operand},
+    function::FunctionProto,
+    opcodes::OpCode,
+    program::Program
+};
+
+/// Fuses common instruction sequences in every chunk of `program` into
+/// single superinstructions:
+///
+/// - `Push k; Add` becomes `AddConst k`
+/// - `LoadGlob g; Push k; Add; StoreGlob g` becomes `IncGlobal g k`
+/// - a comparison followed by `JmpIfFalse t` becomes `JmpIfFalseCmp cmp t`
+///
+/// Sequences that a jump enters in the middle are left alone. Jump targets
+/// and source locations are moved to match the shorter bytecode.
+pub fn optimize(program: &mut Program) {
+    for chunk in &mut program.chunks {
+        fuse(chunk, &program.functions);
+    }
+}
+
+fn fuse(chunk: &mut Chunk, functions: &[FunctionProto]) {
+    let instructions = chunk.decode_all(functions);
+    let targets: HashSet<usize> = instructions.iter()
+        .flat_map(|instruction| &instruction.operands)
+        .filter_map(|operand| match operand {
+            Operand::Target(target) => Some(*target),
+            _ => None
+        })
+        .collect();
+
+    let mut fused = Vec::with_capacity(instructions.len());
+    let mut moved = vec![0; instructions.len() + 1];
+    let mut i = 0;
+    while i < instructions.len() {
+        let (instruction, count) = fuse_at(&instructions[i..], |k| targets.contains(&(i + k)))
+            .unwrap_or_else(|| (instructions[i].clone(), 1));
+        moved[i..i + count].fill(fused.len());
+        fused.push(instruction);
+        i += count;
+    }
+    moved[instructions.len()] = fused.len();
+    for operand in fused.iter_mut().flat_map(|instruction| &mut instruction.operands) {
+        if let Operand::Target(target) = operand {
+            *target = moved[*target];
+        }
+    }
+    // Fusing only shortens the code, so its operands fit as before; a
+    // chunk that failed to assemble would keep its unfused code.
+    let _ = chunk.assemble(&fused);
+}
+
+/// The superinstruction replacing the instructions at the start of
+/// `instructions` and how many it replaces, if they form a known
+/// sequence. `is_target(k)` tells whether a jump lands on instruction `k`.
+fn fuse_at(instructions: &[Instruction], is_target: impl Fn(usize) -> bool) -> Option<(Instruction, usize)> {
+    let op = |k: usize| instructions.get(k).and_then(Instruction::op);
+    let operand = |k: usize| match instructions[k].operands[0] {
+        Operand::Index(index) | Operand::Target(index) => index,
+        Operand::Byte(byte) => byte as usize
+    };
+    let enters = |count: usize| (1..count).any(&is_target);
+    let is_add = |k: usize| matches!(op(k), Some(OpCode::Add | OpCode::AddInt));
+    let fused = |opcode: OpCode, operands: Vec<Operand>| Instruction { opcode: opcode as u8, operands, start: instructions[0].start };
+
+    if matches!(op(0), Some(OpCode::LoadGlob)) && matches!(op(1), Some(OpCode::Push)) && is_add(2)
+        && matches!(op(3), Some(OpCode::StoreGlob)) && operand(0) == operand(3) && !enters(4) {
+        return Some((fused(OpCode::IncGlobal, vec![Operand::Index(operand(0)), Operand::Index(operand(1))]), 4));
+    }
+    if matches!(op(0), Some(OpCode::Push)) && is_add(1) && !enters(2) {
+        return Some((fused(OpCode::AddConst, vec![Operand::Index(operand(0))]), 2));
+    }
+    if let Some(cmp @ (OpCode::Eq | OpCode::NotEq | OpCode::Lt | OpCode::LtEq | OpCode::Gt | OpCode::GtEq)) = op(0)
+        && matches!(op(1), Some(OpCode::JmpIfFalse)) && !enters(2) {
+        return Some((fused(OpCode::JmpIfFalseCmp, vec![Operand::Byte(cmp as u8), Operand::Target(operand(1))]), 2));
+    }
+    return None;
+}
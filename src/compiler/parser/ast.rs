@@ -0,0 +1,185 @@
+use crate::{compiler::location::Location, vm::bigint::BigInt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Rem,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogicalOp {
+    And,
+    Or
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Int(i64, Location),
+    BigInt(BigInt, Location),
+    Float(f64, Location),
+    Str(String, Location),
+    Char(char, Location),
+    Bool(bool, Location),
+    Nil(Location),
+    Var(String, Location),
+    Unary(UnaryOp, Box<Expr>, Location),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>, Location),
+    Logical(LogicalOp, Box<Expr>, Box<Expr>, Location),
+    Call(Box<Expr>, Vec<Expr>, Location),
+    MethodCall(Box<Expr>, String, Vec<Expr>, Location),
+    List(Vec<Expr>, Location),
+    Map(Vec<(Expr, Expr)>, Location),
+    Index(Box<Expr>, Box<Expr>, Location),
+    Get(Box<Expr>, String, Location),
+    Function(Box<FunctionDef>),
+    StructLit(String, Vec<(String, Expr)>, Location),
+    SelfRef(Location),
+    Super(String, Location),
+    Match(Box<Expr>, Vec<MatchArm>, Location),
+    Propagate(Box<Expr>, Location),
+    Interp(Vec<Expr>, Location)
+}
+
+impl Expr {
+    pub fn location(&self) -> Location {
+        match self {
+            Expr::Int(_, loc) | Expr::BigInt(_, loc) | Expr::Float(_, loc) | Expr::Str(_, loc) | Expr::Char(_, loc) |
+            Expr::Bool(_, loc) | Expr::Nil(loc) | Expr::Var(_, loc) | Expr::Unary(_, _, loc) |
+            Expr::Binary(_, _, _, loc) | Expr::Logical(_, _, _, loc) | Expr::Call(_, _, loc) |
+            Expr::MethodCall(_, _, _, loc) | Expr::List(_, loc) | Expr::Map(_, loc) |
+            Expr::Index(_, _, loc) | Expr::Get(_, _, loc) => loc.clone(),
+            Expr::StructLit(_, _, loc) | Expr::SelfRef(loc) | Expr::Super(_, loc) |
+            Expr::Match(_, _, loc) | Expr::Propagate(_, loc) | Expr::Interp(_, loc) => loc.clone(),
+            Expr::Function(def) => def.location.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    VarDef(String, Option<TypeAnn>, Option<Expr>, bool, Location),
+    Print(Expr, Location),
+    Expr(Expr, Location),
+    Assign(Expr, Expr, Location),
+    Block(Vec<Stmt>, Location),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>, Location),
+    While(Expr, Box<Stmt>, Location),
+    For(String, Expr, Box<Stmt>, Location),
+    Fn(Box<FunctionDef>),
+    Return(Option<Expr>, Location),
+    Struct(String, Vec<String>, Location),
+    Class(Box<ClassDef>),
+    Enum(String, Vec<(String, usize)>, Location),
+    Import(String, String, Location),
+    Export(Box<Stmt>, Location),
+    Throw(Expr, Location),
+    Try(Box<Stmt>, Option<(String, Box<Stmt>)>, Option<Box<Stmt>>, Location)
+}
+
+impl Stmt {
+    pub fn location(&self) -> Location {
+        match self {
+            Stmt::VarDef(.., loc) | Stmt::Print(_, loc) | Stmt::Expr(_, loc) | Stmt::Assign(_, _, loc) |
+            Stmt::Block(_, loc) | Stmt::If(.., loc) | Stmt::While(.., loc) | Stmt::For(.., loc) |
+            Stmt::Return(_, loc) | Stmt::Struct(.., loc) | Stmt::Enum(.., loc) | Stmt::Import(.., loc) |
+            Stmt::Export(_, loc) | Stmt::Throw(_, loc) | Stmt::Try(.., loc) => loc.clone(),
+            Stmt::Fn(def) => def.location.clone(),
+            Stmt::Class(def) => def.location.clone()
+        }
+    }
+
+    /// The declaration itself for `export` declarations, otherwise `self`.
+    pub fn unexported(&self) -> &Stmt {
+        match self {
+            Stmt::Export(decl, _) => decl,
+            stmt => stmt
+        }
+    }
+}
+
+/// A type annotation such as the `int` in `var x: int`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAnn {
+    pub name: String,
+    pub location: Location
+}
+
+/// A named `fn` declaration or an anonymous function expression.
+/// `param_types` has one optional annotation per parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDef {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub param_types: Vec<Option<TypeAnn>>,
+    pub return_type: Option<TypeAnn>,
+    pub body: Vec<Stmt>,
+    pub location: Location
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard(Location),
+    Binding(String, Location),
+    Literal(Expr),
+    Range(Expr, Expr, bool, Location),
+    Variant(String, String, Vec<Pattern>, Location),
+    Struct(String, Vec<(String, Pattern)>, Location),
+    List(Vec<Pattern>, Location),
+    Or(Vec<Pattern>, Location)
+}
+
+impl Pattern {
+    pub fn location(&self) -> Location {
+        match self {
+            Pattern::Literal(expr) => expr.location(),
+            Pattern::Wildcard(loc) | Pattern::Binding(_, loc) | Pattern::Range(_, _, _, loc) |
+            Pattern::Variant(_, _, _, loc) | Pattern::Struct(_, _, loc) | Pattern::List(_, loc) |
+            Pattern::Or(_, loc) => loc.clone()
+        }
+    }
+
+    /// Whether the pattern matches every value.
+    pub fn is_irrefutable(&self) -> bool {
+        return matches!(self, Pattern::Wildcard(_) | Pattern::Binding(..));
+    }
+}
+
+/// The body of a match arm: an expression whose value is the value of the
+/// match, or a block, which gives `nil`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArmBody {
+    Expr(Expr),
+    Block(Stmt)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: ArmBody
+}
+
+/// A `class` declaration with an optional superclass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDef {
+    pub name: String,
+    pub superclass: Option<(String, Location)>,
+    pub methods: Vec<FunctionDef>,
+    pub location: Location
+}
@@ -1,164 +1,924 @@
-use crate::{
-    compiler::{
-        lexer::token::Token,
-        location::Location
-    },
-    vm::{
-        VM,
-        chunk::Chunk,
-        opcodes::OpCode,
-        stack_slot::StackSlot
-    }
+pub mod ast;
+use ast::{ArmBody, BinaryOp, ClassDef, Expr, FunctionDef, LogicalOp, MatchArm, Pattern, Stmt, TypeAnn, UnaryOp};
+
+use crate::compiler::{
+    error::CompileError,
+    lexer::token::{StrPart, Token},
+    location::Location
 };
 
 pub struct Parser {
-    tokens: Vec<Option<Token>>,
-    pos: usize,
-    globals: Vec<String>,
-    pub vm: VM
+    tokens: Vec<Token>,
+    pos: usize
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Option<Token>>) -> Self {
-        Self { tokens: tokens, pos: 0, globals: Vec::new(), vm: VM::new(vec![Chunk::new()]) }
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        let mut stmts = Vec::new();
+        while !matches!(self.peek(0), Token::Eof(_)) {
+            stmts.push(self.parse_stmt()?);
+        }
+        return Ok(stmts);
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, CompileError> {
+        match self.peek(0) {
+            Token::Var(loc) => {
+                self.advance();
+                self.parse_var_def(false, loc)
+            }
+            Token::Const(loc) => {
+                self.advance();
+                self.parse_var_def(true, loc)
+            }
+            Token::Print(loc) => {
+                self.advance();
+                self.parse_print(loc)
+            }
+            Token::LBrace(loc) => self.parse_block(loc),
+            Token::If(loc) => {
+                self.advance();
+                self.parse_if(loc)
+            }
+            Token::While(loc) => {
+                self.advance();
+                self.parse_while(loc)
+            }
+            Token::For(loc) => {
+                self.advance();
+                self.parse_for(loc)
+            }
+            Token::Fn(loc) if matches!(self.peek(1), Token::Id(..)) => {
+                self.advance();
+                let name = match self.advance() {
+                    Token::Id(name, _) => name,
+                    _ => unreachable!()
+                };
+                let def = self.parse_function(Some(name), loc)?;
+                Ok(Stmt::Fn(Box::new(def)))
+            }
+            Token::Return(loc) => {
+                self.advance();
+                self.parse_return(loc)
+            }
+            Token::Struct(loc) => {
+                self.advance();
+                self.parse_struct(loc)
+            }
+            Token::Class(loc) => {
+                self.advance();
+                self.parse_class(loc)
+            }
+            Token::Enum(loc) => {
+                self.advance();
+                self.parse_enum(loc)
+            }
+            Token::Import(loc) => {
+                self.advance();
+                self.parse_import(loc)
+            }
+            Token::Export(loc) => {
+                self.advance();
+                match self.peek(0) {
+                    Token::Var(_) | Token::Const(_) | Token::Fn(_) | Token::Class(_) | Token::Struct(_) | Token::Enum(_) =>
+                        Ok(Stmt::Export(Box::new(self.parse_stmt()?), loc)),
+                    tok => Err(CompileError::new("Expected a declaration after `export`", tok.location()))
+                }
+            }
+            Token::Try(loc) => {
+                self.advance();
+                self.parse_try(loc)
+            }
+            Token::Throw(loc) => {
+                self.advance();
+                let value = self.parse_expr()?;
+                self.expect_semi()?;
+                Ok(Stmt::Throw(value, loc))
+            }
+            Token::Match(loc) => {
+                self.advance();
+                let expr = self.parse_match(loc.clone())?;
+                if let Token::Semi(_) = self.peek(0) {
+                    self.advance();
+                }
+                Ok(Stmt::Expr(expr, loc))
+            }
+            tok => {
+                let expr = self.parse_expr()?;
+                if let Token::Assign(loc) = self.peek(0) {
+                    self.advance();
+                    if !matches!(expr, Expr::Var(..) | Expr::Index(..) | Expr::Get(..)) {
+                        return Err(CompileError::new("Invalid assignment target", expr.location()));
+                    }
+                    let value = self.parse_expr()?;
+                    self.expect_semi()?;
+                    return Ok(Stmt::Assign(expr, value, loc));
+                }
+                self.expect_semi()?;
+                Ok(Stmt::Expr(expr, tok.location()))
+            }
+        }
+    }
+
+    fn parse_block(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        self.expect(|tok| matches!(tok, Token::LBrace(_)), "`{`")?;
+        let mut stmts = Vec::new();
+        loop {
+            match self.peek(0) {
+                Token::RBrace(_) => {
+                    self.advance();
+                    break;
+                }
+                Token::Eof(loc) => return Err(CompileError::new("Expected `}`", loc)),
+                _ => stmts.push(self.parse_stmt()?)
+            }
+        }
+        return Ok(Stmt::Block(stmts, loc));
+    }
+
+    fn parse_body(&mut self) -> Result<Stmt, CompileError> {
+        match self.peek(0) {
+            Token::LBrace(loc) => self.parse_block(loc),
+            tok => Err(CompileError::new(format!("Expected `{{`: {:?}", tok), tok.location()))
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, CompileError> {
+        self.expect(|tok| matches!(tok, Token::LParen(_)), "`(`")?;
+        let cond = self.parse_expr()?;
+        self.expect(|tok| matches!(tok, Token::RParen(_)), "`)`")?;
+        return Ok(cond);
+    }
+
+    fn parse_if(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        let cond = self.parse_condition()?;
+        let then_branch = self.parse_body()?;
+        let else_branch = match self.peek(0) {
+            Token::Else(_) => {
+                self.advance();
+                match self.peek(0) {
+                    Token::If(loc) => {
+                        self.advance();
+                        Some(Box::new(self.parse_if(loc)?))
+                    }
+                    _ => Some(Box::new(self.parse_body()?))
+                }
+            }
+            _ => None
+        };
+        return Ok(Stmt::If(cond, Box::new(then_branch), else_branch, loc));
     }
 
-    pub fn generate(&mut self) {
-        while self.pos < self.tokens.len() {
+    fn parse_while(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        let cond = self.parse_condition()?;
+        let body = self.parse_body()?;
+        return Ok(Stmt::While(cond, Box::new(body), loc));
+    }
+
+    fn parse_for(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        self.expect(|tok| matches!(tok, Token::LParen(_)), "`(`")?;
+        let name = match self.advance() {
+            Token::Id(name, _) => name,
+            tok => return Err(CompileError::new("Expected identifier", tok.location()))
+        };
+        self.expect(|tok| matches!(tok, Token::In(_)), "`in`")?;
+        let iterable = self.parse_expr()?;
+        self.expect(|tok| matches!(tok, Token::RParen(_)), "`)`")?;
+        let body = self.parse_body()?;
+        return Ok(Stmt::For(name, iterable, Box::new(body), loc));
+    }
+
+    /// Parses the parameter list, optional return type and body of a
+    /// function after `fn` and its optional name.
+    fn parse_function(&mut self, name: Option<String>, location: Location) -> Result<FunctionDef, CompileError> {
+        self.expect(|tok| matches!(tok, Token::LParen(_)), "`(`")?;
+        let mut params: Vec<String> = Vec::new();
+        let mut param_types = Vec::new();
+        loop {
             match self.advance() {
-                Some(Token::Var(loc)) | Some(Token::Const(loc)) => self.generate_var_def(loc),
-                Some(Token::Print(_)) => self.generate_print(),
-                _ => {}
+                Token::RParen(_) => break,
+                Token::Id(param, param_loc) => {
+                    if params.contains(&param) {
+                        return Err(CompileError::new(format!("Duplicate parameter `{}`", param), param_loc));
+                    }
+                    params.push(param);
+                    param_types.push(self.parse_type_ann()?);
+                    match self.peek(0) {
+                        Token::Comma(_) => { self.advance(); }
+                        Token::RParen(_) => {}
+                        tok => return Err(CompileError::new(format!("Expected `,` or `)`: {:?}", tok), tok.location()))
+                    }
+                }
+                tok => return Err(CompileError::new("Expected parameter name", tok.location()))
             }
         }
+        let return_type = match self.peek(0) {
+            Token::Arrow(_) => {
+                self.advance();
+                Some(self.parse_type()?)
+            }
+            _ => None
+        };
+        let body = match self.parse_body()? {
+            Stmt::Block(stmts, _) => stmts,
+            _ => unreachable!()
+        };
+        return Ok(FunctionDef { name, params, param_types, return_type, body, location });
     }
 
-    fn generate_var_def(&mut self, loc: Location) {
+    /// Parses an optional `: type` annotation.
+    fn parse_type_ann(&mut self) -> Result<Option<TypeAnn>, CompileError> {
+        if !matches!(self.peek(0), Token::Colon(_)) {
+            return Ok(None);
+        }
+        self.advance();
+        return Ok(Some(self.parse_type()?));
+    }
+
+    fn parse_type(&mut self) -> Result<TypeAnn, CompileError> {
+        match self.advance() {
+            Token::Id(name, location) => Ok(TypeAnn { name, location }),
+            Token::Nil(location) => Ok(TypeAnn { name: "nil".to_string(), location }),
+            tok => Err(CompileError::new("Expected type name", tok.location()))
+        }
+    }
+
+    fn parse_struct(&mut self, loc: Location) -> Result<Stmt, CompileError> {
         let name = match self.advance() {
-            Some(Token::Id(name, _)) => name,
-            _ => panic!("Error: Expected identifier")
+            Token::Id(name, _) => name,
+            tok => return Err(CompileError::new("Expected struct name", tok.location()))
         };
-        
+        self.expect(|tok| matches!(tok, Token::LBrace(_)), "`{`")?;
+        let mut fields: Vec<String> = Vec::new();
+        loop {
+            match self.advance() {
+                Token::RBrace(_) => break,
+                Token::Id(field, field_loc) => {
+                    if fields.contains(&field) {
+                        return Err(CompileError::new(format!("Duplicate field `{}`", field), field_loc));
+                    }
+                    fields.push(field);
+                    match self.peek(0) {
+                        Token::Comma(_) => { self.advance(); }
+                        Token::RBrace(_) => {}
+                        tok => return Err(CompileError::new(format!("Expected `,` or `}}`: {:?}", tok), tok.location()))
+                    }
+                }
+                tok => return Err(CompileError::new("Expected field name", tok.location()))
+            }
+        }
+        return Ok(Stmt::Struct(name, fields, loc));
+    }
+
+    /// Parses `class Name : Base { fn method(...) { ... } ... }` after the
+    /// `class` keyword.
+    fn parse_class(&mut self, location: Location) -> Result<Stmt, CompileError> {
+        let name = match self.advance() {
+            Token::Id(name, _) => name,
+            tok => return Err(CompileError::new("Expected class name", tok.location()))
+        };
+        let superclass = match self.peek(0) {
+            Token::Colon(_) => {
+                self.advance();
+                match self.advance() {
+                    Token::Id(name, loc) => Some((name, loc)),
+                    tok => return Err(CompileError::new("Expected superclass name", tok.location()))
+                }
+            }
+            _ => None
+        };
+        self.expect(|tok| matches!(tok, Token::LBrace(_)), "`{`")?;
+        let mut methods: Vec<FunctionDef> = Vec::new();
+        loop {
+            match self.advance() {
+                Token::RBrace(_) => break,
+                Token::Fn(loc) => {
+                    let (method, method_loc) = match self.advance() {
+                        Token::Id(method, method_loc) => (method, method_loc),
+                        tok => return Err(CompileError::new("Expected method name", tok.location()))
+                    };
+                    if methods.iter().any(|m| m.name.as_deref() == Some(method.as_str())) {
+                        return Err(CompileError::new(format!("Method `{}` is already defined", method), method_loc));
+                    }
+                    methods.push(self.parse_function(Some(method), loc)?);
+                }
+                tok => return Err(CompileError::new("Expected `fn` or `}`", tok.location()))
+            }
+        }
+        return Ok(Stmt::Class(Box::new(ClassDef { name, superclass, methods, location })));
+    }
+
+    /// Parses `enum Name { Variant, Variant(field, ...), ... }` after the
+    /// `enum` keyword. Payload field names only document the variant.
+    fn parse_enum(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        let name = match self.advance() {
+            Token::Id(name, _) => name,
+            tok => return Err(CompileError::new("Expected enum name", tok.location()))
+        };
+        self.expect(|tok| matches!(tok, Token::LBrace(_)), "`{`")?;
+        let mut variants: Vec<(String, usize)> = Vec::new();
+        loop {
+            let (variant, variant_loc) = match self.advance() {
+                Token::RBrace(_) => break,
+                Token::Id(variant, variant_loc) => (variant, variant_loc),
+                tok => return Err(CompileError::new("Expected variant name", tok.location()))
+            };
+            if variants.iter().any(|(v, _)| *v == variant) {
+                return Err(CompileError::new(format!("Duplicate variant `{}`", variant), variant_loc));
+            }
+            let mut arity = 0;
+            if let Token::LParen(_) = self.peek(0) {
+                self.advance();
+                loop {
+                    match self.advance() {
+                        Token::RParen(_) => break,
+                        Token::Id(..) => arity += 1,
+                        tok => return Err(CompileError::new("Expected payload field name", tok.location()))
+                    }
+                    match self.peek(0) {
+                        Token::Comma(_) => { self.advance(); }
+                        Token::RParen(_) => {}
+                        tok => return Err(CompileError::new(format!("Expected `,` or `)`: {:?}", tok), tok.location()))
+                    }
+                }
+            }
+            variants.push((variant, arity));
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                Token::RBrace(_) => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or `}}`: {:?}", tok), tok.location()))
+            }
+        }
+        return Ok(Stmt::Enum(name, variants, loc));
+    }
+
+    /// Parses `(value) { pattern if guard => body, ... }` after `match`.
+    /// Arm bodies are expressions or blocks; a comma is required after an
+    /// expression body unless it is the last arm.
+    fn parse_match(&mut self, loc: Location) -> Result<Expr, CompileError> {
+        let value = self.parse_condition()?;
+        self.expect(|tok| matches!(tok, Token::LBrace(_)), "`{`")?;
+        let mut arms = Vec::new();
+        loop {
+            if let Token::RBrace(_) = self.peek(0) {
+                self.advance();
+                break;
+            }
+            let pattern = self.parse_pattern()?;
+            let guard = match self.peek(0) {
+                Token::If(_) => {
+                    self.advance();
+                    Some(self.parse_expr()?)
+                }
+                _ => None
+            };
+            self.expect(|tok| matches!(tok, Token::FatArrow(_)), "`=>`")?;
+            let body = match self.peek(0) {
+                Token::LBrace(block_loc) => ArmBody::Block(self.parse_block(block_loc)?),
+                _ => ArmBody::Expr(self.parse_expr()?)
+            };
+            let is_block = matches!(body, ArmBody::Block(_));
+            arms.push(MatchArm { pattern, guard, body });
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                Token::RBrace(_) => {}
+                _ if is_block => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or `}}`: {:?}", tok), tok.location()))
+            }
+        }
+        return Ok(Expr::Match(Box::new(value), arms, loc));
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, CompileError> {
+        let first = self.parse_single_pattern()?;
+        if !matches!(self.peek(0), Token::Or(_)) {
+            return Ok(first);
+        }
+        let loc = first.location();
+        let mut alternatives = vec![first];
+        while let Token::Or(_) = self.peek(0) {
+            self.advance();
+            alternatives.push(self.parse_single_pattern()?);
+        }
+        return Ok(Pattern::Or(alternatives, loc));
+    }
+
+    fn parse_single_pattern(&mut self) -> Result<Pattern, CompileError> {
         match self.peek(0) {
-            Some(Token::Assign(_)) => {
+            Token::Id(name, loc) if name == "_" => {
+                self.advance();
+                Ok(Pattern::Wildcard(loc))
+            }
+            Token::Id(name, loc) => {
                 self.advance();
-                Some(self.generate_expr());
                 match self.peek(0) {
-                    Some(Token::Semi(_)) => { self.advance(); }
-                    _ => panic!("Error: Expected `;`: {:?}", self.peek(0))
+                    Token::Dot(_) => {
+                        self.advance();
+                        let variant = match self.advance() {
+                            Token::Id(variant, _) => variant,
+                            tok => return Err(CompileError::new("Expected variant name", tok.location()))
+                        };
+                        let mut payload = Vec::new();
+                        if let Token::LParen(_) = self.peek(0) {
+                            self.advance();
+                            payload = self.parse_pattern_list(|tok| matches!(tok, Token::RParen(_)), "`)`")?;
+                        }
+                        Ok(Pattern::Variant(name, variant, payload, loc))
+                    }
+                    Token::LParen(_) if name == "Ok" || name == "Err" => {
+                        self.advance();
+                        let payload = self.parse_pattern_list(|tok| matches!(tok, Token::RParen(_)), "`)`")?;
+                        Ok(Pattern::Variant("Result".to_string(), name, payload, loc))
+                    }
+                    Token::LBrace(_) => {
+                        self.advance();
+                        self.parse_struct_pattern(name, loc)
+                    }
+                    _ => Ok(Pattern::Binding(name, loc))
+                }
+            }
+            Token::LBracket(loc) => {
+                self.advance();
+                let items = self.parse_pattern_list(|tok| matches!(tok, Token::RBracket(_)), "`]`")?;
+                Ok(Pattern::List(items, loc))
+            }
+            _ => {
+                let lhs = self.parse_literal_pattern()?;
+                match self.peek(0) {
+                    Token::DotDot(loc) | Token::DotDotEq(loc) => {
+                        let inclusive = matches!(self.advance(), Token::DotDotEq(_));
+                        let rhs = self.parse_literal_pattern()?;
+                        Ok(Pattern::Range(lhs, rhs, inclusive, loc))
+                    }
+                    _ => Ok(Pattern::Literal(lhs))
                 }
             }
-            Some(Token::Semi(_)) => { self.advance(); }
-            _ => panic!("Error: Unexpected symbol: {:?}", self.peek(0))
         }
-        
-        if self.vm.chunk_index == 0 {
-            self.generate_global_var(name);
+    }
+
+    /// Parses a literal, allowing a leading `-` on numbers.
+    fn parse_literal_pattern(&mut self) -> Result<Expr, CompileError> {
+        match self.advance() {
+            Token::Minus(loc) => match self.advance() {
+                Token::Int(val, _) => Ok(Expr::Int(-val, loc)),
+                Token::BigInt(val, _) => Ok(Expr::BigInt(-&val, loc)),
+                Token::Float(val, _) => Ok(Expr::Float(-val, loc)),
+                tok => Err(CompileError::new("Expected number after `-` in pattern", tok.location()))
+            },
+            Token::Int(val, loc) => Ok(Expr::Int(val, loc)),
+            Token::BigInt(val, loc) => Ok(Expr::BigInt(val, loc)),
+            Token::Float(val, loc) => Ok(Expr::Float(val, loc)),
+            Token::Str(val, loc) => Ok(Expr::Str(val, loc)),
+            Token::Char(val, loc) => Ok(Expr::Char(val, loc)),
+            Token::Bool(val, loc) => Ok(Expr::Bool(val, loc)),
+            Token::Nil(loc) => Ok(Expr::Nil(loc)),
+            tok => Err(CompileError::new("Expected pattern", tok.location()))
         }
-        else {
-            self.generate_local_var(name);
+    }
+
+    fn parse_pattern_list(&mut self, is_close: impl Fn(&Token) -> bool, close: &str) -> Result<Vec<Pattern>, CompileError> {
+        let mut patterns = Vec::new();
+        loop {
+            if is_close(&self.peek(0)) {
+                self.advance();
+                break;
+            }
+            patterns.push(self.parse_pattern()?);
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                tok if is_close(&tok) => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or {}: {:?}", close, tok), tok.location()))
+            }
         }
+        return Ok(patterns);
     }
 
-    fn generate_global_var(&mut self, name: String) {
-        let index = self.vm.create_global();
-        self.globals.push(name);
-        self.vm.store_global(index);
+    /// Parses `{ field: pattern, field }` after `Name {`. A bare field name
+    /// binds the field to a variable of the same name.
+    fn parse_struct_pattern(&mut self, name: String, loc: Location) -> Result<Pattern, CompileError> {
+        let mut fields = Vec::new();
+        loop {
+            let (field, field_loc) = match self.advance() {
+                Token::RBrace(_) => break,
+                Token::Id(field, field_loc) => (field, field_loc),
+                tok => return Err(CompileError::new("Expected field name", tok.location()))
+            };
+            let pattern = match self.peek(0) {
+                Token::Colon(_) => {
+                    self.advance();
+                    self.parse_pattern()?
+                }
+                _ => Pattern::Binding(field.clone(), field_loc)
+            };
+            fields.push((field, pattern));
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                Token::RBrace(_) => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or `}}`: {:?}", tok), tok.location()))
+            }
+        }
+        return Ok(Pattern::Struct(name, fields, loc));
     }
 
-    fn generate_local_var(&mut self, name: String) {
-        todo!()
+    /// Parses `"path/to/module.sd" as name;` after `import`.
+    fn parse_import(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        let path = match self.advance() {
+            Token::Str(path, _) => path,
+            tok => return Err(CompileError::new("Expected module path", tok.location()))
+        };
+        self.expect(|tok| matches!(tok, Token::As(_)), "`as`")?;
+        let alias = match self.advance() {
+            Token::Id(alias, _) => alias,
+            tok => return Err(CompileError::new("Expected identifier", tok.location()))
+        };
+        self.expect_semi()?;
+        return Ok(Stmt::Import(path, alias, loc));
     }
 
-    fn generate_print(&mut self) {
-        self.generate_expr();
-        self.vm.chunks[self.vm.chunk_index].emit_byte(OpCode::Print as u8);
+    /// Parses `try { } catch (e) { } finally { }` after `try`. Either
+    /// clause may be left out, but not both.
+    fn parse_try(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        let body = self.parse_body()?;
+        let catch = match self.peek(0) {
+            Token::Catch(_) => {
+                self.advance();
+                self.expect(|tok| matches!(tok, Token::LParen(_)), "`(`")?;
+                let name = match self.advance() {
+                    Token::Id(name, _) => name,
+                    tok => return Err(CompileError::new("Expected identifier", tok.location()))
+                };
+                self.expect(|tok| matches!(tok, Token::RParen(_)), "`)`")?;
+                Some((name, Box::new(self.parse_body()?)))
+            }
+            _ => None
+        };
+        let finally = match self.peek(0) {
+            Token::Finally(_) => {
+                self.advance();
+                Some(Box::new(self.parse_body()?))
+            }
+            _ => None
+        };
+        if catch.is_none() && finally.is_none() {
+            return Err(CompileError::new("Expected `catch` or `finally` after `try` block", self.peek(0).location()));
+        }
+        return Ok(Stmt::Try(Box::new(body), catch, finally, loc));
+    }
+
+    fn parse_return(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        if let Token::Semi(_) = self.peek(0) {
+            self.advance();
+            return Ok(Stmt::Return(None, loc));
+        }
+        let value = self.parse_expr()?;
+        self.expect_semi()?;
+        return Ok(Stmt::Return(Some(value), loc));
+    }
+
+    fn parse_var_def(&mut self, is_const: bool, loc: Location) -> Result<Stmt, CompileError> {
+        let name = match self.advance() {
+            Token::Id(name, _) => name,
+            tok => return Err(CompileError::new("Expected identifier", tok.location()))
+        };
+        let var_type = self.parse_type_ann()?;
+
         match self.peek(0) {
-            Some(Token::Semi(_)) => { self.advance(); }
-            _ => panic!("Error: Expected `;`: {:?}", self.peek(0))
+            Token::Assign(_) => {
+                self.advance();
+                let value = self.parse_expr()?;
+                self.expect_semi()?;
+                Ok(Stmt::VarDef(name, var_type, Some(value), is_const, loc))
+            }
+            Token::Semi(_) => {
+                self.advance();
+                Ok(Stmt::VarDef(name, var_type, None, is_const, loc))
+            }
+            tok => Err(CompileError::new(format!("Unexpected symbol: {:?}", tok), tok.location()))
         }
     }
 
-    fn generate_expr(&mut self) {
-        self.generate_additive_expr();
+    fn parse_print(&mut self, loc: Location) -> Result<Stmt, CompileError> {
+        let expr = self.parse_expr()?;
+        self.expect_semi()?;
+        return Ok(Stmt::Print(expr, loc));
     }
 
-    fn generate_additive_expr(&mut self) {
-        self.generate_multiplicative_expr();
-        while let tok = self.peek(0) {
-            match tok {
-                Some(Token::Plus(_)) => {
-                    self.advance();
-                    self.generate_multiplicative_expr();
-                    self.vm.chunks[self.vm.chunk_index].emit_byte(OpCode::Add as u8);
-                },
-                Some(Token::Minus(_)) => {
-                    self.advance();
-                    self.generate_multiplicative_expr();
-                    self.vm.chunks[self.vm.chunk_index].emit_byte(OpCode::Sub as u8);
-                },
+    fn parse_expr(&mut self) -> Result<Expr, CompileError> {
+        return self.parse_logical_or_expr();
+    }
+
+    fn parse_logical_or_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.parse_logical_and_expr()?;
+        while let Token::LogicalOr(loc) = self.peek(0) {
+            self.advance();
+            let rhs = self.parse_logical_and_expr()?;
+            lhs = Expr::Logical(LogicalOp::Or, Box::new(lhs), Box::new(rhs), loc);
+        }
+        return Ok(lhs);
+    }
+
+    fn parse_logical_and_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.parse_equality_expr()?;
+        while let Token::LogicalAnd(loc) = self.peek(0) {
+            self.advance();
+            let rhs = self.parse_equality_expr()?;
+            lhs = Expr::Logical(LogicalOp::And, Box::new(lhs), Box::new(rhs), loc);
+        }
+        return Ok(lhs);
+    }
+
+    fn parse_equality_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.parse_comparison_expr()?;
+        loop {
+            let (op, loc) = match self.peek(0) {
+                Token::Eq(loc) => (BinaryOp::Eq, loc),
+                Token::NotEq(loc) => (BinaryOp::NotEq, loc),
                 _ => break
-            }
+            };
+            self.advance();
+            let rhs = self.parse_comparison_expr()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs), loc);
         }
+        return Ok(lhs);
     }
 
-    fn generate_multiplicative_expr(&mut self) {
-        self.generate_primary_expr();
-        while let tok = self.peek(0) {
-            match tok {
-                Some(Token::Star(_)) => {
+    fn parse_comparison_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.parse_additive_expr()?;
+        loop {
+            let (op, loc) = match self.peek(0) {
+                Token::Lt(loc) => (BinaryOp::Lt, loc),
+                Token::LtEq(loc) => (BinaryOp::LtEq, loc),
+                Token::Gt(loc) => (BinaryOp::Gt, loc),
+                Token::GtEq(loc) => (BinaryOp::GtEq, loc),
+                _ => break
+            };
+            self.advance();
+            let rhs = self.parse_additive_expr()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs), loc);
+        }
+        return Ok(lhs);
+    }
+
+    fn parse_additive_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.parse_multiplicative_expr()?;
+        loop {
+            let (op, loc) = match self.peek(0) {
+                Token::Plus(loc) => (BinaryOp::Add, loc),
+                Token::Minus(loc) => (BinaryOp::Sub, loc),
+                _ => break
+            };
+            self.advance();
+            let rhs = self.parse_multiplicative_expr()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs), loc);
+        }
+        return Ok(lhs);
+    }
+
+    fn parse_multiplicative_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.parse_unary_expr()?;
+        loop {
+            let (op, loc) = match self.peek(0) {
+                Token::Star(loc) => (BinaryOp::Mul, loc),
+                Token::Slash(loc) => (BinaryOp::Div, loc),
+                Token::SlashSlash(loc) => (BinaryOp::FloorDiv, loc),
+                Token::Percent(loc) => (BinaryOp::Rem, loc),
+                _ => break
+            };
+            self.advance();
+            let rhs = self.parse_unary_expr()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs), loc);
+        }
+        return Ok(lhs);
+    }
+
+    fn parse_unary_expr(&mut self) -> Result<Expr, CompileError> {
+        let (op, loc) = match self.peek(0) {
+            Token::Minus(loc) => (UnaryOp::Neg, loc),
+            Token::Not(loc) => (UnaryOp::Not, loc),
+            _ => return self.parse_postfix_expr()
+        };
+        self.advance();
+        let operand = self.parse_unary_expr()?;
+        return Ok(Expr::Unary(op, Box::new(operand), loc));
+    }
+
+    fn parse_postfix_expr(&mut self) -> Result<Expr, CompileError> {
+        let mut expr = self.parse_primary_expr()?;
+        loop {
+            match self.peek(0) {
+                Token::LParen(_) => {
                     self.advance();
-                    self.generate_primary_expr();
-                    self.vm.chunks[self.vm.chunk_index].emit_byte(OpCode::Mul as u8);
-                },
-                Some(Token::Slash(_)) => {
+                    let args = self.parse_args()?;
+                    let loc = expr.location();
+                    expr = Expr::Call(Box::new(expr), args, loc);
+                }
+                Token::LBracket(loc) => {
                     self.advance();
-                    self.generate_primary_expr();
-                    self.vm.chunks[self.vm.chunk_index].emit_byte(OpCode::Div as u8);
-                },
-                Some(Token::Percent(_)) => {
+                    let index = self.parse_expr()?;
+                    self.expect(|tok| matches!(tok, Token::RBracket(_)), "`]`")?;
+                    expr = Expr::Index(Box::new(expr), Box::new(index), loc);
+                }
+                Token::Dot(loc) => {
                     self.advance();
-                    self.generate_primary_expr();
-                    self.vm.chunks[self.vm.chunk_index].emit_byte(OpCode::Rem as u8);
-                },
+                    let name = match self.advance() {
+                        Token::Id(name, _) => name,
+                        tok => return Err(CompileError::new("Expected field or method name", tok.location()))
+                    };
+                    if let Token::LParen(_) = self.peek(0) {
+                        self.advance();
+                        let args = self.parse_args()?;
+                        expr = Expr::MethodCall(Box::new(expr), name, args, loc);
+                    }
+                    else {
+                        expr = Expr::Get(Box::new(expr), name, loc);
+                    }
+                }
+                Token::Question(loc) => {
+                    self.advance();
+                    expr = Expr::Propagate(Box::new(expr), loc);
+                }
                 _ => break
             }
         }
+        return Ok(expr);
     }
 
-    fn generate_primary_expr(&mut self) {
-        let tok = self.advance();
-        match tok {
-            Some(Token::Id(name, _)) => {
-                let var_index = self.globals.iter().position(|s| **s == name);
-                match var_index {
-                    Some(index) => { self.vm.load_global(index); },
-                    None => panic!("Error: Variable does not defined")
+    /// Parses the expressions of an interpolated string, each from its own
+    /// token list.
+    fn parse_interpolation(&mut self, parts: Vec<StrPart>, loc: Location) -> Result<Expr, CompileError> {
+        let mut items = Vec::new();
+        for part in parts {
+            match part {
+                StrPart::Lit(text) => items.push(Expr::Str(text, loc.clone())),
+                StrPart::Expr(tokens) => {
+                    let mut parser = Parser::new(tokens);
+                    if let Token::Eof(end) = parser.peek(0) {
+                        return Err(CompileError::new("Expected expression in string interpolation", end));
+                    }
+                    items.push(parser.parse_expr()?);
+                    let tok = parser.peek(0);
+                    if !matches!(tok, Token::Eof(_)) {
+                        return Err(CompileError::new("Expected `}` after interpolated expression", tok.location()));
+                    }
                 }
             }
-            Some(Token::Int(val, _)) => {
-                self.vm.chunks[self.vm.chunk_index].emit_const(StackSlot::Int(val));
-            },
-            Some(Token::Float(val, _)) => {
-                self.vm.chunks[self.vm.chunk_index].emit_const(StackSlot::Float(val));
-            },
-            _ => panic!("Error: Expected expression")
         }
+        return Ok(Expr::Interp(items, loc));
+    }
+
+    fn parse_args(&mut self) -> Result<Vec<Expr>, CompileError> {
+        return self.parse_expr_list(|tok| matches!(tok, Token::RParen(_)), "`)`");
+    }
+
+    /// Parses comma-separated expressions up to and including the closing
+    /// token, allowing a trailing comma.
+    fn parse_expr_list(&mut self, is_close: impl Fn(&Token) -> bool, close: &str) -> Result<Vec<Expr>, CompileError> {
+        let mut exprs = Vec::new();
+        loop {
+            if is_close(&self.peek(0)) {
+                self.advance();
+                break;
+            }
+            exprs.push(self.parse_expr()?);
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                tok if is_close(&tok) => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or {}: {:?}", close, tok), tok.location()))
+            }
+        }
+        return Ok(exprs);
+    }
+
+    fn parse_primary_expr(&mut self) -> Result<Expr, CompileError> {
+        match self.advance() {
+            Token::Id(name, loc) if self.is_struct_lit() => {
+                self.advance();
+                self.parse_struct_lit(name, loc)
+            }
+            Token::Id(name, loc) => Ok(Expr::Var(name, loc)),
+            Token::Int(val, loc) => Ok(Expr::Int(val, loc)),
+            Token::BigInt(val, loc) => Ok(Expr::BigInt(val, loc)),
+            Token::Float(val, loc) => Ok(Expr::Float(val, loc)),
+            Token::Str(val, loc) => Ok(Expr::Str(val, loc)),
+            Token::InterpStr(parts, loc) => self.parse_interpolation(parts, loc),
+            Token::Char(val, loc) => Ok(Expr::Char(val, loc)),
+            Token::Bool(val, loc) => Ok(Expr::Bool(val, loc)),
+            Token::Nil(loc) => Ok(Expr::Nil(loc)),
+            Token::LBracket(loc) => {
+                let items = self.parse_expr_list(|tok| matches!(tok, Token::RBracket(_)), "`]`")?;
+                Ok(Expr::List(items, loc))
+            }
+            Token::LBrace(loc) => self.parse_map(loc),
+            Token::Fn(loc) => {
+                let def = self.parse_function(None, loc)?;
+                Ok(Expr::Function(Box::new(def)))
+            }
+            Token::SelfRef(loc) => Ok(Expr::SelfRef(loc)),
+            Token::Match(loc) => self.parse_match(loc),
+            Token::Super(loc) => {
+                self.expect(|tok| matches!(tok, Token::Dot(_)), "`.` after `super`")?;
+                match self.advance() {
+                    Token::Id(name, _) => Ok(Expr::Super(name, loc)),
+                    tok => Err(CompileError::new("Expected superclass method name", tok.location()))
+                }
+            }
+            Token::LParen(_) => {
+                let expr = self.parse_expr()?;
+                self.expect(|tok| matches!(tok, Token::RParen(_)), "`)`")?;
+                Ok(expr)
+            }
+            tok => Err(CompileError::new("Expected expression", tok.location()))
+        }
+    }
+
+    /// `Name {` starts a struct literal when the brace is followed by `}`
+    /// or by `field:`.
+    fn is_struct_lit(&self) -> bool {
+        return matches!(self.peek(0), Token::LBrace(_)) &&
+            matches!((self.peek(1), self.peek(2)), (Token::RBrace(_), _) | (Token::Id(..), Token::Colon(_)));
     }
 
-    fn peek(&mut self, rpos: i32) -> Option<Token> {
-        if self.pos as i128 + rpos as i128 >= self.tokens.len() as i128 {
-            panic!("Index passed to parser out of bounds: {} + {} / {}", self.pos, rpos, self.tokens.len());
+    /// Parses the `field: value` pairs of a struct literal after the
+    /// opening brace.
+    fn parse_struct_lit(&mut self, name: String, loc: Location) -> Result<Expr, CompileError> {
+        let mut fields: Vec<(String, Expr)> = Vec::new();
+        loop {
+            let (field, field_loc) = match self.advance() {
+                Token::RBrace(_) => break,
+                Token::Id(field, field_loc) => (field, field_loc),
+                tok => return Err(CompileError::new("Expected field name", tok.location()))
+            };
+            if fields.iter().any(|(f, _)| *f == field) {
+                return Err(CompileError::new(format!("Field `{}` is specified more than once", field), field_loc));
+            }
+            self.expect(|tok| matches!(tok, Token::Colon(_)), "`:`")?;
+            let value = self.parse_expr()?;
+            fields.push((field, value));
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                Token::RBrace(_) => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or `}}`: {:?}", tok), tok.location()))
+            }
         }
-        return self.tokens[(self.pos as i128 + rpos as i128) as usize].clone();
+        return Ok(Expr::StructLit(name, fields, loc));
     }
 
-    fn advance(&mut self) -> Option<Token> {
-        if self.pos < self.tokens.len() {
-            let tok = self.peek(0);
+    /// Parses the entries of a `{ key: value, ... }` literal after the
+    /// opening brace. A bare identifier key is shorthand for a string key.
+    fn parse_map(&mut self, loc: Location) -> Result<Expr, CompileError> {
+        let mut entries = Vec::new();
+        loop {
+            if let Token::RBrace(_) = self.peek(0) {
+                self.advance();
+                break;
+            }
+            let key = match (self.peek(0), self.peek(1)) {
+                (Token::Id(name, key_loc), Token::Colon(_)) => {
+                    self.advance();
+                    Expr::Str(name, key_loc)
+                }
+                _ => self.parse_expr()?
+            };
+            self.expect(|tok| matches!(tok, Token::Colon(_)), "`:`")?;
+            let value = self.parse_expr()?;
+            entries.push((key, value));
+            match self.peek(0) {
+                Token::Comma(_) => { self.advance(); }
+                Token::RBrace(_) => {}
+                tok => return Err(CompileError::new(format!("Expected `,` or `}}`: {:?}", tok), tok.location()))
+            }
+        }
+        return Ok(Expr::Map(entries, loc));
+    }
+
+    fn expect(&mut self, is_expected: impl Fn(&Token) -> bool, what: &str) -> Result<Token, CompileError> {
+        let tok = self.peek(0);
+        if is_expected(&tok) {
+            self.advance();
+            return Ok(tok);
+        }
+        return Err(CompileError::new(format!("Expected {}: {:?}", what, tok), tok.location()));
+    }
+
+    fn expect_semi(&mut self) -> Result<(), CompileError> {
+        self.expect(|tok| matches!(tok, Token::Semi(_)), "`;`")?;
+        return Ok(());
+    }
+
+    fn peek(&self, rpos: usize) -> Token {
+        let index = (self.pos + rpos).min(self.tokens.len() - 1);
+        return self.tokens[index].clone();
+    }
+
+    fn advance(&mut self) -> Token {
+        let tok = self.peek(0);
+        if self.pos < self.tokens.len() - 1 {
             self.pos += 1;
-            return tok;
         }
-        return None;
+        return tok;
     }
-}
\ No newline at end of file
+}
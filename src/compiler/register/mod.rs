@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+
+use crate::{
+    compiler::{
+        error::CompileError,
+        location::Location,
+        parser::ast::{BinaryOp, Expr, FunctionDef, LogicalOp, Stmt, UnaryOp}
+    },
+    vm::{
+        enum_def::EnumDef,
+        native::{NativeFunction, VARIADIC},
+        register::{
+            instruction::{Instr, Reg},
+            program::{RegisterFunction, RegisterProgram}
+        },
+        stack_slot::StackSlot
+    }
+};
+
+struct Global {
+    name: String,
+    is_const: bool
+}
+
+struct Local {
+    name: String,
+    reg: Reg,
+    depth: usize,
+    is_const: bool
+}
+
+/// Per-function compilation state. Every local keeps the register it was
+/// declared in until its scope ends. Temporaries are allocated from
+/// `next_reg`, above the innermost local, and released after every
+/// statement.
+struct FunctionState {
+    function: usize,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    next_reg: usize
+}
+
+impl FunctionState {
+    fn new(function: usize, scope_depth: usize) -> Self {
+        Self { function, locals: Vec::new(), scope_depth, next_reg: 0 }
+    }
+}
+
+enum Variable {
+    Global(usize, bool),
+    Local(Reg, bool),
+    Function(usize)
+}
+
+/// Generates code for the register machine. It covers the core of the
+/// language: variables, arithmetic, control flow, top-level functions,
+/// lists, maps, string interpolation and native calls. Anything else is
+/// rejected with a compile error.
+pub struct RegisterGenerator<'a> {
+    functions: Vec<RegisterFunction>,
+    function_names: HashMap<String, usize>,
+    hoisted: HashMap<String, usize>,
+    globals: Vec<Global>,
+    states: Vec<FunctionState>,
+    location: Location,
+    natives: &'a [NativeFunction]
+}
+
+impl<'a> RegisterGenerator<'a> {
+    pub fn new(natives: &'a [NativeFunction]) -> Self {
+        Self {
+            functions: vec![Self::function(None, 0)],
+            function_names: HashMap::new(),
+            hoisted: HashMap::new(),
+            globals: Vec::new(),
+            states: vec![FunctionState::new(0, 0)],
+            location: Location { line: 1, col: 1 },
+            natives
+        }
+    }
+
+    /// Generates `stmts` as the top-level script. A trailing expression
+    /// statement returns its value as the program result.
+    pub fn generate(mut self, stmts: &[Stmt]) -> Result<RegisterProgram, CompileError> {
+        self.declare_functions(stmts);
+        for (i, stmt) in stmts.iter().enumerate() {
+            match stmt {
+                Stmt::Expr(expr, loc) if i + 1 == stmts.len() => {
+                    self.location = loc.clone();
+                    let reg = self.generate_operand(expr)?;
+                    self.emit(Instr::Ret(reg));
+                }
+                _ => self.generate_stmt(stmt)?
+            }
+        }
+        return Ok(RegisterProgram { functions: self.functions, globals: self.globals.len() });
+    }
+
+    /// Top-level functions are declared before any code is generated, so
+    /// that they can call functions declared further down and each other.
+    fn declare_functions(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Stmt::Fn(def) = stmt && let Some(name) = &def.name && !self.hoisted.contains_key(name) {
+                let index = self.functions.len();
+                self.functions.push(Self::function(def.name.clone(), def.params.len()));
+                self.function_names.insert(name.clone(), index);
+                self.hoisted.insert(name.clone(), index);
+            }
+        }
+    }
+
+    fn function(name: Option<String>, arity: usize) -> RegisterFunction {
+        return RegisterFunction { name, arity, registers: 0, code: Vec::new(), constants: Vec::new(), locations: Vec::new() };
+    }
+
+    fn state(&self) -> &FunctionState {
+        return self.states.last().unwrap();
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        return self.states.last_mut().unwrap();
+    }
+
+    fn current(&mut self) -> &mut RegisterFunction {
+        let function = self.state().function;
+        return &mut self.functions[function];
+    }
+
+    fn is_global_scope(&self) -> bool {
+        return self.states.len() == 1 && self.state().scope_depth == 0;
+    }
+
+    fn generate_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        self.location = stmt.location();
+        match stmt {
+            Stmt::VarDef(name, _, value, is_const, loc) => self.generate_var_def(name, value.as_ref(), *is_const, loc)?,
+            Stmt::Print(expr, _) => {
+                let reg = self.generate_operand(expr)?;
+                self.emit(Instr::Print(reg));
+            }
+            Stmt::Expr(expr, _) => { self.generate_operand(expr)?; }
+            Stmt::Assign(target, value, loc) => self.generate_assign(target, value, loc)?,
+            Stmt::Block(stmts, _) => {
+                self.begin_scope();
+                for stmt in stmts {
+                    self.generate_stmt(stmt)?;
+                }
+                self.end_scope();
+            }
+            Stmt::If(cond, then_branch, else_branch, _) => self.generate_if(cond, then_branch, else_branch.as_deref())?,
+            Stmt::While(cond, body, _) => self.generate_while(cond, body)?,
+            Stmt::For(name, iterable, body, loc) => self.generate_for(name, iterable, body, loc)?,
+            Stmt::Fn(def) => self.generate_fn_decl(def)?,
+            Stmt::Return(value, loc) => self.generate_return(value.as_ref(), loc)?,
+            Stmt::Struct(.., loc) => return Err(Self::unsupported("structs", loc)),
+            Stmt::Class(def) => return Err(Self::unsupported("classes", &def.location)),
+            Stmt::Enum(.., loc) => return Err(Self::unsupported("enums", loc)),
+            Stmt::Import(.., loc) => return Err(Self::unsupported("imports", loc)),
+            Stmt::Export(_, loc) => return Err(Self::unsupported("exports", loc)),
+            Stmt::Throw(_, loc) | Stmt::Try(.., loc) => return Err(Self::unsupported("exceptions", loc))
+        }
+        self.release_temps();
+        return Ok(());
+    }
+
+    fn unsupported(what: &str, loc: &Location) -> CompileError {
+        return CompileError::new(format!("The register backend does not support {}", what), loc.clone());
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.state_mut();
+        state.scope_depth -= 1;
+        while state.locals.last().is_some_and(|local| local.depth > state.scope_depth) {
+            state.locals.pop();
+        }
+        self.release_temps();
+    }
+
+    /// Frees every register above the innermost local.
+    fn release_temps(&mut self) {
+        let state = self.state_mut();
+        state.next_reg = state.locals.last().map_or(0, |local| local.reg as usize + 1);
+    }
+
+    fn alloc(&mut self) -> Result<Reg, CompileError> {
+        let reg = self.state().next_reg;
+        if reg > Reg::MAX as usize {
+            return Err(CompileError::new("Too many registers in function", self.location.clone()));
+        }
+        self.state_mut().next_reg += 1;
+        let function = self.current();
+        function.registers = function.registers.max(reg + 1);
+        return Ok(reg as Reg);
+    }
+
+    /// Allocates `count` consecutive registers and returns the first.
+    fn alloc_range(&mut self, count: usize) -> Result<Reg, CompileError> {
+        let first = self.state().next_reg as Reg;
+        for _ in 0..count {
+            self.alloc()?;
+        }
+        return Ok(first);
+    }
+
+    fn add_local(&mut self, name: &str, reg: Reg, is_const: bool) {
+        let state = self.state_mut();
+        state.locals.push(Local { name: name.to_string(), reg, depth: state.scope_depth, is_const });
+    }
+
+    fn declare_local(&mut self, name: &str, reg: Reg, is_const: bool, loc: &Location) -> Result<(), CompileError> {
+        let depth = self.state().scope_depth;
+        if self.state().locals.iter().any(|l| l.depth == depth && l.name == name) {
+            return Err(CompileError::new(format!("Variable `{}` is already defined in this scope", name), loc.clone()));
+        }
+        self.add_local(name, reg, is_const);
+        return Ok(());
+    }
+
+    fn is_local(&self, reg: Reg) -> bool {
+        return self.state().locals.iter().any(|local| local.reg == reg);
+    }
+
+    fn resolve(&self, name: &str) -> Option<Variable> {
+        if let Some(local) = self.state().locals.iter().rfind(|l| l.name == name) {
+            return Some(Variable::Local(local.reg, local.is_const));
+        }
+        if let Some(index) = self.globals.iter().rposition(|g| g.name == name) {
+            return Some(Variable::Global(index, self.globals[index].is_const));
+        }
+        return self.function_names.get(name).map(|&index| Variable::Function(index));
+    }
+
+    fn emit(&mut self, instr: Instr) -> usize {
+        let location = self.location.clone();
+        let function = self.current();
+        function.code.push(instr);
+        function.locations.push(location);
+        return function.code.len() - 1;
+    }
+
+    fn emit_const(&mut self, dst: Reg, val: StackSlot) {
+        let function = self.current();
+        function.constants.push(val);
+        let index = function.constants.len() - 1;
+        self.emit(Instr::LoadConst(dst, index as u32));
+    }
+
+    fn here(&mut self) -> u32 {
+        return self.current().code.len() as u32;
+    }
+
+    /// Points the jump at `pos` to the next instruction.
+    fn patch_jump(&mut self, pos: usize) {
+        let here = self.here();
+        match &mut self.current().code[pos] {
+            Instr::Jmp(target) | Instr::JmpIfFalse(_, target) | Instr::JmpIfTrue(_, target) | Instr::ForIter(_, _, target) =>
+                *target = here,
+            _ => unreachable!("not a jump")
+        }
+    }
+
+    fn generate_var_def(&mut self, name: &str, value: Option<&Expr>, is_const: bool, loc: &Location) -> Result<(), CompileError> {
+        if self.is_global_scope() {
+            let src = match value {
+                Some(expr) => self.generate_operand(expr)?,
+                None => {
+                    let reg = self.alloc()?;
+                    self.emit_const(reg, StackSlot::Nil);
+                    reg
+                }
+            };
+            self.globals.push(Global { name: name.to_string(), is_const });
+            self.emit(Instr::StoreGlobal(self.globals.len() as u32 - 1, src));
+            return Ok(());
+        }
+        let reg = self.alloc()?;
+        match value {
+            Some(expr) => self.generate_expr(expr, reg)?,
+            None => self.emit_const(reg, StackSlot::Nil)
+        }
+        return self.declare_local(name, reg, is_const, loc);
+    }
+
+    fn generate_assign(&mut self, target: &Expr, value: &Expr, loc: &Location) -> Result<(), CompileError> {
+        match target {
+            Expr::Var(name, var_loc) => match self.resolve(name) {
+                Some(Variable::Local(_, true) | Variable::Global(_, true) | Variable::Function(_)) =>
+                    return Err(CompileError::new(format!("Cannot assign to constant `{}`", name), loc.clone())),
+                Some(Variable::Local(reg, false)) => self.generate_expr(value, reg)?,
+                Some(Variable::Global(index, false)) => {
+                    let src = self.generate_operand(value)?;
+                    self.emit(Instr::StoreGlobal(index as u32, src));
+                }
+                None => return Err(CompileError::new(format!("Variable `{}` is not defined", name), var_loc.clone()))
+            },
+            Expr::Index(collection, index, _) => {
+                let collection = self.generate_operand(collection)?;
+                let index = self.generate_operand(index)?;
+                let src = self.generate_operand(value)?;
+                self.location = loc.clone();
+                self.emit(Instr::StoreIndex(collection, index, src));
+            }
+            Expr::Get(_, _, loc) => return Err(Self::unsupported("field access", loc)),
+            _ => return Err(CompileError::new("Invalid assignment target", loc.clone()))
+        }
+        return Ok(());
+    }
+
+    fn generate_if(&mut self, cond: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) -> Result<(), CompileError> {
+        let cond = self.generate_operand(cond)?;
+        let jmp_else = self.emit(Instr::JmpIfFalse(cond, 0));
+        self.generate_stmt(then_branch)?;
+        match else_branch {
+            Some(else_branch) => {
+                let jmp_end = self.emit(Instr::Jmp(0));
+                self.patch_jump(jmp_else);
+                self.generate_stmt(else_branch)?;
+                self.patch_jump(jmp_end);
+            }
+            None => self.patch_jump(jmp_else)
+        }
+        return Ok(());
+    }
+
+    fn generate_while(&mut self, cond: &Expr, body: &Stmt) -> Result<(), CompileError> {
+        let start = self.here();
+        let cond = self.generate_operand(cond)?;
+        let exit = self.emit(Instr::JmpIfFalse(cond, 0));
+        self.release_temps();
+        self.generate_stmt(body)?;
+        self.emit(Instr::Jmp(start));
+        self.patch_jump(exit);
+        return Ok(());
+    }
+
+    /// The iterable and the cursor after it are hidden locals; the loop
+    /// variable gets a fresh scope on every iteration.
+    fn generate_for(&mut self, name: &str, iterable: &Expr, body: &Stmt, loc: &Location) -> Result<(), CompileError> {
+        self.begin_scope();
+        let iterable_reg = self.alloc()?;
+        self.generate_expr(iterable, iterable_reg)?;
+        self.add_local("", iterable_reg, true);
+        let cursor = self.alloc()?;
+        self.emit_const(cursor, StackSlot::Int(0));
+        self.add_local("", cursor, true);
+
+        let item = self.alloc()?;
+        let start = self.here();
+        self.location = loc.clone();
+        let exit = self.emit(Instr::ForIter(item, iterable_reg, 0));
+        self.begin_scope();
+        self.add_local(name, item, false);
+        self.generate_stmt(body)?;
+        self.end_scope();
+        self.emit(Instr::Jmp(start));
+        self.patch_jump(exit);
+        self.end_scope();
+        return Ok(());
+    }
+
+    /// Functions can only be declared at the top level, where they cannot
+    /// capture locals. A function not hoisted by `declare_functions` is
+    /// declared before its body is generated so that it can call itself.
+    fn generate_fn_decl(&mut self, def: &FunctionDef) -> Result<(), CompileError> {
+        if !self.is_global_scope() {
+            return Err(Self::unsupported("nested functions", &def.location));
+        }
+        if def.params.len() > u8::MAX as usize {
+            return Err(CompileError::new("Too many parameters in function", def.location.clone()));
+        }
+        let name = def.name.clone().unwrap_or_default();
+        let index = match self.hoisted.remove(&name) {
+            Some(index) => index,
+            None => {
+                self.functions.push(Self::function(def.name.clone(), def.params.len()));
+                self.functions.len() - 1
+            }
+        };
+        self.function_names.insert(name, index);
+        self.states.push(FunctionState::new(index, 1));
+        for param in &def.params {
+            let reg = self.alloc()?;
+            self.add_local(param, reg, false);
+        }
+        for stmt in &def.body {
+            self.generate_stmt(stmt)?;
+        }
+        if !matches!(def.body.last(), Some(Stmt::Return(..))) {
+            self.location = def.location.clone();
+            let reg = self.alloc()?;
+            self.emit_const(reg, StackSlot::Nil);
+            self.emit(Instr::Ret(reg));
+        }
+        self.states.pop();
+        return Ok(());
+    }
+
+    fn generate_return(&mut self, value: Option<&Expr>, loc: &Location) -> Result<(), CompileError> {
+        if self.states.len() == 1 {
+            return Err(CompileError::new("Cannot return from top-level code", loc.clone()));
+        }
+        let src = match value {
+            Some(expr) => self.generate_operand(expr)?,
+            None => {
+                let reg = self.alloc()?;
+                self.emit_const(reg, StackSlot::Nil);
+                reg
+            }
+        };
+        self.emit(Instr::Ret(src));
+        return Ok(());
+    }
+
+    /// Generates `expr` into a register that is only read afterwards: the
+    /// register of a local variable itself, or a new temporary.
+    fn generate_operand(&mut self, expr: &Expr) -> Result<Reg, CompileError> {
+        if let Expr::Var(name, _) = expr && let Some(Variable::Local(reg, _)) = self.resolve(name) {
+            return Ok(reg);
+        }
+        let reg = self.alloc()?;
+        self.generate_expr(expr, reg)?;
+        return Ok(reg);
+    }
+
+    /// Generates `exprs` into consecutive registers and returns the first.
+    fn generate_sequence<'e>(&mut self, exprs: impl ExactSizeIterator<Item = &'e Expr>) -> Result<Reg, CompileError> {
+        let first = self.alloc_range(exprs.len())?;
+        for (i, expr) in exprs.enumerate() {
+            self.generate_expr(expr, first + i as Reg)?;
+        }
+        return Ok(first);
+    }
+
+    /// Generates `expr` and stores its value in `dst`. Only logical
+    /// operators write to `dst` before all their operands are read.
+    fn generate_expr(&mut self, expr: &Expr, dst: Reg) -> Result<(), CompileError> {
+        match expr {
+            Expr::Int(val, _) => self.emit_const(dst, StackSlot::Int(*val)),
+            Expr::BigInt(val, _) => self.emit_const(dst, StackSlot::from(val.clone())),
+            Expr::Float(val, _) => self.emit_const(dst, StackSlot::Float(*val)),
+            Expr::Str(val, _) => self.emit_const(dst, StackSlot::from(val.clone())),
+            Expr::Char(val, _) => self.emit_const(dst, StackSlot::Char(*val)),
+            Expr::Bool(val, _) => self.emit_const(dst, StackSlot::Bool(*val)),
+            Expr::Nil(_) => self.emit_const(dst, StackSlot::Nil),
+            Expr::Var(name, loc) => match self.resolve(name) {
+                Some(Variable::Local(reg, _)) => if reg != dst {
+                    self.emit(Instr::Move(dst, reg));
+                },
+                Some(Variable::Global(index, _)) => {
+                    self.location = loc.clone();
+                    self.emit(Instr::LoadGlobal(dst, index as u32));
+                }
+                Some(Variable::Function(_)) => return Err(Self::unsupported("function values", loc)),
+                None => return Err(CompileError::new(format!("Variable `{}` is not defined", name), loc.clone()))
+            },
+            Expr::Unary(op, operand, loc) => {
+                let src = self.generate_operand(operand)?;
+                self.location = loc.clone();
+                self.emit(match op {
+                    UnaryOp::Neg => Instr::Neg(dst, src),
+                    UnaryOp::Not => Instr::Not(dst, src)
+                });
+            }
+            Expr::Binary(op, lhs, rhs, loc) => {
+                let a = self.generate_operand(lhs)?;
+                let b = self.generate_operand(rhs)?;
+                self.location = loc.clone();
+                self.emit(match op {
+                    BinaryOp::Add => Instr::Add(dst, a, b),
+                    BinaryOp::Sub => Instr::Sub(dst, a, b),
+                    BinaryOp::Mul => Instr::Mul(dst, a, b),
+                    BinaryOp::Div => Instr::Div(dst, a, b),
+                    BinaryOp::FloorDiv => Instr::FloorDiv(dst, a, b),
+                    BinaryOp::Rem => Instr::Rem(dst, a, b),
+                    BinaryOp::Eq => Instr::Eq(dst, a, b),
+                    BinaryOp::NotEq => Instr::NotEq(dst, a, b),
+                    BinaryOp::Lt => Instr::Lt(dst, a, b),
+                    BinaryOp::LtEq => Instr::LtEq(dst, a, b),
+                    BinaryOp::Gt => Instr::Gt(dst, a, b),
+                    BinaryOp::GtEq => Instr::GtEq(dst, a, b)
+                });
+            }
+            Expr::Logical(op, lhs, rhs, loc) => self.generate_logical(*op, lhs, rhs, dst, loc)?,
+            Expr::Call(callee, args, loc) => self.generate_call(callee, args, dst, loc)?,
+            Expr::MethodCall(receiver, name, args, loc) => {
+                if !self.natives.iter().any(|n| n.name == *name) {
+                    return Err(Self::unsupported("methods", loc));
+                }
+                self.generate_native_call(name, Some(receiver), args, dst, loc)?;
+            }
+            Expr::List(items, loc) => {
+                let first = self.generate_sequence(items.iter())?;
+                self.location = loc.clone();
+                self.emit(Instr::MakeList(dst, first, items.len() as u32));
+            }
+            Expr::Map(entries, loc) => {
+                let operands: Vec<&Expr> = entries.iter().flat_map(|(key, value)| [key, value]).collect();
+                let first = self.generate_sequence(operands.into_iter())?;
+                self.location = loc.clone();
+                self.emit(Instr::MakeMap(dst, first, entries.len() as u32));
+            }
+            Expr::Interp(parts, loc) => {
+                let first = self.generate_sequence(parts.iter())?;
+                self.location = loc.clone();
+                self.emit(Instr::Concat(dst, first, parts.len() as u32));
+            }
+            Expr::Index(collection, index, loc) => {
+                let collection = self.generate_operand(collection)?;
+                let index = self.generate_operand(index)?;
+                self.location = loc.clone();
+                self.emit(Instr::Index(dst, collection, index));
+            }
+            Expr::Get(_, _, loc) => return Err(Self::unsupported("field access", loc)),
+            Expr::Function(def) => return Err(Self::unsupported("function values", &def.location)),
+            Expr::StructLit(_, _, loc) => return Err(Self::unsupported("structs", loc)),
+            Expr::SelfRef(loc) | Expr::Super(_, loc) => return Err(Self::unsupported("classes", loc)),
+            Expr::Match(_, _, loc) => return Err(Self::unsupported("`match`", loc)),
+            Expr::Propagate(_, loc) => return Err(Self::unsupported("`?`", loc))
+        }
+        return Ok(());
+    }
+
+    /// The left operand is kept in a temporary when `dst` is a local, so
+    /// that the right operand still sees the local's old value.
+    fn generate_logical(&mut self, op: LogicalOp, lhs: &Expr, rhs: &Expr, dst: Reg, loc: &Location) -> Result<(), CompileError> {
+        let reg = if self.is_local(dst) { self.alloc()? } else { dst };
+        self.generate_expr(lhs, reg)?;
+        self.location = loc.clone();
+        let jmp_end = self.emit(match op {
+            LogicalOp::And => Instr::JmpIfFalse(reg, 0),
+            LogicalOp::Or => Instr::JmpIfTrue(reg, 0)
+        });
+        self.generate_expr(rhs, reg)?;
+        self.patch_jump(jmp_end);
+        if reg != dst {
+            self.emit(Instr::Move(dst, reg));
+        }
+        return Ok(());
+    }
+
+    /// Calls are bound at compile time, to a top-level function or else to
+    /// the native of the same name.
+    fn generate_call(&mut self, callee: &Expr, args: &[Expr], dst: Reg, loc: &Location) -> Result<(), CompileError> {
+        let Expr::Var(name, _) = callee else {
+            return Err(Self::unsupported("function values", loc));
+        };
+        match self.resolve(name) {
+            Some(Variable::Function(index)) => {
+                let arity = self.functions[index].arity;
+                if args.len() != arity {
+                    return Err(CompileError::new(format!("`{}` expects {} arguments, got {}", name, arity, args.len()), loc.clone()));
+                }
+                let first = self.generate_sequence(args.iter())?;
+                self.location = loc.clone();
+                self.emit(Instr::Call(dst, index as u32, first, args.len() as u8));
+            }
+            Some(_) => return Err(Self::unsupported("function values", loc)),
+            None if EnumDef::result().variant_index(name).is_some() => return Err(Self::unsupported("results", loc)),
+            None => self.generate_native_call(name, None, args, dst, loc)?
+        }
+        return Ok(());
+    }
+
+    fn generate_native_call(&mut self, name: &str, receiver: Option<&Expr>, args: &[Expr], dst: Reg, loc: &Location) -> Result<(), CompileError> {
+        let index = match self.natives.iter().position(|n| n.name == name) {
+            Some(index) => index,
+            None => return Err(CompileError::new(format!("Function `{}` is not defined", name), loc.clone()))
+        };
+        let argc = args.len() + receiver.is_some() as usize;
+        let arity = self.natives[index].arity;
+        if arity != VARIADIC && argc != arity {
+            return Err(CompileError::new(format!("`{}` expects {} arguments, got {}", name, arity, argc), loc.clone()));
+        }
+        if argc > u8::MAX as usize {
+            return Err(CompileError::new("Too many arguments in function call", loc.clone()));
+        }
+        let operands: Vec<&Expr> = receiver.into_iter().chain(args).collect();
+        let first = self.generate_sequence(operands.into_iter())?;
+        self.location = loc.clone();
+        self.emit(Instr::CallNative(dst, index as u32, first, argc as u8));
+        return Ok(());
+    }
+}
@@ -0,0 +1,163 @@
+use std::{fmt, path::{Path, PathBuf}};
+
+use crate::{
+    compiler::{self, Options, error::CompileError},
+    vm::{
+        VM,
+        error::RuntimeError,
+        heap::GcStats,
+        map::Map,
+        native::NativeFn,
+        program::Program,
+        register::program::RegisterProgram,
+        stack_slot::Value
+    }
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Compile(CompileError),
+    Runtime(RuntimeError)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Compile(err) => err.fmt(f),
+            Error::Runtime(err) => err.fmt(f)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<CompileError> for Error {
+    fn from(err: CompileError) -> Self {
+        Error::Compile(err)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(err)
+    }
+}
+
+/// High-level entry point for embedding Cherry scripts into a Rust program.
+pub struct Engine {
+    vm: VM,
+    options: Options
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self { vm: VM::new(), options: Options::default() }
+    }
+
+    /// Exposes a Rust function to scripts compiled by this engine.
+    pub fn register_fn(&mut self, name: &str, arity: usize, function: NativeFn) {
+        self.vm.register_native(name, arity, function);
+    }
+
+    pub fn compile(&self, src: &str) -> Result<Program, CompileError> {
+        return compiler::compile(src, self.vm.natives(), &self.options);
+    }
+
+    /// Compiles `src`, read from the file at `path`. Its imports are
+    /// resolved relative to that file.
+    pub fn compile_file(&self, src: &str, path: &Path) -> Result<Program, CompileError> {
+        return compiler::compile_at(src, Some(path), self.vm.natives(), &self.options);
+    }
+
+    /// Compiles `src` for the register machine, an experimental backend
+    /// that supports only variables, arithmetic, control flow, top-level
+    /// functions, lists, maps, strings and native calls.
+    pub fn compile_registers(&self, src: &str) -> Result<RegisterProgram, CompileError> {
+        return compiler::compile_registers_at(src, None, self.vm.natives(), &self.options);
+    }
+
+    pub fn compile_registers_file(&self, src: &str, path: &Path) -> Result<RegisterProgram, CompileError> {
+        return compiler::compile_registers_at(src, Some(path), self.vm.natives(), &self.options);
+    }
+
+    /// Adds a directory to search for imported modules.
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.options.search_paths.push(path.into());
+    }
+
+    /// Enables whole-program type inference for scripts compiled afterwards.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.options.strict = strict;
+    }
+
+    /// Sets how much scripts compiled afterwards are optimized: 0 for not
+    /// at all, 1 for constant folding and dead code elimination, 2 for
+    /// superinstructions on top of that.
+    pub fn set_opt_level(&mut self, level: u8) {
+        self.options.opt_level = level;
+    }
+
+    pub fn run(&mut self, program: &Program) -> Result<Value, RuntimeError> {
+        return self.vm.run(program);
+    }
+
+    pub fn run_registers(&mut self, program: &RegisterProgram) -> Result<Value, RuntimeError> {
+        return self.vm.run_registers(program);
+    }
+
+    /// The items of `value` if it is a list returned by a run of this engine
+    /// that has not been collected since.
+    pub fn list(&self, value: &Value) -> Option<&Vec<Value>> {
+        if !self.vm.is_live(value) {
+            return None;
+        }
+        return self.vm.list(value);
+    }
+
+    /// The entries of `value` if it is a map returned by a run of this
+    /// engine that has not been collected since.
+    pub fn map(&self, value: &Value) -> Option<&Map> {
+        if !self.vm.is_live(value) {
+            return None;
+        }
+        return self.vm.map(value);
+    }
+
+    /// Formats `value` the way `print` shows it, following heap objects, or
+    /// returns `None` if the object it refers to has been collected.
+    pub fn format_value(&self, value: &Value) -> Option<String> {
+        if !self.vm.is_live(value) {
+            return None;
+        }
+        return Some(self.vm.format_value(value));
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        return self.vm.gc_stats();
+    }
+
+    /// Sets how many live heap objects trigger an automatic collection.
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        self.vm.set_gc_threshold(threshold);
+    }
+
+    pub fn collect_garbage(&mut self) -> usize {
+        return self.vm.collect_garbage();
+    }
+
+    /// Compiles and runs `src`, returning the value of its trailing
+    /// expression statement or `Value::Nil` if there is none. A returned
+    /// list, map or other heap value stays readable through `list`, `map`
+    /// and `format_value` until the next run returns; after that it may be
+    /// collected, and those accessors return `None` for it.
+    pub fn eval(&mut self, src: &str) -> Result<Value, Error> {
+        let program = self.compile(src)?;
+        return Ok(self.run(&program)?);
+    }
+}
@@ -0,0 +1,17 @@
+#![allow(clippy::needless_return)]
+
+pub mod compiler;
+pub mod vm;
+pub mod engine;
+
+pub use compiler::error::{CompileError, CompileWarning};
+pub use engine::{Engine, Error};
+pub use vm::{
+    VM,
+    error::RuntimeError,
+    heap::GcStats,
+    native::{NativeFn, VARIADIC},
+    program::Program,
+    register::program::RegisterProgram,
+    stack_slot::Value
+};
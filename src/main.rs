@@ -1,36 +1,106 @@
-use std::io::Read;
-
-pub mod compiler;
-use compiler::lexer::Lexer;
-use compiler::parser::Parser;
-
-mod vm;
-use vm::{
-    VM,
-    opcodes::OpCode,
-    chunk::Chunk,
-    stack_slot::StackSlot
-};
-
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if (args.len() != 2) {
-        println!("Error: Usage: cherry path/to/file.sd");
+#![allow(clippy::needless_return)]
+
+use std::{path::Path, process::ExitCode, time::Instant};
+
+use cherry_script::{Engine, Error, Value};
+
+/// The machine that runs the script: the stack VM, or the experimental
+/// register VM.
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Stack,
+    Register
+}
+
+fn main() -> ExitCode {
+    let mut engine = Engine::new();
+    let mut path = None;
+    let mut backend = Backend::Stack;
+    let mut stats = false;
+    let mut disassemble = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => engine.set_strict(true),
+            "-O0" => engine.set_opt_level(0),
+            "-O1" => engine.set_opt_level(1),
+            "-O2" => engine.set_opt_level(2),
+            "--path" => match args.next() {
+                Some(dir) => engine.add_search_path(dir),
+                None => return usage()
+            },
+            "--backend" => match args.next().as_deref() {
+                Some("stack") => backend = Backend::Stack,
+                Some("register") => backend = Backend::Register,
+                _ => return usage()
+            },
+            "--stats" => stats = true,
+            "--disassemble" => disassemble = true,
+            _ if arg.starts_with('-') || path.is_some() => return usage(),
+            _ => path = Some(arg)
+        }
     }
-    let file = std::fs::File::open(&args[1]);
-    let mut content = String::new();
-    match file {
-        Ok(mut file) => {
-            let _ = file.read_to_string(&mut content);
-        },
+    let path = match path {
+        Some(path) if !disassemble || backend == Backend::Stack => path,
+        _ => return usage()
+    };
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
         Err(err) => {
-            println!("Error: {}", err);
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
         }
+    };
+
+    let result = match backend {
+        Backend::Stack if disassemble => print_disassembly(&mut engine, &content, Path::new(&path)),
+        Backend::Stack => run_stack(&mut engine, &content, Path::new(&path), stats),
+        Backend::Register => run_registers(&mut engine, &content, Path::new(&path), stats)
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        return ExitCode::FAILURE;
     }
+    return ExitCode::SUCCESS;
+}
+
+fn run_stack(engine: &mut Engine, src: &str, path: &Path, stats: bool) -> Result<Value, Error> {
+    let program = engine.compile_file(src, path)?;
+    for warning in &program.warnings {
+        eprintln!("{}", warning);
+    }
+    let start = Instant::now();
+    let result = engine.run(&program);
+    if stats {
+        print_stats(program.instruction_count(), start);
+    }
+    return Ok(result?);
+}
+
+fn run_registers(engine: &mut Engine, src: &str, path: &Path, stats: bool) -> Result<Value, Error> {
+    let program = engine.compile_registers_file(src, path)?;
+    let start = Instant::now();
+    let result = engine.run_registers(&program);
+    if stats {
+        print_stats(program.instruction_count(), start);
+    }
+    return Ok(result?);
+}
+
+/// Prints the compiled program instead of running it.
+fn print_disassembly(engine: &mut Engine, src: &str, path: &Path) -> Result<Value, Error> {
+    let program = engine.compile_file(src, path)?;
+    print!("{}", program.disassemble());
+    return Ok(Value::Nil);
+}
+
+/// Reports the size of the compiled program and how long it ran on stderr.
+fn print_stats(instructions: usize, start: Instant) {
+    eprintln!("Instructions: {}", instructions);
+    eprintln!("Run time: {:.3} ms", start.elapsed().as_secs_f64() * 1000.0);
+}
 
-    let mut lexer = Lexer::new(content);
-    let tokens = lexer.tokenize();
-    let mut parser = Parser::new(tokens);
-    parser.generate();
-    parser.vm.execute();
-}
\ No newline at end of file
+fn usage() -> ExitCode {
+    eprintln!("Error: Usage: cherry [--strict] [-O0|-O1|-O2] [--backend stack|register] [--stats] [--disassemble] [--path dir]... path/to/file.sd");
+    return ExitCode::FAILURE;
+}
@@ -0,0 +1,292 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, Mul, Neg, Sub}
+};
+
+/// An integer of any size: a sign and a magnitude in 32-bit limbs, least
+/// significant first. The magnitude never ends in a zero limb, so zero has
+/// no limbs, and zero is never negative.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>
+}
+
+/// `10^9`, the largest power of ten that fits in a limb.
+const DECIMAL_BASE: u32 = 1_000_000_000;
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Self {
+        trim(&mut limbs);
+        let negative = negative && !limbs.is_empty();
+        return BigInt { negative, limbs };
+    }
+
+    pub fn from_i64(val: i64) -> Self {
+        let magnitude = val.unsigned_abs();
+        return Self::new(val < 0, vec![magnitude as u32, (magnitude >> 32) as u32]);
+    }
+
+    /// Parses decimal digits with an optional leading `-`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text)
+        };
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let mut limbs = Vec::new();
+        let first = digits.len() % 9;
+        let chunks = std::iter::once(&digits[..first])
+            .chain(digits.as_bytes()[first..].chunks(9).map(|chunk| std::str::from_utf8(chunk).unwrap()));
+        for chunk in chunks.filter(|chunk| !chunk.is_empty()) {
+            mul_small_add(&mut limbs, 10u32.pow(chunk.len() as u32), chunk.parse().unwrap());
+        }
+        return Some(Self::new(negative, limbs));
+    }
+
+    /// The value as an `i64`, if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let magnitude = self.limbs.iter().rev().fold(0u64, |acc, limb| acc << 32 | *limb as u64);
+        return match self.negative {
+            true => 0i64.checked_sub_unsigned(magnitude),
+            false => i64::try_from(magnitude).ok()
+        };
+    }
+
+    /// The nearest float to the value.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.limbs.iter().rev().fold(0.0, |acc, limb| acc * 4_294_967_296.0 + *limb as f64);
+        return if self.negative { -magnitude } else { magnitude };
+    }
+
+    pub fn is_zero(&self) -> bool {
+        return self.limbs.is_empty();
+    }
+
+    /// The quotient rounded towards zero and the remainder, which has the
+    /// sign of `self`, or `None` when `divisor` is zero.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitudes(&self.limbs, &divisor.limbs);
+        return Some((Self::new(self.negative != divisor.negative, quotient), Self::new(self.negative, remainder)));
+    }
+
+    /// The quotient rounded towards negative infinity, or `None` when
+    /// `divisor` is zero.
+    pub fn div_floor(&self, divisor: &BigInt) -> Option<BigInt> {
+        let (quotient, remainder) = self.div_rem(divisor)?;
+        if !remainder.is_zero() && self.negative != divisor.negative {
+            return Some(&quotient - &BigInt::from_i64(1));
+        }
+        return Some(quotient);
+    }
+
+    /// `self + rhs`, with the sign of `rhs` given separately so that
+    /// subtraction can flip it.
+    fn sum(&self, rhs_negative: bool, rhs: &[u32]) -> BigInt {
+        if self.negative == rhs_negative {
+            return Self::new(self.negative, add_magnitudes(&self.limbs, rhs));
+        }
+        return match cmp_magnitudes(&self.limbs, rhs) {
+            Ordering::Less => Self::new(rhs_negative, sub_magnitudes(rhs, &self.limbs)),
+            _ => Self::new(self.negative, sub_magnitudes(&self.limbs, rhs))
+        };
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, rhs: &BigInt) -> BigInt {
+        return self.sum(rhs.negative, &rhs.limbs);
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, rhs: &BigInt) -> BigInt {
+        return self.sum(!rhs.negative, &rhs.limbs);
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, rhs: &BigInt) -> BigInt {
+        return BigInt::new(self.negative != rhs.negative, mul_magnitudes(&self.limbs, &rhs.limbs));
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        return BigInt::new(!self.negative, self.limbs.clone());
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        return match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => cmp_magnitudes(&other.limbs, &self.limbs)
+        };
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut magnitude = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !magnitude.is_empty() {
+            chunks.push(div_small(&mut magnitude, DECIMAL_BASE));
+            trim(&mut magnitude);
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Debug for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}n", self);
+    }
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn cmp_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    return a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()));
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0;
+    for (i, limb) in long.iter().enumerate() {
+        let total = *limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(total as u32);
+        carry = total >> 32;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    return sum;
+}
+
+/// `a - b`, for `a` at least as large as `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, limb) in a.iter().enumerate() {
+        let (partial, overflow_a) = limb.overflowing_sub(*b.get(i).unwrap_or(&0));
+        let (total, overflow_b) = partial.overflowing_sub(borrow);
+        difference.push(total);
+        borrow = (overflow_a || overflow_b) as u32;
+    }
+    trim(&mut difference);
+    return difference;
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, y) in b.iter().enumerate() {
+            let total = *x as u64 * *y as u64 + product[i + j] as u64 + carry;
+            product[i + j] = total as u32;
+            carry = total >> 32;
+        }
+        product[i + b.len()] = carry as u32;
+    }
+    trim(&mut product);
+    return product;
+}
+
+/// Sets `limbs` to `limbs * factor + addend`.
+fn mul_small_add(limbs: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for limb in limbs.iter_mut() {
+        let total = *limb as u64 * factor as u64 + carry;
+        *limb = total as u32;
+        carry = total >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+}
+
+/// Divides `limbs` by `divisor` in place and returns the remainder.
+fn div_small(limbs: &mut [u32], divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in limbs.iter_mut().rev() {
+        let current = remainder << 32 | *limb as u64;
+        *limb = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    return remainder as u32;
+}
+
+/// Long division of `a` by the non-zero `b`, one bit at a time when `b`
+/// has more than one limb.
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_magnitudes(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let mut quotient = a.to_vec();
+        let remainder = div_small(&mut quotient, b[0]);
+        return (quotient, vec![remainder]);
+    }
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::with_capacity(b.len() + 1);
+    for bit in (0..a.len() * 32).rev() {
+        let mut carry = a[bit / 32] >> (bit % 32) & 1;
+        for limb in remainder.iter_mut() {
+            let next = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = next;
+        }
+        if carry > 0 {
+            remainder.push(carry);
+        }
+        if cmp_magnitudes(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitudes(&remainder, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    return (quotient, remainder);
+}
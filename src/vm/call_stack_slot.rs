@@ -1,4 +1,8 @@
+use super::heap::ObjRef;
+
 pub struct CallStackSlot {
     pub bc_pos: usize,
-    pub chunk_index: usize
-}
\ No newline at end of file
+    pub chunk_index: usize,
+    pub stack_base: usize,
+    pub closure: Option<ObjRef>
+}
@@ -1,77 +1,651 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::compiler::location::Location;
+
+use super::bigint::BigInt;
+use super::function::FunctionProto;
+use super::interner::Interner;
 use super::stack_slot::StackSlot;
 use super::OpCode;
 
+/// The widest operand in bytes, given by three `Wide` prefixes.
+pub const MAX_WIDTH: usize = 4;
+
+/// An operand of a decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    /// A one-byte operand, such as an argument count.
+    Byte(u8),
+    /// An index or a count.
+    Index(usize),
+    /// A jump target: a bytecode position, or an instruction index when
+    /// decoded by `Chunk::decode_all`.
+    Target(usize)
+}
+
+/// An instruction decoded from the bytecode at `start`.
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub opcode: u8,
+    pub operands: Vec<Operand>,
+    pub start: usize
+}
+
+impl Instruction {
+    pub fn op(&self) -> Option<OpCode> {
+        return OpCode::from_u8(self.opcode);
+    }
+
+    /// The length in bytes of the instruction when its index and target
+    /// operands are `width` bytes wide, prefixes included.
+    fn len(&self, width: usize) -> usize {
+        let operands: usize = self.operands.iter()
+            .map(|operand| match operand {
+                Operand::Byte(_) => 1,
+                _ => width
+            })
+            .sum();
+        return width + operands;
+    }
+}
+
+/// The number of bytes `index` needs as an operand, if it fits in
+/// `MAX_WIDTH` bytes.
+pub fn operand_width(index: usize) -> Option<usize> {
+    return match index as u64 {
+        0..=0xFF => Some(1),
+        0x100..=0xFFFF => Some(2),
+        0x1_0000..=0xFF_FFFF => Some(3),
+        0x100_0000..=0xFFFF_FFFF => Some(4),
+        _ => None
+    };
+}
+
+fn encode_operand(bytecode: &mut Vec<u8>, index: usize, width: usize) {
+    for byte in (0..width).rev() {
+        bytecode.push((index >> (8 * byte)) as u8);
+    }
+}
+
+/// What makes two constants interchangeable: floats are compared by
+/// their bits, so `0.0` and `-0.0` stay apart.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum ConstKey {
+    Nil,
+    Int(i64),
+    BigInt(Rc<BigInt>),
+    Float(u64),
+    Bool(bool),
+    Char(char),
+    Str(Rc<str>)
+}
+
+impl ConstKey {
+    fn of(slot: &StackSlot) -> Option<Self> {
+        return match slot {
+            StackSlot::Nil => Some(ConstKey::Nil),
+            StackSlot::Int(val) => Some(ConstKey::Int(*val)),
+            StackSlot::BigInt(val) => Some(ConstKey::BigInt(Rc::clone(val))),
+            StackSlot::Float(val) => Some(ConstKey::Float(val.to_bits())),
+            StackSlot::Bool(val) => Some(ConstKey::Bool(*val)),
+            StackSlot::Char(val) => Some(ConstKey::Char(*val)),
+            StackSlot::Str(val) => Some(ConstKey::Str(Rc::clone(val))),
+            _ => None
+        };
+    }
+}
+
+/// Reads the operands of one instruction, `width` bytes per index.
+struct Decoder<'a> {
+    code: &'a [u8],
+    pos: usize,
+    width: usize,
+    operands: Vec<Operand>
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) {
+        self.operands.push(Operand::Byte(self.code[self.pos]));
+        self.pos += 1;
+    }
+
+    fn read(&mut self) -> usize {
+        let index = self.code[self.pos..self.pos + self.width].iter()
+            .fold(0, |index, byte| index << 8 | *byte as usize);
+        self.pos += self.width;
+        return index;
+    }
+
+    fn index(&mut self) -> usize {
+        let index = self.read();
+        self.operands.push(Operand::Index(index));
+        return index;
+    }
+
+    fn target(&mut self) {
+        let target = self.read();
+        self.operands.push(Operand::Target(target));
+    }
+}
+
+/// Index, count and target operands are big-endian and one byte wide,
+/// plus one byte for each `Wide` prefix in front of the opcode; the
+/// emitters pick the fewest prefixes that fit. Jumps are emitted at
+/// `MAX_WIDTH` so they can be patched, and `compact` narrows them once
+/// the chunk is complete.
+///
+/// `locations` maps bytecode positions to source positions: each entry
+/// covers the instructions from its position up to the next entry.
+/// `overflow` holds the position of the first instruction with an
+/// operand too large for `MAX_WIDTH` bytes.
+///
+/// Equal constants share one entry of `constants`, and string constants
+/// come from `interner`, which the chunks of a program share.
 #[derive(Clone)]
 pub struct Chunk {
     pub constants: Vec<StackSlot>,
     pub bytecode: Vec<u8>,
-    pub locals: Vec<Option<StackSlot>>
+    pub locations: Vec<(usize, Location)>,
+    pub overflow: Option<usize>,
+    pub interner: Rc<Interner>,
+    const_indices: HashMap<ConstKey, usize>
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
     pub fn new() -> Self {
-        Self { constants: Vec::new(), bytecode: Vec::new(), locals: Vec::new() }
+        return Self::with_interner(Rc::new(Interner::new()));
+    }
+
+    pub fn with_interner(interner: Rc<Interner>) -> Self {
+        Self {
+            constants: Vec::new(),
+            bytecode: Vec::new(),
+            locations: Vec::new(),
+            overflow: None,
+            interner,
+            const_indices: HashMap::new()
+        }
+    }
+
+    /// Attributes the instructions emitted from now on to `loc`.
+    pub fn mark(&mut self, loc: &Location) {
+        let pos = self.bytecode.len();
+        match self.locations.last_mut() {
+            Some((_, last)) if last == loc => {}
+            Some((last_pos, last)) if *last_pos == pos => *last = loc.clone(),
+            _ => self.locations.push((pos, loc.clone()))
+        }
+    }
+
+    /// The source position of the instruction at `pos`.
+    pub fn location_at(&self, pos: usize) -> Option<Location> {
+        let index = self.locations.partition_point(|(start, _)| *start <= pos);
+        return index.checked_sub(1).map(|index| self.locations[index].1.clone());
+    }
+
+    /// Decodes the instruction at `pos`, returning it with its length in
+    /// bytes, prefixes included. Targets are bytecode positions.
+    /// `functions` gives the number of upvalues a `Closure` lists.
+    pub fn decode(&self, pos: usize, functions: &[FunctionProto]) -> (Instruction, usize) {
+        let mut opcode_pos = pos;
+        while matches!(OpCode::from_u8(self.bytecode[opcode_pos]), Some(OpCode::Wide)) {
+            opcode_pos += 1;
+        }
+        let opcode = self.bytecode[opcode_pos];
+        let mut decoder = Decoder { code: &self.bytecode, pos: opcode_pos + 1, width: opcode_pos - pos + 1, operands: Vec::new() };
+        match OpCode::from_u8(opcode) {
+            Some(OpCode::Call | OpCode::InRange) => decoder.byte(),
+            Some(OpCode::Push | OpCode::StoreGlob | OpCode::LoadGlob | OpCode::StoreLoc | OpCode::LoadLoc |
+                 OpCode::MakeList | OpCode::MakeMap | OpCode::GetField | OpCode::SetField | OpCode::GetUpvalue |
+                 OpCode::SetUpvalue | OpCode::Class | OpCode::Method | OpCode::GetSuper | OpCode::GetPayload |
+                 OpCode::IsStruct | OpCode::IsList | OpCode::Concat | OpCode::AddConst) => {
+                decoder.index();
+            }
+            Some(OpCode::Jmp | OpCode::JmpIf | OpCode::JmpIfFalse | OpCode::PushHandler) => decoder.target(),
+            Some(OpCode::CallNative | OpCode::Invoke | OpCode::SuperInvoke) => {
+                decoder.index();
+                decoder.byte();
+            }
+            Some(OpCode::JmpIfFalseCmp) => {
+                decoder.byte();
+                decoder.target();
+            }
+            Some(OpCode::ForIter) => {
+                decoder.index();
+                decoder.target();
+            }
+            Some(OpCode::MakeVariant | OpCode::IsVariant | OpCode::IncGlobal) => {
+                decoder.index();
+                decoder.index();
+            }
+            Some(OpCode::GetFieldAt | OpCode::SetFieldAt) => {
+                for _ in 0..3 {
+                    decoder.index();
+                }
+            }
+            Some(OpCode::MakeStruct) => {
+                decoder.index();
+                for _ in 0..decoder.index() {
+                    decoder.index();
+                }
+            }
+            Some(OpCode::JmpTable) => {
+                decoder.index();
+                for _ in 0..=decoder.index() {
+                    decoder.target();
+                }
+            }
+            Some(OpCode::Closure) => {
+                let function = decoder.index();
+                for _ in 0..functions[function].upvalue_count {
+                    decoder.byte();
+                    decoder.index();
+                }
+            }
+            _ => {}
+        }
+        let instruction = Instruction { opcode, operands: decoder.operands, start: pos };
+        return (instruction, decoder.pos - pos);
+    }
+
+    /// The length in bytes of the instruction at `pos`, prefixes and
+    /// operands included.
+    pub fn instruction_len(&self, pos: usize, functions: &[FunctionProto]) -> usize {
+        return self.decode(pos, functions).1;
+    }
+
+    /// Decodes every instruction. Targets are given as the index of the
+    /// instruction they jump to, or the instruction count for the end of
+    /// the chunk.
+    pub fn decode_all(&self, functions: &[FunctionProto]) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        let mut index_at = vec![usize::MAX; self.bytecode.len() + 1];
+        let mut pos = 0;
+        while pos < self.bytecode.len() {
+            let (instruction, len) = self.decode(pos, functions);
+            index_at[pos] = instructions.len();
+            instructions.push(instruction);
+            pos += len;
+        }
+        index_at[pos] = instructions.len();
+        for operand in instructions.iter_mut().flat_map(|instruction| &mut instruction.operands) {
+            if let Operand::Target(target) = operand {
+                *target = index_at[*target];
+            }
+        }
+        return instructions;
+    }
+
+    /// Replaces the bytecode with `instructions`, whose targets are
+    /// instruction indices, each with the fewest prefixes its operands
+    /// allow. Source locations follow the instruction decoded at their
+    /// position; one that replaces several keeps the `start` of the first.
+    /// Fails with the `start` of an instruction whose operand does not fit
+    /// in `MAX_WIDTH` bytes, leaving the chunk as it was.
+    pub fn assemble(&mut self, instructions: &[Instruction]) -> Result<(), usize> {
+        let mut widths = vec![1; instructions.len()];
+        let mut starts = vec![0; instructions.len() + 1];
+        loop {
+            for (i, instruction) in instructions.iter().enumerate() {
+                starts[i + 1] = starts[i] + instruction.len(widths[i]);
+            }
+            let mut widened = false;
+            for (i, instruction) in instructions.iter().enumerate() {
+                for operand in &instruction.operands {
+                    let index = match *operand {
+                        Operand::Byte(_) => continue,
+                        Operand::Index(index) => index,
+                        Operand::Target(target) => starts[target]
+                    };
+                    let width = operand_width(index).ok_or(instruction.start)?;
+                    if width > widths[i] {
+                        widths[i] = width;
+                        widened = true;
+                    }
+                }
+            }
+            if !widened {
+                break;
+            }
+        }
+
+        let mut bytecode = Vec::with_capacity(starts[instructions.len()]);
+        for (instruction, &width) in instructions.iter().zip(&widths) {
+            bytecode.resize(bytecode.len() + width - 1, OpCode::Wide as u8);
+            bytecode.push(instruction.opcode);
+            for operand in &instruction.operands {
+                match *operand {
+                    Operand::Byte(byte) => bytecode.push(byte),
+                    Operand::Index(index) => encode_operand(&mut bytecode, index, width),
+                    Operand::Target(target) => encode_operand(&mut bytecode, starts[target], width)
+                }
+            }
+        }
+
+        let mut moved = vec![0; self.bytecode.len() + 1];
+        for (i, instruction) in instructions.iter().enumerate() {
+            let end = instructions.get(i + 1).map_or(self.bytecode.len(), |next| next.start);
+            moved[instruction.start..end].fill(starts[i]);
+        }
+        moved[self.bytecode.len()] = bytecode.len();
+        let mut locations: Vec<(usize, Location)> = Vec::with_capacity(self.locations.len());
+        for (pos, loc) in self.locations.drain(..) {
+            let pos = moved[pos];
+            match locations.last_mut() {
+                Some((_, last)) if *last == loc => {}
+                Some((last_pos, last)) if *last_pos == pos => *last = loc,
+                _ => locations.push((pos, loc))
+            }
+        }
+        self.locations = locations;
+        self.bytecode = bytecode;
+        return Ok(());
+    }
+
+    /// Re-encodes the chunk with every operand as narrow as it can be,
+    /// jump targets included. Fails with the position of an instruction
+    /// whose operand does not fit in `MAX_WIDTH` bytes.
+    pub fn compact(&mut self, functions: &[FunctionProto]) -> Result<(), usize> {
+        if let Some(pos) = self.overflow {
+            return Err(pos);
+        }
+        let instructions = self.decode_all(functions);
+        return self.assemble(&instructions);
     }
-    
+
     pub fn emit_byte(&mut self, byte: u8) -> usize {
         self.bytecode.push(byte);
         return self.bytecode.len() - 1;
     }
 
-    pub fn emit_const(&mut self, slot: StackSlot) -> usize {
-        self.constants.push(slot);
-        let index = self.constants.len() - 1;
-        let first_instruction = self.emit_byte(OpCode::Push as u8);
-        self.emit_byte(((index >> 16) & 0xFF) as u8);
-        self.emit_byte(((index >> 8) & 0xFF) as u8);
-        self.emit_byte((index & 0xFF) as u8);
+    /// The narrowest width that fits all of `indices`. An index too large
+    /// for any width is recorded in `overflow`.
+    fn width_of(&mut self, indices: &[usize]) -> usize {
+        let mut width = 1;
+        for index in indices {
+            match operand_width(*index) {
+                Some(needed) => width = width.max(needed),
+                None => {
+                    self.overflow.get_or_insert(self.bytecode.len());
+                    width = MAX_WIDTH;
+                }
+            }
+        }
+        return width;
+    }
+
+    /// Emits `opcode` behind the prefixes that make its operands `width`
+    /// bytes wide.
+    fn emit_opcode(&mut self, opcode: OpCode, width: usize) -> usize {
+        let first_instruction = self.bytecode.len();
+        for _ in 1..width {
+            self.emit_byte(OpCode::Wide as u8);
+        }
+        self.emit_byte(opcode as u8);
         return first_instruction;
     }
 
-    pub fn emit_jmp(&mut self, index: u32) -> usize {
-        let first_instruction = self.emit_byte(OpCode::Jmp as u8);
-        self.emit_byte(((index >> 16) & 0xFF) as u8);
-        self.emit_byte(((index >> 8) & 0xFF) as u8);
-        self.emit_byte((index & 0xFF) as u8);
+    /// Emits a `width`-byte big-endian operand.
+    pub fn emit_operand(&mut self, index: usize, width: usize) -> usize {
+        let first_byte = self.bytecode.len();
+        encode_operand(&mut self.bytecode, index, width);
+        return first_byte;
+    }
+
+    /// Emits `opcode` followed by `indices`, as narrow as they all fit.
+    pub fn emit_op_indices(&mut self, opcode: OpCode, indices: &[usize]) -> usize {
+        let width = self.width_of(indices);
+        let first_instruction = self.emit_opcode(opcode, width);
+        for index in indices {
+            self.emit_operand(*index, width);
+        }
         return first_instruction;
     }
 
-    pub fn emit_jmp_if(&mut self, index: u32) -> usize {
-        let first_instruction = self.emit_byte(OpCode::JmpIf as u8);
-        self.emit_byte(((index >> 16) & 0xFF) as u8);
-        self.emit_byte(((index >> 8) & 0xFF) as u8);
-        self.emit_byte((index & 0xFF) as u8);
+    pub fn emit_op_index(&mut self, opcode: OpCode, index: usize) -> usize {
+        return self.emit_op_indices(opcode, &[index]);
+    }
+
+    /// Emits `opcode` with an index operand followed by a one-byte operand.
+    fn emit_op_index_byte(&mut self, opcode: OpCode, index: usize, byte: u8) -> usize {
+        let first_instruction = self.emit_op_index(opcode, index);
+        self.emit_byte(byte);
         return first_instruction;
     }
 
-    pub fn emit_call(&mut self, index: usize) -> usize {
+    /// Emits `opcode` followed by `indices` and `targets` at `MAX_WIDTH`,
+    /// so that the targets can be patched with any position. Returns the
+    /// position of the first target operand.
+    fn emit_op_targets(&mut self, opcode: OpCode, indices: &[usize], targets: &[usize]) -> usize {
+        self.width_of(indices);
+        self.emit_opcode(opcode, MAX_WIDTH);
+        for index in indices {
+            self.emit_operand(*index, MAX_WIDTH);
+        }
+        let first_target = self.bytecode.len();
+        for target in targets {
+            self.emit_operand(*target, MAX_WIDTH);
+        }
+        return first_target;
+    }
+
+    /// Adds `slot` to the constant pool, or finds the entry of an equal
+    /// constant. Strings are interned.
+    pub fn add_const(&mut self, slot: StackSlot) -> usize {
+        let slot = match slot {
+            StackSlot::Str(text) => StackSlot::Str(self.interner.intern(&text)),
+            slot => slot
+        };
+        let key = ConstKey::of(&slot);
+        if let Some(key) = &key && let Some(index) = self.const_indices.get(key) {
+            return *index;
+        }
+        self.constants.push(slot);
+        let index = self.constants.len() - 1;
+        if let Some(key) = key {
+            self.const_indices.insert(key, index);
+        }
+        return index;
+    }
+
+    /// Drops the constants from `len` on.
+    pub fn truncate_constants(&mut self, len: usize) {
+        self.constants.truncate(len);
+        self.const_indices.retain(|_, index| *index < len);
+    }
+
+    pub fn emit_const(&mut self, slot: StackSlot) -> usize {
+        let index = self.add_const(slot);
+        return self.emit_op_index(OpCode::Push, index);
+    }
+
+    /// The jump emitters return the position of the target operand, for
+    /// `patch_jmp`.
+    pub fn emit_jmp(&mut self, target: usize) -> usize {
+        return self.emit_op_targets(OpCode::Jmp, &[], &[target]);
+    }
+
+    pub fn emit_jmp_if(&mut self, target: usize) -> usize {
+        return self.emit_op_targets(OpCode::JmpIf, &[], &[target]);
+    }
+
+    pub fn emit_jmp_if_false(&mut self, target: usize) -> usize {
+        return self.emit_op_targets(OpCode::JmpIfFalse, &[], &[target]);
+    }
+
+    /// Rewrites the `MAX_WIDTH` target operand at `operand`.
+    pub fn patch_jmp(&mut self, operand: usize, target: usize) {
+        if operand_width(target).is_none() {
+            self.overflow.get_or_insert(operand);
+        }
+        for (i, byte) in (0..MAX_WIDTH).rev().enumerate() {
+            self.bytecode[operand + i] = (target >> (8 * byte)) as u8;
+        }
+    }
+
+    /// Calls the value sitting below the `argc` arguments on the stack.
+    pub fn emit_call(&mut self, argc: u8) -> usize {
         let first_instruction = self.emit_byte(OpCode::Call as u8);
-        self.emit_byte(((index >> 16) & 0xFF) as u8);
-        self.emit_byte(((index >> 8) & 0xFF) as u8);
-        self.emit_byte((index & 0xFF) as u8);
+        self.emit_byte(argc);
         return first_instruction;
     }
 
-    pub fn create_local(&mut self) -> usize {
-        self.locals.push(None);
-        return self.locals.len() - 1;
+    pub fn emit_call_native(&mut self, index: usize, argc: u8) -> usize {
+        return self.emit_op_index_byte(OpCode::CallNative, index, argc);
     }
 
-    pub fn store_local(&mut self, index: usize) {
-        self.emit_byte(OpCode::StoreLoc as u8);
-        self.emit_byte(((index >> 16) & 0xFF) as u8);
-        self.emit_byte(((index >> 8) & 0xFF) as u8);
-        self.emit_byte((index & 0xFF) as u8);
+    pub fn emit_make_list(&mut self, count: usize) -> usize {
+        return self.emit_op_index(OpCode::MakeList, count);
     }
 
-    pub fn load_local(&mut self, index: usize) {
-        self.emit_byte(OpCode::LoadLoc as u8);
-        self.emit_byte(((index >> 16) & 0xFF) as u8);
-        self.emit_byte(((index >> 8) & 0xFF) as u8);
-        self.emit_byte((index & 0xFF) as u8);
+    pub fn emit_make_map(&mut self, count: usize) -> usize {
+        return self.emit_op_index(OpCode::MakeMap, count);
+    }
+
+    /// Emits `Concat`, which joins the top `count` values into a str the
+    /// way `print` shows them.
+    pub fn emit_concat(&mut self, count: usize) -> usize {
+        return self.emit_op_index(OpCode::Concat, count);
+    }
+
+    pub fn emit_get_field(&mut self, name: &str) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index(OpCode::GetField, index);
+    }
+
+    pub fn emit_set_field(&mut self, name: &str) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index(OpCode::SetField, index);
+    }
+
+    /// Emits `MakeStruct` for the struct `def`, storing the values on top of
+    /// the stack into the fields at `offsets`, in push order.
+    pub fn emit_make_struct(&mut self, def: usize, offsets: &[usize]) -> usize {
+        let mut indices = vec![def, offsets.len()];
+        indices.extend_from_slice(offsets);
+        return self.emit_op_indices(OpCode::MakeStruct, &indices);
+    }
+
+    /// Emits a field read that uses `offset` directly when the object is an
+    /// instance of struct `def`, and looks `name` up otherwise.
+    pub fn emit_get_field_at(&mut self, name: &str, def: usize, offset: usize) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_indices(OpCode::GetFieldAt, &[index, def, offset]);
+    }
+
+    pub fn emit_set_field_at(&mut self, name: &str, def: usize, offset: usize) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_indices(OpCode::SetFieldAt, &[index, def, offset]);
+    }
+
+    /// Emits `MakeVariant`, which takes the payload of variant `variant` of
+    /// enum `def` from the top of the stack.
+    pub fn emit_make_variant(&mut self, def: usize, variant: usize) -> usize {
+        return self.emit_op_indices(OpCode::MakeVariant, &[def, variant]);
+    }
+
+    pub fn emit_is_variant(&mut self, def: usize, variant: usize) -> usize {
+        return self.emit_op_indices(OpCode::IsVariant, &[def, variant]);
+    }
+
+    pub fn emit_in_range(&mut self, inclusive: bool) -> usize {
+        let first_instruction = self.emit_byte(OpCode::InRange as u8);
+        self.emit_byte(inclusive as u8);
+        return first_instruction;
+    }
+
+    /// Emits a `JmpTable` for int values `min..min + count`, leaving the
+    /// default target and the `count` case targets to be patched. Returns
+    /// the position of the default target operand; case `i` follows it at
+    /// `+ MAX_WIDTH * (i + 1)`.
+    pub fn emit_jmp_table(&mut self, min: i64, count: usize) -> usize {
+        let index = self.add_const(StackSlot::Int(min));
+        return self.emit_op_targets(OpCode::JmpTable, &[index, count], &vec![0; count + 1]);
+    }
+
+    /// Emits `ForIter` over the iterable stored in local `slot` (its cursor
+    /// lives in `slot + 1`), returning the exit target operand to be patched.
+    pub fn emit_for_iter(&mut self, slot: usize) -> usize {
+        return self.emit_op_targets(OpCode::ForIter, &[slot], &[0]);
+    }
+
+    /// Emits `Closure` for `function` followed by one `(is_local, index)`
+    /// operand per captured variable.
+    pub fn emit_closure(&mut self, function: usize, upvalues: &[(bool, usize)]) -> usize {
+        let mut indices = vec![function];
+        indices.extend(upvalues.iter().map(|(_, index)| *index));
+        let width = self.width_of(&indices);
+        let first_instruction = self.emit_opcode(OpCode::Closure, width);
+        self.emit_operand(function, width);
+        for (is_local, index) in upvalues {
+            self.emit_byte(*is_local as u8);
+            self.emit_operand(*index, width);
+        }
+        return first_instruction;
+    }
+
+    /// Emits `PushHandler`, returning the handler target operand to be
+    /// patched.
+    pub fn emit_push_handler(&mut self) -> usize {
+        return self.emit_op_targets(OpCode::PushHandler, &[], &[0]);
+    }
+
+    pub fn emit_class(&mut self, name: &str) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index(OpCode::Class, index);
+    }
+
+    pub fn emit_method(&mut self, name: &str) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index(OpCode::Method, index);
+    }
+
+    /// Calls method `name` on the receiver sitting below the `argc`
+    /// arguments on the stack.
+    pub fn emit_invoke(&mut self, name: &str, argc: u8) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index_byte(OpCode::Invoke, index, argc);
     }
 
-    pub fn get_local(&mut self, index: usize) -> StackSlot {
-        return self.locals[index].clone().unwrap();
+    pub fn emit_get_super(&mut self, name: &str) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index(OpCode::GetSuper, index);
     }
-}
\ No newline at end of file
+
+    /// Like `emit_invoke`, but looks `name` up in the superclass that is
+    /// on top of the stack.
+    pub fn emit_super_invoke(&mut self, name: &str, argc: u8) -> usize {
+        let index = self.add_const(StackSlot::from(name));
+        return self.emit_op_index_byte(OpCode::SuperInvoke, index, argc);
+    }
+
+    pub fn load_upvalue(&mut self, index: usize) {
+        self.emit_op_index(OpCode::GetUpvalue, index);
+    }
+
+    pub fn store_upvalue(&mut self, index: usize) {
+        self.emit_op_index(OpCode::SetUpvalue, index);
+    }
+
+    pub fn store_global(&mut self, index: usize) {
+        self.emit_op_index(OpCode::StoreGlob, index);
+    }
+
+    pub fn load_global(&mut self, index: usize) -> usize {
+        return self.emit_op_index(OpCode::LoadGlob, index);
+    }
+
+    pub fn store_local(&mut self, index: usize) {
+        self.emit_op_index(OpCode::StoreLoc, index);
+    }
+
+    pub fn load_local(&mut self, index: usize) {
+        self.emit_op_index(OpCode::LoadLoc, index);
+    }
+}
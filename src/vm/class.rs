@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+
+use super::{
+    heap::ObjRef,
+    map::Map
+};
+
+/// A class created by a `class` declaration. Inherited methods are copied
+/// into `methods` when the class is created, so lookups never walk the
+/// superclass chain.
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<ObjRef>,
+    pub methods: HashMap<String, ObjRef>
+}
+
+impl Class {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), superclass: None, methods: HashMap::new() }
+    }
+}
+
+/// An object created by calling a class. Fields are added by assignment
+/// and kept in insertion order.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: Map
+}
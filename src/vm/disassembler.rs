@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use super::{
+    chunk::{Chunk, Operand},
+    opcodes::OpCode,
+    program::Program
+};
+
+impl Program {
+    /// A listing of every chunk: the size of its constant pool and each
+    /// constant, then one line per instruction with its position, source
+    /// position and operands. Prefixed instructions show their `Wide`
+    /// prefixes, and constant operands the constant they refer to.
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        let constants: usize = self.chunks.iter().map(|chunk| chunk.constants.len()).sum();
+        writeln!(out, "{} chunks, {} constants, {} interned strings", self.chunks.len(), constants, self.interner.len()).unwrap();
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let name = match self.functions.iter().find(|function| function.chunk_index == index) {
+                Some(function) => function.name.clone().unwrap_or_else(|| String::from("<anonymous>")),
+                None => String::from("<script>")
+            };
+            writeln!(out).unwrap();
+            writeln!(out, "== {} ==", name).unwrap();
+            self.disassemble_chunk(chunk, &mut out);
+        }
+        return out;
+    }
+
+    fn disassemble_chunk(&self, chunk: &Chunk, out: &mut String) {
+        let strings = chunk.constants.iter().filter(|slot| slot.as_str().is_some()).count();
+        writeln!(out, "constants: {} ({} strings), bytecode: {} bytes", chunk.constants.len(), strings, chunk.bytecode.len()).unwrap();
+        for (index, slot) in chunk.constants.iter().enumerate() {
+            writeln!(out, "  [{}] {:?}", index, slot).unwrap();
+        }
+
+        let mut last_location = None;
+        let mut pos = 0;
+        while pos < chunk.bytecode.len() {
+            let (instruction, len) = chunk.decode(pos, &self.functions);
+            let location = chunk.location_at(pos);
+            let source = match &location {
+                Some(loc) if location != last_location => format!("{}:{}", loc.line, loc.col),
+                _ => String::from("|")
+            };
+            last_location = location;
+
+            let prefixes = "Wide ".repeat(bytecode_prefixes(chunk, pos));
+            let name = match instruction.op() {
+                Some(op) => format!("{:?}", op),
+                None => format!("<{}>", instruction.opcode)
+            };
+            write!(out, "{:04} {:>7}  {}{}", pos, source, prefixes, name).unwrap();
+            let constant = constant_operand(instruction.op());
+            for (i, operand) in instruction.operands.iter().enumerate() {
+                match operand {
+                    Operand::Byte(byte) => write!(out, " {}", byte).unwrap(),
+                    Operand::Index(index) if constant == Some(i) => write!(out, " {} ({:?})", index, chunk.constants[*index]).unwrap(),
+                    Operand::Index(index) => write!(out, " {}", index).unwrap(),
+                    Operand::Target(target) => write!(out, " -> {:04}", target).unwrap()
+                }
+            }
+            writeln!(out).unwrap();
+            pos += len;
+        }
+    }
+}
+
+/// The number of `Wide` prefixes in front of the instruction at `pos`.
+fn bytecode_prefixes(chunk: &Chunk, pos: usize) -> usize {
+    return chunk.bytecode[pos..].iter()
+        .take_while(|byte| matches!(OpCode::from_u8(**byte), Some(OpCode::Wide)))
+        .count();
+}
+
+/// Which operand of an instruction indexes the constant pool.
+fn constant_operand(op: Option<OpCode>) -> Option<usize> {
+    return match op {
+        Some(OpCode::Push | OpCode::GetField | OpCode::SetField | OpCode::GetFieldAt | OpCode::SetFieldAt |
+             OpCode::Class | OpCode::Method | OpCode::Invoke | OpCode::GetSuper | OpCode::SuperInvoke |
+             OpCode::JmpTable | OpCode::AddConst) => Some(0),
+        Some(OpCode::IncGlobal) => Some(1),
+        _ => None
+    };
+}
@@ -0,0 +1,32 @@
+/// Index of the built-in `Result` enum, which every program declares first.
+pub const RESULT_ENUM: usize = 0;
+pub const OK_VARIANT: usize = 0;
+pub const ERR_VARIANT: usize = 1;
+
+/// An `enum` declaration. Variants are addressed by their position.
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<VariantDef>
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantDef {
+    pub name: String,
+    pub arity: usize
+}
+
+impl EnumDef {
+    /// The built-in `enum Result { Ok(value), Err(error) }`.
+    pub fn result() -> Self {
+        let variants = vec![
+            VariantDef { name: "Ok".to_string(), arity: 1 },
+            VariantDef { name: "Err".to_string(), arity: 1 }
+        ];
+        return Self { name: "Result".to_string(), variants };
+    }
+
+    pub fn variant_index(&self, name: &str) -> Option<usize> {
+        return self.variants.iter().position(|variant| variant.name == name);
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+
+use crate::compiler::location::Location;
+
+/// An error raised while running a program. `location` is the source
+/// position of the instruction that failed, when it is known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub location: Option<Location>
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), location: None }
+    }
+
+    /// Attributes the error to `location` unless it already has one.
+    pub fn at(mut self, location: Option<Location>) -> Self {
+        if self.location.is_none() {
+            self.location = location;
+        }
+        return self;
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.location {
+            Some(loc) => write!(f, "Runtime error at {}:{}: {}", loc.line, loc.col, self.message),
+            None => write!(f, "Runtime error: {}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
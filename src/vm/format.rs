@@ -0,0 +1,133 @@
+use super::{
+    VM,
+    heap::ObjRef,
+    map::MapKey,
+    stack_slot::StackSlot
+};
+
+impl VM {
+    /// Formats `slot` the way `print` and `str()` show it.
+    pub fn format_value(&self, slot: &StackSlot) -> String {
+        let mut out = String::new();
+        self.write_value(slot, false, &mut Vec::new(), &mut out);
+        return out;
+    }
+
+    /// Formats a map key the way it appears inside a printed map.
+    pub fn format_key(&self, key: &MapKey) -> String {
+        let mut out = String::new();
+        self.write_value(&key.to_slot(), true, &mut Vec::new(), &mut out);
+        return out;
+    }
+
+    /// Strings and chars nested in containers are quoted, containers that
+    /// contain themselves are shown as `[...]`.
+    fn write_value(&self, slot: &StackSlot, nested: bool, seen: &mut Vec<ObjRef>, out: &mut String) {
+        match slot {
+            StackSlot::Str(val) if nested => out.push_str(&format!("{:?}", val)),
+            StackSlot::Char(val) if nested => out.push_str(&format!("{:?}", val)),
+            StackSlot::List(obj_ref) => {
+                if seen.contains(obj_ref) {
+                    out.push_str("[...]");
+                    return;
+                }
+                seen.push(*obj_ref);
+                out.push('[');
+                for (i, item) in self.heap.list(*obj_ref).iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_value(item, true, seen, out);
+                }
+                out.push(']');
+                seen.pop();
+            }
+            StackSlot::Map(obj_ref) => {
+                if seen.contains(obj_ref) {
+                    out.push_str("{...}");
+                    return;
+                }
+                seen.push(*obj_ref);
+                out.push('{');
+                for (i, (key, val)) in self.heap.map(*obj_ref).iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_value(&key.to_slot(), true, seen, out);
+                    out.push_str(": ");
+                    self.write_value(val, true, seen, out);
+                }
+                out.push('}');
+                seen.pop();
+            }
+            StackSlot::Struct(obj_ref) => {
+                let (def, fields) = self.heap.instance(*obj_ref);
+                let def = &self.structs[def];
+                if seen.contains(obj_ref) {
+                    out.push_str(&format!("{} {{...}}", def.name));
+                    return;
+                }
+                seen.push(*obj_ref);
+                out.push_str(&def.name);
+                out.push_str(" {");
+                for (i, (name, val)) in def.fields.iter().zip(fields.iter()).enumerate() {
+                    out.push_str(if i > 0 { ", " } else { " " });
+                    out.push_str(name);
+                    out.push_str(": ");
+                    self.write_value(val, true, seen, out);
+                }
+                out.push_str(if fields.is_empty() { "}" } else { " }" });
+                seen.pop();
+            }
+            StackSlot::Instance(obj_ref) => {
+                let instance = self.heap.object(*obj_ref);
+                let name = &self.heap.class(instance.class).name;
+                if seen.contains(obj_ref) {
+                    out.push_str(&format!("{} {{...}}", name));
+                    return;
+                }
+                seen.push(*obj_ref);
+                out.push_str(name);
+                out.push_str(" {");
+                for (i, (key, val)) in instance.fields.iter().enumerate() {
+                    out.push_str(if i > 0 { ", " } else { " " });
+                    self.write_value(&key.to_slot(), false, seen, out);
+                    out.push_str(": ");
+                    self.write_value(val, true, seen, out);
+                }
+                out.push_str(if instance.fields.is_empty() { "}" } else { " }" });
+                seen.pop();
+            }
+            StackSlot::Variant(obj_ref) => {
+                let (def, variant, payload) = self.heap.variant(*obj_ref);
+                let def = &self.enums[def];
+                out.push_str(&def.name);
+                out.push('.');
+                out.push_str(&def.variants[variant].name);
+                if payload.is_empty() {
+                    return;
+                }
+                if seen.contains(obj_ref) {
+                    out.push_str("(...)");
+                    return;
+                }
+                seen.push(*obj_ref);
+                out.push('(');
+                for (i, item) in payload.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_value(item, true, seen, out);
+                }
+                out.push(')');
+                seen.pop();
+            }
+            StackSlot::Class(obj_ref) => out.push_str(&format!("<class {}>", self.heap.class(*obj_ref).name)),
+            StackSlot::Function(_) | StackSlot::Native(_) | StackSlot::BoundMethod(_) => match self.function_name(slot) {
+                Some(name) => out.push_str(&format!("<fn {}>", name)),
+                None => out.push_str("<fn>")
+            }
+            _ => out.push_str(&slot.to_string())
+        }
+    }
+}
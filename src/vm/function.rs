@@ -0,0 +1,9 @@
+/// Compile-time description of a script function. Its runtime instances
+/// are closures on the heap that pair it with captured upvalues.
+#[derive(Debug, Clone)]
+pub struct FunctionProto {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub chunk_index: usize,
+    pub upvalue_count: usize
+}
@@ -0,0 +1,45 @@
+use super::{
+    VM,
+    packed::Pack,
+    heap::GcStats
+};
+
+impl VM {
+    /// Runs a full mark-and-sweep collection and returns the number of
+    /// objects freed. Roots are the value stack, the globals, the closures
+    /// of the active call frames and handlers, the open upvalues, the
+    /// constants of every chunk, the registers of the register machine and
+    /// the value returned to the host by the last run.
+    pub fn collect_garbage(&mut self) -> usize {
+        let mut roots = Vec::new();
+        roots.extend(self.evaluated_stack.iter().filter_map(|slot| slot.unpack().obj_ref()));
+        roots.extend(self.globals.iter().flatten().filter_map(|slot| slot.unpack().obj_ref()));
+        roots.extend(self.call_stack.iter().filter_map(|frame| frame.closure));
+        roots.extend(self.handlers.iter().filter_map(|handler| handler.closure));
+        roots.extend(self.closure);
+        roots.extend(self.open_upvalues.iter().copied());
+        roots.extend(self.registers.iter().filter_map(|slot| slot.obj_ref()));
+        roots.extend(self.result.as_ref().and_then(|slot| slot.obj_ref()));
+        for chunk in self.chunks.iter() {
+            roots.extend(chunk.constants.iter().filter_map(|slot| slot.obj_ref()));
+        }
+        self.heap.mark(roots);
+        return self.heap.sweep();
+    }
+
+    /// Collects if the allocation threshold has been reached. Only called
+    /// between instructions, when every live value is reachable from a root.
+    pub(super) fn maybe_collect(&mut self) {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        return self.heap.stats();
+    }
+
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        self.heap.set_threshold(threshold);
+    }
+}
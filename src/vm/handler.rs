@@ -0,0 +1,12 @@
+use super::heap::ObjRef;
+
+/// An active `try` block: where to resume when an exception is raised
+/// inside it, and the frame and stack height to unwind to.
+pub struct Handler {
+    pub target: usize,
+    pub chunk_index: usize,
+    pub frame_depth: usize,
+    pub stack_height: usize,
+    pub stack_base: usize,
+    pub closure: Option<ObjRef>
+}
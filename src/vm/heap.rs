@@ -0,0 +1,314 @@
+use super::{
+    class::{Class, Instance},
+    map::Map,
+    stack_slot::StackSlot
+};
+
+/// Number of live objects that triggers the first collection.
+const INITIAL_GC_THRESHOLD: usize = 1024;
+
+/// Number of low bits of an `ObjRef` holding the index of its slot.
+const INDEX_BITS: u32 = 32;
+/// Generations wrap around so that a handle fits in a NaN box.
+const GENERATIONS: usize = 1 << 13;
+
+/// Handle to an object owned by the `Heap`: the index of its slot, and
+/// above it the generation of the slot when the object was allocated. A
+/// slot's generation changes whenever it is reused, so a handle kept past
+/// the collection of its object does not reach the slot's new object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(pub usize);
+
+impl ObjRef {
+    fn new(index: usize, generation: usize) -> Self {
+        debug_assert!(index >> INDEX_BITS == 0, "heap index out of range");
+        return ObjRef(generation << INDEX_BITS | index);
+    }
+
+    pub fn index(self) -> usize {
+        return self.0 & ((1 << INDEX_BITS) - 1);
+    }
+
+    fn generation(self) -> usize {
+        return self.0 >> INDEX_BITS;
+    }
+}
+
+/// A variable captured by a closure. It points into the value stack while
+/// the declaring frame is alive and owns the value once it is closed.
+#[derive(Debug, Clone)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(StackSlot)
+}
+
+#[derive(Debug, Clone)]
+pub enum Obj {
+    List(Vec<StackSlot>),
+    Map(Map),
+    Closure(usize, Vec<ObjRef>),
+    Upvalue(Upvalue),
+    Struct(usize, Vec<StackSlot>),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(StackSlot, ObjRef),
+    Variant(usize, usize, Vec<StackSlot>)
+}
+
+impl Obj {
+    /// Pushes every heap object directly referenced by `self` onto `gray`.
+    fn trace(&self, gray: &mut Vec<ObjRef>) {
+        match self {
+            Obj::List(items) => gray.extend(items.iter().filter_map(StackSlot::obj_ref)),
+            Obj::Map(map) => gray.extend(map.iter().filter_map(|(_, val)| val.obj_ref())),
+            Obj::Closure(_, upvalues) => gray.extend(upvalues.iter().copied()),
+            Obj::Struct(_, fields) => gray.extend(fields.iter().filter_map(StackSlot::obj_ref)),
+            Obj::Class(class) => {
+                gray.extend(class.superclass);
+                gray.extend(class.methods.values().copied());
+            }
+            Obj::Instance(instance) => {
+                gray.push(instance.class);
+                gray.extend(instance.fields.iter().filter_map(|(_, val)| val.obj_ref()));
+            }
+            Obj::BoundMethod(receiver, method) => {
+                gray.extend(receiver.obj_ref());
+                gray.push(*method);
+            }
+            Obj::Variant(_, _, payload) => gray.extend(payload.iter().filter_map(StackSlot::obj_ref)),
+            Obj::Upvalue(Upvalue::Closed(val)) => gray.extend(val.obj_ref()),
+            Obj::Upvalue(Upvalue::Open(_)) => {}
+        }
+    }
+}
+
+/// Counters describing the collector's work so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub live_objects: usize,
+    pub freed_objects: usize,
+    pub threshold: usize
+}
+
+pub struct Heap {
+    objects: Vec<Option<Obj>>,
+    generations: Vec<usize>,
+    marks: Vec<bool>,
+    free: Vec<usize>,
+    threshold: usize,
+    min_threshold: usize,
+    collections: usize,
+    freed_objects: usize
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self { objects: Vec::new(), generations: Vec::new(), marks: Vec::new(), free: Vec::new(), threshold: INITIAL_GC_THRESHOLD, min_threshold: INITIAL_GC_THRESHOLD, collections: 0, freed_objects: 0 }
+    }
+
+    pub fn alloc(&mut self, obj: Obj) -> ObjRef {
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index] = Some(obj);
+                self.generations[index] = (self.generations[index] + 1) % GENERATIONS;
+                return ObjRef::new(index, self.generations[index]);
+            }
+            None => {
+                self.objects.push(Some(obj));
+                self.generations.push(0);
+                self.marks.push(false);
+                return ObjRef::new(self.objects.len() - 1, 0);
+            }
+        }
+    }
+
+    pub fn get(&self, obj_ref: ObjRef) -> &Obj {
+        return self.objects[obj_ref.index()].as_ref().expect("use of a collected object");
+    }
+
+    pub fn get_mut(&mut self, obj_ref: ObjRef) -> &mut Obj {
+        return self.objects[obj_ref.index()].as_mut().expect("use of a collected object");
+    }
+
+    /// Whether the object `obj_ref` was created for has not been collected.
+    pub fn is_live(&self, obj_ref: ObjRef) -> bool {
+        let index = obj_ref.index();
+        return self.objects.get(index).is_some_and(Option::is_some) && self.generations[index] == obj_ref.generation();
+    }
+
+    pub fn list(&self, obj_ref: ObjRef) -> &Vec<StackSlot> {
+        match self.get(obj_ref) {
+            Obj::List(items) => items,
+            _ => unreachable!("StackSlot::List must refer to a list")
+        }
+    }
+
+    pub fn list_mut(&mut self, obj_ref: ObjRef) -> &mut Vec<StackSlot> {
+        match self.get_mut(obj_ref) {
+            Obj::List(items) => items,
+            _ => unreachable!("StackSlot::List must refer to a list")
+        }
+    }
+
+    pub fn map(&self, obj_ref: ObjRef) -> &Map {
+        match self.get(obj_ref) {
+            Obj::Map(map) => map,
+            _ => unreachable!("StackSlot::Map must refer to a map")
+        }
+    }
+
+    pub fn map_mut(&mut self, obj_ref: ObjRef) -> &mut Map {
+        match self.get_mut(obj_ref) {
+            Obj::Map(map) => map,
+            _ => unreachable!("StackSlot::Map must refer to a map")
+        }
+    }
+
+    /// Returns the function index and upvalues of a closure.
+    pub fn closure(&self, obj_ref: ObjRef) -> (usize, &Vec<ObjRef>) {
+        match self.get(obj_ref) {
+            Obj::Closure(function, upvalues) => (*function, upvalues),
+            _ => unreachable!("StackSlot::Function must refer to a closure")
+        }
+    }
+
+    /// Returns the definition index and field values of a struct instance.
+    pub fn instance(&self, obj_ref: ObjRef) -> (usize, &Vec<StackSlot>) {
+        match self.get(obj_ref) {
+            Obj::Struct(def, fields) => (*def, fields),
+            _ => unreachable!("StackSlot::Struct must refer to a struct instance")
+        }
+    }
+
+    pub fn instance_mut(&mut self, obj_ref: ObjRef) -> (usize, &mut Vec<StackSlot>) {
+        match self.get_mut(obj_ref) {
+            Obj::Struct(def, fields) => (*def, fields),
+            _ => unreachable!("StackSlot::Struct must refer to a struct instance")
+        }
+    }
+
+    pub fn class(&self, obj_ref: ObjRef) -> &Class {
+        match self.get(obj_ref) {
+            Obj::Class(class) => class,
+            _ => unreachable!("StackSlot::Class must refer to a class")
+        }
+    }
+
+    pub fn class_mut(&mut self, obj_ref: ObjRef) -> &mut Class {
+        match self.get_mut(obj_ref) {
+            Obj::Class(class) => class,
+            _ => unreachable!("StackSlot::Class must refer to a class")
+        }
+    }
+
+    pub fn object(&self, obj_ref: ObjRef) -> &Instance {
+        match self.get(obj_ref) {
+            Obj::Instance(instance) => instance,
+            _ => unreachable!("StackSlot::Instance must refer to an instance")
+        }
+    }
+
+    pub fn object_mut(&mut self, obj_ref: ObjRef) -> &mut Instance {
+        match self.get_mut(obj_ref) {
+            Obj::Instance(instance) => instance,
+            _ => unreachable!("StackSlot::Instance must refer to an instance")
+        }
+    }
+
+    /// Returns the receiver and method closure of a bound method.
+    pub fn bound_method(&self, obj_ref: ObjRef) -> (&StackSlot, ObjRef) {
+        match self.get(obj_ref) {
+            Obj::BoundMethod(receiver, method) => (receiver, *method),
+            _ => unreachable!("StackSlot::BoundMethod must refer to a bound method")
+        }
+    }
+
+    /// Returns the enum index, variant index and payload of an enum value.
+    pub fn variant(&self, obj_ref: ObjRef) -> (usize, usize, &Vec<StackSlot>) {
+        match self.get(obj_ref) {
+            Obj::Variant(def, variant, payload) => (*def, *variant, payload),
+            _ => unreachable!("StackSlot::Variant must refer to an enum value")
+        }
+    }
+
+    pub fn upvalue(&self, obj_ref: ObjRef) -> &Upvalue {
+        match self.get(obj_ref) {
+            Obj::Upvalue(upvalue) => upvalue,
+            _ => unreachable!("closure upvalues must refer to upvalues")
+        }
+    }
+
+    pub fn upvalue_mut(&mut self, obj_ref: ObjRef) -> &mut Upvalue {
+        match self.get_mut(obj_ref) {
+            Obj::Upvalue(upvalue) => upvalue,
+            _ => unreachable!("closure upvalues must refer to upvalues")
+        }
+    }
+
+    pub fn live_objects(&self) -> usize {
+        return self.objects.len() - self.free.len();
+    }
+
+    pub fn should_collect(&self) -> bool {
+        return self.live_objects() >= self.threshold;
+    }
+
+    /// Sets the smallest live object count that triggers a collection.
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.min_threshold = threshold.max(1);
+        self.threshold = self.min_threshold;
+    }
+
+    /// Marks everything reachable from `roots`. Uses an explicit worklist
+    /// so that deeply nested structures cannot overflow the Rust stack.
+    pub fn mark(&mut self, roots: Vec<ObjRef>) {
+        let mut gray = roots;
+        while let Some(obj_ref) = gray.pop() {
+            let index = obj_ref.index();
+            if self.marks[index] {
+                continue;
+            }
+            self.marks[index] = true;
+            if let Some(obj) = &self.objects[index] {
+                obj.trace(&mut gray);
+            }
+        }
+    }
+
+    /// Frees every unmarked object, clears the marks and returns the number
+    /// of objects freed. The next collection happens once the live set has
+    /// doubled, but never below the configured threshold.
+    pub fn sweep(&mut self) -> usize {
+        let mut freed = 0;
+        for index in 0..self.objects.len() {
+            if self.marks[index] {
+                self.marks[index] = false;
+            }
+            else if self.objects[index].is_some() {
+                self.objects[index] = None;
+                self.free.push(index);
+                freed += 1;
+            }
+        }
+        self.collections += 1;
+        self.freed_objects += freed;
+        self.threshold = self.min_threshold.max(self.live_objects() * 2);
+        return freed;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        return GcStats {
+            collections: self.collections,
+            live_objects: self.live_objects(),
+            freed_objects: self.freed_objects,
+            threshold: self.threshold
+        };
+    }
+}
@@ -0,0 +1,39 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+/// The string constants of a program, stored once each: every chunk that
+/// uses a string shares one allocation of it.
+#[derive(Default)]
+pub struct Interner {
+    strings: RefCell<HashSet<Rc<str>>>
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self { strings: RefCell::new(HashSet::new()) }
+    }
+
+    /// The shared copy of `text`, added on first use.
+    pub fn intern(&self, text: &str) -> Rc<str> {
+        let mut strings = self.strings.borrow_mut();
+        if let Some(string) = strings.get(text) {
+            return Rc::clone(string);
+        }
+        let string: Rc<str> = Rc::from(text);
+        strings.insert(Rc::clone(&string));
+        return string;
+    }
+
+    /// Drops the strings that nothing but the interner refers to, such as
+    /// constants of code that was discarded.
+    pub fn prune(&self) {
+        self.strings.borrow_mut().retain(|string| Rc::strong_count(string) > 1);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.strings.borrow().len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.strings.borrow().is_empty();
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::HashMap,
+    rc::Rc
+};
+
+use super::stack_slot::StackSlot;
+
+/// The subset of values that can be used as map keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Int(i64),
+    Bool(bool),
+    Char(char),
+    Str(Rc<str>)
+}
+
+impl MapKey {
+    pub fn from_slot(slot: &StackSlot) -> Option<MapKey> {
+        match slot {
+            StackSlot::Int(val) => Some(MapKey::Int(*val)),
+            StackSlot::Bool(val) => Some(MapKey::Bool(*val)),
+            StackSlot::Char(val) => Some(MapKey::Char(*val)),
+            StackSlot::Str(val) => Some(MapKey::Str(val.clone())),
+            _ => None
+        }
+    }
+
+    pub fn to_slot(&self) -> StackSlot {
+        match self {
+            MapKey::Int(val) => StackSlot::Int(*val),
+            MapKey::Bool(val) => StackSlot::Bool(*val),
+            MapKey::Char(val) => StackSlot::Char(*val),
+            MapKey::Str(val) => StackSlot::Str(val.clone())
+        }
+    }
+}
+
+/// Hash map that remembers insertion order. Removed entries leave a hole
+/// in `entries` until more than half of them are holes.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<Option<(MapKey, StackSlot)>>,
+    positions: HashMap<MapKey, usize>
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self { entries: Vec::new(), positions: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.positions.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.positions.is_empty();
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&StackSlot> {
+        let pos = *self.positions.get(key)?;
+        return self.entries[pos].as_ref().map(|(_, val)| val);
+    }
+
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        return self.positions.contains_key(key);
+    }
+
+    pub fn insert(&mut self, key: MapKey, val: StackSlot) {
+        match self.positions.get(&key) {
+            Some(pos) => self.entries[*pos] = Some((key, val)),
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push(Some((key, val)));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<StackSlot> {
+        let pos = self.positions.remove(key)?;
+        let (_, val) = self.entries[pos].take()?;
+        if self.entries.len() > 8 && self.positions.len() * 2 < self.entries.len() {
+            self.compact();
+        }
+        return Some(val);
+    }
+
+    /// Returns the first entry at or after `cursor` along with the cursor
+    /// of the entry that follows it.
+    pub fn entry_from(&self, cursor: usize) -> Option<(usize, &MapKey, &StackSlot)> {
+        return self.entries.iter().enumerate().skip(cursor)
+            .find_map(|(pos, entry)| entry.as_ref().map(|(key, val)| (pos + 1, key, val)));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &StackSlot)> {
+        return self.entries.iter().flatten().map(|(key, val)| (key, val));
+    }
+
+    fn compact(&mut self) {
+        self.entries.retain(|entry| entry.is_some());
+        for (pos, entry) in self.entries.iter().enumerate() {
+            if let Some((key, _)) = entry {
+                self.positions.insert(key.clone(), pos);
+            }
+        }
+    }
+}
@@ -1,192 +1,1349 @@
+use std::{cmp::Ordering, rc::Rc};
+
 pub mod opcodes;
 use opcodes::OpCode;
 
+pub mod bigint;
+use bigint::BigInt;
 pub mod chunk;
+pub mod interner;
 pub mod stack_slot;
-use stack_slot::StackSlot;
+use stack_slot::{StackSlot, floor_div};
 use chunk::Chunk;
 
 pub mod call_stack_slot;
 use call_stack_slot::CallStackSlot;
 
+pub mod handler;
+use handler::Handler;
+
+pub mod error;
+use error::RuntimeError;
+
+pub mod program;
+use program::Program;
+
+pub mod native;
+use native::{NativeFn, NativeFunction};
+
+pub mod stdlib;
+
+pub mod heap;
+use heap::{Heap, Obj, ObjRef, Upvalue};
+
+pub mod function;
+use function::FunctionProto;
+
+pub mod struct_def;
+use struct_def::StructDef;
+
+pub mod class;
+use class::{Class, Instance};
+
+pub mod enum_def;
+use enum_def::{EnumDef, ERR_VARIANT, OK_VARIANT, RESULT_ENUM};
+
+pub mod map;
+use map::{Map, MapKey};
+
+pub mod register;
+
+mod packed;
+use packed::{Pack, Packed};
+
+mod disassembler;
+mod format;
+mod gc;
+
+/// Maximum depth of nested script function calls.
+pub const MAX_FRAMES: usize = 4096;
+
 pub struct VM {
-    evaluated_stack: Vec<StackSlot>,
+    evaluated_stack: Vec<Packed>,
     call_stack: Vec<CallStackSlot>,
-    pub chunks: Vec<Chunk>,
+    handlers: Vec<Handler>,
+    thrown: Option<StackSlot>,
+    escaped: Option<(RuntimeError, StackSlot)>,
+    entry_depth: Option<usize>,
+    handler_floor: usize,
+    pub chunks: Rc<Vec<Chunk>>,
     pub chunk_index: usize,
     bc_pos: usize,
-    globals: Vec<Option<StackSlot>>
+    globals: Vec<Option<Packed>>,
+    natives: Vec<NativeFunction>,
+    functions: Vec<FunctionProto>,
+    structs: Vec<StructDef>,
+    enums: Vec<EnumDef>,
+    stack_base: usize,
+    closure: Option<ObjRef>,
+    open_upvalues: Vec<ObjRef>,
+    registers: Vec<StackSlot>,
+    result: Option<StackSlot>,
+    heap: Heap
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VM {
-    pub fn new(chunks: Vec<Chunk>) -> Self {
-        Self { evaluated_stack: Vec::new(), call_stack: Vec::new(), chunks: chunks, chunk_index: 0, bc_pos: 0, globals: Vec::new() }
+    /// Creates a machine with the standard library already registered.
+    pub fn new() -> Self {
+        let mut vm = Self {
+            evaluated_stack: Vec::new(), call_stack: Vec::new(), handlers: Vec::new(), thrown: None, escaped: None, entry_depth: None, handler_floor: 0, chunks: Rc::default(), chunk_index: 0, bc_pos: 0,
+            globals: Vec::new(), natives: Vec::new(), functions: Vec::new(), structs: Vec::new(), enums: Vec::new(), stack_base: 0, closure: None,
+            open_upvalues: Vec::new(), registers: Vec::new(), result: None, heap: Heap::new()
+        };
+        stdlib::register(&mut vm);
+        return vm;
+    }
+
+    /// Loads `program` into a clean machine state and executes it, returning
+    /// the value left on the stack by a trailing expression statement. The
+    /// heap outlives the run: objects left over from earlier runs are freed
+    /// by the collector, except for the value returned by the last run,
+    /// which stays readable until another run returns.
+    pub fn run(&mut self, program: &Program) -> Result<StackSlot, RuntimeError> {
+        self.evaluated_stack.clear();
+        self.call_stack.clear();
+        self.handlers.clear();
+        self.thrown = None;
+        self.escaped = None;
+        self.entry_depth = None;
+        self.handler_floor = 0;
+        self.chunks = Rc::new(program.chunks.clone());
+        self.functions = program.functions.clone();
+        self.structs = program.structs.clone();
+        self.enums = program.enums.clone();
+        self.chunk_index = 0;
+        self.bc_pos = 0;
+        self.stack_base = 0;
+        self.closure = None;
+        self.open_upvalues.clear();
+        self.globals = vec![None; program.globals];
+        let result = self.execute()?;
+        self.keep_result(&result);
+        return Ok(result);
+    }
+
+    /// Roots `result` in place of the previous one, so that the host can
+    /// read it while the next run collects garbage.
+    fn keep_result(&mut self, result: &StackSlot) {
+        self.result = Some(result.clone());
+    }
+
+    /// Whether `slot` can still be read: it is not a heap value, or the
+    /// object it refers to has not been collected.
+    pub fn is_live(&self, slot: &StackSlot) -> bool {
+        return slot.obj_ref().is_none_or(|obj_ref| self.heap.is_live(obj_ref));
+    }
+
+    /// Registers a host function callable from scripts as `name(...)`.
+    /// Registering a name twice replaces the previous function. Functions
+    /// registered with `VARIADIC` arity accept any number of arguments.
+    pub fn register_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction { name: name.to_string(), arity, function };
+        match self.natives.iter().position(|n| n.name == name) {
+            Some(index) => self.natives[index] = native,
+            None => self.natives.push(native)
+        }
+    }
+
+    pub fn natives(&self) -> &[NativeFunction] {
+        return &self.natives;
     }
 
     pub fn push(&mut self, slot: StackSlot) {
-        self.evaluated_stack.push(slot);
+        self.evaluated_stack.push(Packed::pack(slot));
+    }
+
+    pub fn pop(&mut self) -> Result<StackSlot, RuntimeError> {
+        return self.evaluated_stack.pop().map(Packed::into_slot).ok_or_else(|| RuntimeError::new("Stack underflow"));
     }
 
-    pub fn pop(&mut self) -> StackSlot {
-        return self.evaluated_stack.pop().unwrap();
+    /// Pops the top value without unpacking it, for instructions that only
+    /// move values around.
+    fn pop_packed(&mut self) -> Result<Packed, RuntimeError> {
+        return self.evaluated_stack.pop().ok_or_else(|| RuntimeError::new("Stack underflow"));
+    }
+
+    /// Pops the top `count` values, deepest first.
+    fn pop_many(&mut self, count: usize) -> Result<Vec<StackSlot>, RuntimeError> {
+        if self.evaluated_stack.len() < count {
+            return Err(RuntimeError::new("Stack underflow"));
+        }
+        let values = self.evaluated_stack.split_off(self.evaluated_stack.len() - count);
+        return Ok(values.into_iter().map(Packed::into_slot).collect());
+    }
+
+    fn slot(&self, index: usize) -> StackSlot {
+        return self.evaluated_stack[index].unpack();
+    }
+
+    fn set_slot(&mut self, index: usize, val: StackSlot) {
+        self.evaluated_stack[index] = Packed::pack(val);
+    }
+
+    pub fn alloc_list(&mut self, items: Vec<StackSlot>) -> StackSlot {
+        return StackSlot::List(self.heap.alloc(Obj::List(items)));
+    }
+
+    pub fn list(&self, slot: &StackSlot) -> Option<&Vec<StackSlot>> {
+        match slot {
+            StackSlot::List(obj_ref) => Some(self.heap.list(*obj_ref)),
+            _ => None
+        }
+    }
+
+    pub fn list_mut(&mut self, slot: &StackSlot) -> Option<&mut Vec<StackSlot>> {
+        match slot {
+            StackSlot::List(obj_ref) => Some(self.heap.list_mut(*obj_ref)),
+            _ => None
+        }
+    }
+
+    pub fn alloc_result(&mut self, is_ok: bool, val: StackSlot) -> StackSlot {
+        let variant = if is_ok { OK_VARIANT } else { ERR_VARIANT };
+        return StackSlot::Variant(self.heap.alloc(Obj::Variant(RESULT_ENUM, variant, vec![val])));
+    }
+
+    /// Whether `slot` is `Ok` rather than `Err`, and its payload, if it is
+    /// a `Result`.
+    pub fn result(&self, slot: &StackSlot) -> Option<(bool, &StackSlot)> {
+        match slot {
+            StackSlot::Variant(obj_ref) => match self.heap.variant(*obj_ref) {
+                (RESULT_ENUM, variant, payload) => Some((variant == OK_VARIANT, &payload[0])),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    pub fn alloc_map(&mut self, map: Map) -> StackSlot {
+        return StackSlot::Map(self.heap.alloc(Obj::Map(map)));
+    }
+
+    pub fn map(&self, slot: &StackSlot) -> Option<&Map> {
+        match slot {
+            StackSlot::Map(obj_ref) => Some(self.heap.map(*obj_ref)),
+            _ => None
+        }
+    }
+
+    pub fn map_mut(&mut self, slot: &StackSlot) -> Option<&mut Map> {
+        match slot {
+            StackSlot::Map(obj_ref) => Some(self.heap.map_mut(*obj_ref)),
+            _ => None
+        }
+    }
+
+    /// Converts `slot` into a map key, failing for unhashable values.
+    pub fn map_key(slot: &StackSlot) -> Result<MapKey, RuntimeError> {
+        return MapKey::from_slot(slot)
+            .ok_or_else(|| RuntimeError::new(format!("Map key must be an int, bool, char or str, got {}", slot.type_name())));
     }
 
     pub fn add_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        Rc::make_mut(&mut self.chunks).push(chunk);
+    }
+
+    fn global(&self, index: usize) -> Result<StackSlot, RuntimeError> {
+        return self.globals[index].as_ref().map(Packed::unpack).ok_or_else(Self::uninitialised_global);
     }
 
-    pub fn create_global(&mut self) -> usize {
-        self.globals.push(None);
-        return self.globals.len() - 1;
+    fn uninitialised_global() -> RuntimeError {
+        return RuntimeError::new("Global variable used before initialisation");
     }
 
-    pub fn store_global(&mut self, index: usize) {
-        self.chunks[self.chunk_index].emit_byte(OpCode::StoreGlob as u8);
-        self.chunks[self.chunk_index].emit_byte(((index >> 16) & 0xFF) as u8);
-        self.chunks[self.chunk_index].emit_byte(((index >> 8) & 0xFF) as u8);
-        self.chunks[self.chunk_index].emit_byte((index & 0xFF) as u8);
+    fn set_global(&mut self, index: usize, val: StackSlot) {
+        self.globals[index] = Some(Packed::pack(val));
     }
 
-    pub fn load_global(&mut self, index: usize) -> usize {
-        let first_instruction = self.chunks[self.chunk_index].emit_byte(OpCode::LoadGlob as u8);
-        self.chunks[self.chunk_index].emit_byte(((index >> 16) & 0xFF) as u8);
-        self.chunks[self.chunk_index].emit_byte(((index >> 8) & 0xFF) as u8);
-        self.chunks[self.chunk_index].emit_byte((index & 0xFF) as u8);
-        return first_instruction;
+    /// Runs until the current chunk ends. A runtime error raised inside a
+    /// `try` block resumes execution at its handler; any other error stops
+    /// the program.
+    pub fn execute(&mut self) -> Result<StackSlot, RuntimeError> {
+        loop {
+            let mut ip = self.bc_pos;
+            match self.dispatch(&mut ip) {
+                Ok(result) => return Ok(result),
+                Err(err) => {
+                    self.bc_pos = ip;
+                    self.unwind(err)?
+                }
+            }
+        }
+    }
+
+    /// Calls `callee` with `args` from a native function and returns its
+    /// result. Handlers installed outside the call do not see exceptions
+    /// raised inside it; they are returned as errors instead. Values the
+    /// native only holds itself are not GC roots while `callee` runs. The
+    /// frame the native runs in is restored afterwards, also on error, so
+    /// that the native may handle the error and carry on. A value thrown by
+    /// `callee` is thrown on if the native returns the error unchanged.
+    pub fn call(&mut self, callee: StackSlot, args: &[StackSlot]) -> Result<StackSlot, RuntimeError> {
+        let depth = self.call_stack.len();
+        let height = self.evaluated_stack.len();
+        let handlers = self.handlers.len();
+        let (chunk_index, bc_pos, stack_base, closure) = (self.chunk_index, self.bc_pos, self.stack_base, self.closure);
+        self.push(callee);
+        self.evaluated_stack.extend(args.iter().cloned().map(Packed::pack));
+        let result = match self.call_value(args.len()) {
+            Ok(()) if self.call_stack.len() == depth => self.pop(),
+            Ok(()) => {
+                let entry_depth = self.entry_depth.replace(depth);
+                let handler_floor = std::mem::replace(&mut self.handler_floor, handlers);
+                let result = self.execute();
+                self.entry_depth = entry_depth;
+                self.handler_floor = handler_floor;
+                result
+            }
+            Err(err) => Err(err)
+        };
+        if let Err(err) = &result {
+            self.escaped = self.thrown.take().map(|thrown| (err.clone(), thrown));
+        }
+        self.call_stack.truncate(depth);
+        self.handlers.truncate(handlers);
+        self.close_upvalues(height);
+        self.evaluated_stack.truncate(height);
+        self.chunk_index = chunk_index;
+        self.bc_pos = bc_pos;
+        self.stack_base = stack_base;
+        self.closure = closure;
+        return result;
     }
-    
-    pub fn get_global(&mut self) -> StackSlot {
-        let index = self.get_index();
-        return self.globals[index].clone().unwrap();
+
+    /// Attributes `err` to the instruction that raised it and transfers
+    /// control to the innermost handler, discarding the frames and stack
+    /// slots above it. The handler receives the thrown value, or an error
+    /// value wrapping `err` for errors raised by the machine itself.
+    fn unwind(&mut self, err: RuntimeError) -> Result<(), RuntimeError> {
+        let err = err.at(self.chunks[self.chunk_index].location_at(self.bc_pos.saturating_sub(1)));
+        if self.handlers.len() <= self.handler_floor {
+            return Err(err);
+        }
+        let handler = self.handlers.pop().unwrap();
+        let thrown = self.thrown.take();
+        self.call_stack.truncate(handler.frame_depth);
+        self.chunk_index = handler.chunk_index;
+        self.bc_pos = handler.target;
+        self.stack_base = handler.stack_base;
+        self.closure = handler.closure;
+        self.close_upvalues(handler.stack_height);
+        self.evaluated_stack.truncate(handler.stack_height);
+        self.push(thrown.unwrap_or_else(|| StackSlot::Error(Rc::new(err))));
+        return Ok(());
     }
 
-    pub fn execute(&mut self) {
-        while self.bc_pos < self.chunks[self.chunk_index].bytecode.len() {
-            match OpCode::from_u8(self.chunks[self.chunk_index].bytecode[self.bc_pos]) {
+    /// The interpreter loop. The instruction pointer lives in `ip` and the
+    /// current chunk is read through the `code` and `constants` slices;
+    /// `self.bc_pos` is only written when a frame is entered, to be
+    /// restored on return. `ip` is left past the opcode of the instruction
+    /// that failed when an error is returned.
+    fn dispatch(&mut self, ip: &mut usize) -> Result<StackSlot, RuntimeError> {
+        let chunks = Rc::clone(&self.chunks);
+        let (mut code, mut constants) = Self::chunk_slices(&chunks, self.chunk_index);
+        let mut wide = 1;
+        while let Some(&byte) = code.get(*ip) {
+            *ip += 1;
+            let width = std::mem::replace(&mut wide, 1);
+            match OpCode::from_u8(byte) {
+                Some(OpCode::Wide) => wide = width + 1,
                 Some(OpCode::Push) => {
-                    self.bc_pos += 1;
-                    let index = self.get_index();
-                    self.push(self.chunks[self.chunk_index].constants[index].clone());
+                    let index = Self::read_operand(code, ip, width);
+                    self.push(constants[index].clone());
+                }
+                Some(OpCode::Pop) => {
+                    self.pop_packed()?;
                 }
                 Some(OpCode::Add) => {
-                    self.bc_pos += 1;
-                    let rhs = self.pop();
-                    let lhs = self.pop();
-                    match (rhs, lhs) {
-                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(StackSlot::Int(a + b)),
-                        (StackSlot::Float(a), StackSlot::Int(b)) => self.push(StackSlot::Float(a + b as f64)),
-                        (StackSlot::Int(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a as f64 + b)),
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.push(Self::add(lhs, rhs)?);
+                }
+                Some(OpCode::AddInt) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    match (lhs, rhs) {
+                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(Self::add_ints(a, b)?),
+                        (lhs, rhs) => self.push(Self::add(lhs, rhs)?)
+                    }
+                }
+                Some(OpCode::AddFloat) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    match (lhs, rhs) {
                         (StackSlot::Float(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a + b)),
+                        (lhs, rhs) => self.push(Self::add(lhs, rhs)?)
                     }
                 }
-                Some(OpCode::Sub) => {
-                    self.bc_pos += 1;
-                    let rhs = self.pop();
-                    let lhs = self.pop();
-                    match (rhs, lhs) {
-                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(StackSlot::Int(a - b)),
-                        (StackSlot::Float(a), StackSlot::Int(b)) => self.push(StackSlot::Float(a - b as f64)),
-                        (StackSlot::Int(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a as f64 - b)),
-                        (StackSlot::Float(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a - b)),
+                Some(OpCode::AddConst) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let lhs = self.pop()?;
+                    match (lhs, &constants[index]) {
+                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(Self::add_ints(a, *b)?),
+                        (lhs, rhs) => self.push(Self::add(lhs, rhs.clone())?)
                     }
                 }
+                Some(OpCode::IncGlobal) => {
+                    let global = Self::read_operand(code, ip, width);
+                    let index = Self::read_operand(code, ip, width);
+                    let sum = match (self.global(global)?, &constants[index]) {
+                        (StackSlot::Int(a), StackSlot::Int(b)) => Self::add_ints(a, *b)?,
+                        (lhs, rhs) => Self::add(lhs, rhs.clone())?
+                    };
+                    self.set_global(global, sum);
+                }
+                Some(OpCode::Sub) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.push(Self::sub(lhs, rhs)?);
+                }
                 Some(OpCode::Mul) => {
-                    self.bc_pos += 1;
-                    let rhs = self.pop();
-                    let lhs = self.pop();
-                    match (rhs, lhs) {
-                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(StackSlot::Int(a * b)),
-                        (StackSlot::Float(a), StackSlot::Int(b)) => self.push(StackSlot::Float(a * b as f64)),
-                        (StackSlot::Int(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a as f64 * b)),
-                        (StackSlot::Float(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a * b)),
-                    }
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.push(Self::mul(lhs, rhs)?);
                 }
                 Some(OpCode::Div) => {
-                    self.bc_pos += 1;
-                    let rhs = self.pop();
-                    let lhs = self.pop();
-                    match (rhs, lhs) {
-                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(StackSlot::Int(a / b)),
-                        (StackSlot::Float(a), StackSlot::Int(b)) => self.push(StackSlot::Float(a / b as f64)),
-                        (StackSlot::Int(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a as f64 / b)),
-                        (StackSlot::Float(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a / b)),
-                    }
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.push(Self::div(lhs, rhs)?);
+                }
+                Some(OpCode::FloorDiv) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.push(Self::floor_div(lhs, rhs)?);
                 }
                 Some(OpCode::Rem) => {
-                    self.bc_pos += 1;
-                    let rhs = self.pop();
-                    let lhs = self.pop();
-                    match (rhs, lhs) {
-                        (StackSlot::Int(a), StackSlot::Int(b)) => self.push(StackSlot::Int(a % b)),
-                        (StackSlot::Float(a), StackSlot::Int(b)) => self.push(StackSlot::Float(a % b as f64)),
-                        (StackSlot::Int(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a as f64 % b)),
-                        (StackSlot::Float(a), StackSlot::Float(b)) => self.push(StackSlot::Float(a % b)),
-                    }
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    self.push(Self::rem(lhs, rhs)?);
+                }
+                Some(OpCode::Not) => {
+                    let val = self.pop()?;
+                    self.push(Self::not(val)?);
+                }
+                Some(OpCode::Neg) => {
+                    let val = self.pop()?;
+                    self.push(Self::neg(val)?);
+                }
+                Some(OpCode::Eq) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    let result = self.values_equal(&lhs, &rhs);
+                    self.push(StackSlot::Bool(result));
+                }
+                Some(OpCode::NotEq) => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    let result = self.values_equal(&lhs, &rhs);
+                    self.push(StackSlot::Bool(!result));
+                }
+                Some(OpCode::Lt) => {
+                    let ordering = self.compare_operands("<")?;
+                    self.push(StackSlot::Bool(ordering.is_some_and(Ordering::is_lt)));
+                }
+                Some(OpCode::LtEq) => {
+                    let ordering = self.compare_operands("<=")?;
+                    self.push(StackSlot::Bool(ordering.is_some_and(Ordering::is_le)));
+                }
+                Some(OpCode::Gt) => {
+                    let ordering = self.compare_operands(">")?;
+                    self.push(StackSlot::Bool(ordering.is_some_and(Ordering::is_gt)));
+                }
+                Some(OpCode::GtEq) => {
+                    let ordering = self.compare_operands(">=")?;
+                    self.push(StackSlot::Bool(ordering.is_some_and(Ordering::is_ge)));
                 }
                 Some(OpCode::StoreGlob) => {
-                    self.bc_pos += 1;
-                    let val = self.pop();
-                    let index = self.get_index();
+                    let val = self.pop_packed()?;
+                    let index = Self::read_operand(code, ip, width);
                     self.globals[index] = Some(val);
                 }
                 Some(OpCode::LoadGlob) => {
-                    self.bc_pos += 1;
-                    let slot = self.get_global();
-                    self.push(slot);
+                    let index = Self::read_operand(code, ip, width);
+                    let slot = self.globals[index].clone().ok_or_else(Self::uninitialised_global)?;
+                    self.evaluated_stack.push(slot);
                 }
                 Some(OpCode::Jmp) => {
-                    self.bc_pos += 1;
-                    self.bc_pos = self.get_index();
+                    *ip = Self::read_operand(code, ip, width);
                 }
                 Some(OpCode::JmpIf) => {
-                    self.bc_pos += 1;
-                    let cond = self.pop();
-                    let index = self.get_index();
-                    if let Some(val) = cond.as_i64() && val == 1 {
-                        self.bc_pos = index;
+                    let cond = self.pop()?;
+                    let index = Self::read_operand(code, ip, width);
+                    match cond {
+                        StackSlot::Bool(true) => *ip = index,
+                        StackSlot::Bool(false) => {}
+                        val => return Err(RuntimeError::new(format!("Condition must be a bool, got {}", val.type_name())))
+                    }
+                }
+                Some(OpCode::JmpIfFalse) => {
+                    let cond = self.pop()?;
+                    let index = Self::read_operand(code, ip, width);
+                    match cond {
+                        StackSlot::Bool(false) => *ip = index,
+                        StackSlot::Bool(true) => {}
+                        val => return Err(RuntimeError::new(format!("Condition must be a bool, got {}", val.type_name())))
+                    }
+                }
+                Some(OpCode::JmpIfFalseCmp) => {
+                    let holds = match OpCode::from_u8(code[*ip]) {
+                        Some(OpCode::Eq) => self.equal_operands()?,
+                        Some(OpCode::NotEq) => !self.equal_operands()?,
+                        Some(OpCode::Lt) => self.compare_operands("<")?.is_some_and(Ordering::is_lt),
+                        Some(OpCode::LtEq) => self.compare_operands("<=")?.is_some_and(Ordering::is_le),
+                        Some(OpCode::Gt) => self.compare_operands(">")?.is_some_and(Ordering::is_gt),
+                        _ => self.compare_operands(">=")?.is_some_and(Ordering::is_ge)
+                    };
+                    *ip += 1;
+                    let target = Self::read_operand(code, ip, width);
+                    if !holds {
+                        *ip = target;
                     }
                 }
                 Some(OpCode::Call) => {
-                    self.bc_pos += 1;
-                    let new_chunk_index = self.get_index();
-                    self.call_stack.push(CallStackSlot { bc_pos: self.bc_pos, chunk_index: self.chunk_index });
-                    self.chunk_index = new_chunk_index;
-                    self.bc_pos = 0;
+                    let argc = code[*ip] as usize;
+                    *ip += 1;
+                    self.bc_pos = *ip;
+                    self.call_value(argc)?;
+                    *ip = self.bc_pos;
+                    (code, constants) = Self::chunk_slices(&chunks, self.chunk_index);
+                    self.maybe_collect();
                 }
                 Some(OpCode::Ret) => {
-                    self.bc_pos += 1;
-                    let call_slot = self.call_stack.pop().unwrap();
+                    let result = self.pop()?;
+                    let call_slot = self.call_stack.pop().ok_or_else(|| RuntimeError::new("Return outside of a function"))?;
+                    self.close_upvalues(self.stack_base);
+                    self.evaluated_stack.truncate(self.stack_base);
                     self.chunk_index = call_slot.chunk_index;
                     self.bc_pos = call_slot.bc_pos;
+                    self.stack_base = call_slot.stack_base;
+                    self.closure = call_slot.closure;
+                    *ip = call_slot.bc_pos;
+                    (code, constants) = Self::chunk_slices(&chunks, self.chunk_index);
+                    if self.entry_depth == Some(self.call_stack.len()) {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                }
+                Some(OpCode::CallNative) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let argc = code[*ip] as usize;
+                    *ip += 1;
+                    self.call_native(index, argc)?;
+                    self.maybe_collect();
+                }
+                Some(OpCode::Closure) => {
+                    let function = Self::read_operand(code, ip, width);
+                    let mut upvalues = Vec::with_capacity(self.functions[function].upvalue_count);
+                    for _ in 0..self.functions[function].upvalue_count {
+                        let is_local = code[*ip] != 0;
+                        *ip += 1;
+                        let index = Self::read_operand(code, ip, width);
+                        let upvalue = match is_local {
+                            true => self.capture_upvalue(self.stack_base + index),
+                            false => self.upvalue_ref(index)?
+                        };
+                        upvalues.push(upvalue);
+                    }
+                    let closure = self.heap.alloc(Obj::Closure(function, upvalues));
+                    self.push(StackSlot::Function(closure));
+                    self.maybe_collect();
+                }
+                Some(OpCode::GetUpvalue) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let upvalue = self.upvalue_ref(index)?;
+                    let val = match self.heap.upvalue(upvalue) {
+                        Upvalue::Open(slot) => self.slot(*slot),
+                        Upvalue::Closed(val) => val.clone()
+                    };
+                    self.push(val);
+                }
+                Some(OpCode::SetUpvalue) => {
+                    let val = self.pop()?;
+                    let index = Self::read_operand(code, ip, width);
+                    let upvalue = self.upvalue_ref(index)?;
+                    match self.heap.upvalue_mut(upvalue) {
+                        Upvalue::Open(slot) => {
+                            let slot = *slot;
+                            self.set_slot(slot, val);
+                        }
+                        Upvalue::Closed(closed) => *closed = val
+                    }
+                }
+                Some(OpCode::CloseUpvalue) => {
+                    if self.evaluated_stack.is_empty() {
+                        return Err(RuntimeError::new("Stack underflow"));
+                    }
+                    self.close_upvalues(self.evaluated_stack.len() - 1);
+                    self.pop()?;
                 }
                 Some(OpCode::StoreLoc) => {
-                    self.bc_pos += 1;
-                    let val = self.pop();
-                    let index = self.get_index();
-                    self.chunks[self.chunk_index].locals[index] = Some(val);
+                    let val = self.pop_packed()?;
+                    let index = Self::read_operand(code, ip, width);
+                    self.evaluated_stack[self.stack_base + index] = val;
                 }
                 Some(OpCode::LoadLoc) => {
-                    self.bc_pos += 1;
-                    let index = self.get_index();
-                    let slot = self.chunks[self.chunk_index].get_local(index);
-                    self.push(slot);
+                    let index = Self::read_operand(code, ip, width);
+                    let slot = self.evaluated_stack[self.stack_base + index].clone();
+                    self.evaluated_stack.push(slot);
                 }
                 Some(OpCode::Print) => {
-                    self.bc_pos += 1;
-                    let val = self.pop();
-                    println!("{:?}", val)
+                    let val = self.pop()?;
+                    println!("{}", self.format_value(&val))
+                }
+                Some(OpCode::MakeList) => {
+                    let count = Self::read_operand(code, ip, width);
+                    let items = self.pop_many(count)?;
+                    let list = self.alloc_list(items);
+                    self.push(list);
+                    self.maybe_collect();
+                }
+                Some(OpCode::Concat) => {
+                    let count = Self::read_operand(code, ip, width);
+                    let parts = self.pop_many(count)?;
+                    let text: String = parts.iter().map(|part| self.format_value(part)).collect();
+                    self.push(StackSlot::from(text));
+                }
+                Some(OpCode::Index) => {
+                    let index = self.pop()?;
+                    let collection = self.pop()?;
+                    let item = self.get_item(&collection, &index)?;
+                    self.push(item);
+                }
+                Some(OpCode::StoreIndex) => {
+                    let val = self.pop()?;
+                    let index = self.pop()?;
+                    let collection = self.pop()?;
+                    self.set_item(&collection, &index, val)?;
+                }
+                Some(OpCode::ForIter) => {
+                    let slot = self.stack_base + Self::read_operand(code, ip, width);
+                    let exit = Self::read_operand(code, ip, width);
+                    let cursor = self.slot(slot + 1).as_i64().unwrap_or(0);
+                    let iterable = self.slot(slot);
+                    match self.iterate(&iterable, cursor as usize)? {
+                        Some((item, next_cursor)) => {
+                            self.set_slot(slot + 1, StackSlot::Int(next_cursor as i64));
+                            self.push(item);
+                        }
+                        None => *ip = exit
+                    }
+                }
+                Some(OpCode::MakeMap) => {
+                    let count = Self::read_operand(code, ip, width);
+                    let items = self.pop_many(count * 2)?;
+                    let mut map = Map::new();
+                    for pair in items.chunks(2) {
+                        map.insert(Self::map_key(&pair[0])?, pair[1].clone());
+                    }
+                    let map = self.alloc_map(map);
+                    self.push(map);
+                    self.maybe_collect();
+                }
+                Some(OpCode::GetField) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let obj = self.pop()?;
+                    let val = self.get_field(&obj, &constants[index])?;
+                    self.push(val);
+                }
+                Some(OpCode::MakeStruct) => {
+                    let def = Self::read_operand(code, ip, width);
+                    let count = Self::read_operand(code, ip, width);
+                    let values = self.pop_many(count)?;
+                    let mut fields = vec![StackSlot::Nil; self.structs[def].fields.len()];
+                    for val in values {
+                        let offset = Self::read_operand(code, ip, width);
+                        fields[offset] = val;
+                    }
+                    let instance = self.heap.alloc(Obj::Struct(def, fields));
+                    self.push(StackSlot::Struct(instance));
+                    self.maybe_collect();
+                }
+                Some(OpCode::GetFieldAt) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let def = Self::read_operand(code, ip, width);
+                    let offset = Self::read_operand(code, ip, width);
+                    let obj = self.pop()?;
+                    let val = match obj {
+                        StackSlot::Struct(instance) if self.heap.instance(instance).0 == def => self.heap.instance(instance).1[offset].clone(),
+                        _ => self.get_field(&obj, &constants[index])?
+                    };
+                    self.push(val);
+                }
+                Some(OpCode::SetFieldAt) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let def = Self::read_operand(code, ip, width);
+                    let offset = Self::read_operand(code, ip, width);
+                    let val = self.pop()?;
+                    let obj = self.pop()?;
+                    match obj {
+                        StackSlot::Struct(instance) if self.heap.instance(instance).0 == def => self.heap.instance_mut(instance).1[offset] = val,
+                        _ => self.set_field(&obj, &constants[index], val)?
+                    }
+                }
+                Some(OpCode::Class) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let class = self.heap.alloc(Obj::Class(Class::new(constants[index].as_str().unwrap_or_default())));
+                    self.push(StackSlot::Class(class));
+                    self.maybe_collect();
+                }
+                Some(OpCode::Inherit) => {
+                    let subclass = match self.pop()? {
+                        StackSlot::Class(class) => class,
+                        val => return Err(RuntimeError::new(format!("Expected a class, got {}", val.type_name())))
+                    };
+                    let superclass = match self.evaluated_stack.last().map(Packed::unpack) {
+                        Some(StackSlot::Class(class)) => class,
+                        Some(val) => return Err(RuntimeError::new(format!("Superclass must be a class, got {}", val.type_name()))),
+                        None => return Err(RuntimeError::new("Stack underflow"))
+                    };
+                    let methods = self.heap.class(superclass).methods.clone();
+                    let class = self.heap.class_mut(subclass);
+                    class.superclass = Some(superclass);
+                    class.methods.extend(methods);
+                }
+                Some(OpCode::Method) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let name = &constants[index];
+                    let method = match self.pop()? {
+                        StackSlot::Function(closure) => closure,
+                        val => return Err(RuntimeError::new(format!("Expected a method, got {}", val.type_name())))
+                    };
+                    match self.evaluated_stack.last().map(Packed::unpack) {
+                        Some(StackSlot::Class(class)) => {
+                            self.heap.class_mut(class).methods.insert(name.to_string(), method);
+                        }
+                        _ => return Err(RuntimeError::new("Method defined outside of a class"))
+                    }
+                }
+                Some(OpCode::Invoke) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let argc = code[*ip] as usize;
+                    *ip += 1;
+                    self.bc_pos = *ip;
+                    self.invoke(&constants[index], argc)?;
+                    *ip = self.bc_pos;
+                    (code, constants) = Self::chunk_slices(&chunks, self.chunk_index);
+                    self.maybe_collect();
+                }
+                Some(OpCode::GetSuper) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let superclass = self.pop()?;
+                    let receiver = self.pop()?;
+                    let method = self.find_method(&superclass, &constants[index])?;
+                    let bound = self.heap.alloc(Obj::BoundMethod(receiver, method));
+                    self.push(StackSlot::BoundMethod(bound));
+                    self.maybe_collect();
+                }
+                Some(OpCode::SuperInvoke) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let argc = code[*ip] as usize;
+                    *ip += 1;
+                    let superclass = self.pop()?;
+                    let method = self.find_method(&superclass, &constants[index])?;
+                    self.bc_pos = *ip;
+                    self.call_closure(method, argc)?;
+                    *ip = self.bc_pos;
+                    (code, constants) = Self::chunk_slices(&chunks, self.chunk_index);
+                }
+                Some(OpCode::MakeVariant) => {
+                    let def = Self::read_operand(code, ip, width);
+                    let variant = Self::read_operand(code, ip, width);
+                    let arity = self.enums[def].variants[variant].arity;
+                    let payload = self.pop_many(arity)?;
+                    let value = self.heap.alloc(Obj::Variant(def, variant, payload));
+                    self.push(StackSlot::Variant(value));
+                    self.maybe_collect();
                 }
-                _ => { break; }
+                Some(OpCode::IsVariant) => {
+                    let def = Self::read_operand(code, ip, width);
+                    let variant = Self::read_operand(code, ip, width);
+                    let is_variant = match self.pop()? {
+                        StackSlot::Variant(value) => {
+                            let (value_def, value_variant, _) = self.heap.variant(value);
+                            value_def == def && value_variant == variant
+                        }
+                        _ => false
+                    };
+                    self.push(StackSlot::Bool(is_variant));
+                }
+                Some(OpCode::GetPayload) => {
+                    let index = Self::read_operand(code, ip, width);
+                    match self.pop()? {
+                        StackSlot::Variant(value) => {
+                            let item = self.heap.variant(value).2[index].clone();
+                            self.push(item);
+                        }
+                        val => return Err(RuntimeError::new(format!("Expected an enum value, got {}", val.type_name())))
+                    }
+                }
+                Some(OpCode::IsStruct) => {
+                    let def = Self::read_operand(code, ip, width);
+                    let is_struct = match self.pop()? {
+                        StackSlot::Struct(instance) => self.heap.instance(instance).0 == def,
+                        _ => false
+                    };
+                    self.push(StackSlot::Bool(is_struct));
+                }
+                Some(OpCode::IsList) => {
+                    let len = Self::read_operand(code, ip, width);
+                    let is_list = match self.pop()? {
+                        StackSlot::List(list) => self.heap.list(list).len() == len,
+                        _ => false
+                    };
+                    self.push(StackSlot::Bool(is_list));
+                }
+                Some(OpCode::InRange) => {
+                    let inclusive = code[*ip] != 0;
+                    *ip += 1;
+                    let hi = self.pop()?;
+                    let lo = self.pop()?;
+                    let val = self.pop()?;
+                    let above = val.compare(&lo).is_some_and(|ordering| ordering.is_ge());
+                    let below = val.compare(&hi).is_some_and(|ordering| if inclusive { ordering.is_le() } else { ordering.is_lt() });
+                    self.push(StackSlot::Bool(above && below));
+                }
+                Some(OpCode::JmpTable) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let min = constants[index].as_i64().unwrap_or_default();
+                    let count = Self::read_operand(code, ip, width);
+                    let default = Self::read_operand(code, ip, width);
+                    // floats and big ints equal to an int select its arm, as
+                    // they would with `==`; the offset of a value far from
+                    // `min` does not fit in an int
+                    let offset = self.pop()?.as_integral()
+                        .and_then(|val| val.checked_sub(min))
+                        .filter(|offset| (0..count as i64).contains(offset));
+                    *ip = match offset {
+                        Some(offset) => {
+                            let mut case = *ip + offset as usize * width;
+                            Self::read_operand(code, &mut case, width)
+                        }
+                        None => default
+                    };
+                }
+                Some(OpCode::NoMatch) => {
+                    let val = self.pop()?;
+                    return Err(RuntimeError::new(format!("No match arm matches value {}", self.format_value(&val))));
+                }
+                Some(OpCode::Throw) => {
+                    let val = self.pop()?;
+                    let err = match &val {
+                        StackSlot::Error(err) => (**err).clone(),
+                        _ => RuntimeError::new(format!("Uncaught exception: {}", self.format_value(&val)))
+                    };
+                    self.thrown = Some(val);
+                    return Err(err);
+                }
+                Some(OpCode::PushHandler) => {
+                    let target = Self::read_operand(code, ip, width);
+                    self.handlers.push(Handler {
+                        target,
+                        chunk_index: self.chunk_index,
+                        frame_depth: self.call_stack.len(),
+                        stack_height: self.evaluated_stack.len(),
+                        stack_base: self.stack_base,
+                        closure: self.closure
+                    });
+                }
+                Some(OpCode::PopHandler) => {
+                    self.handlers.pop();
+                }
+                Some(OpCode::SetField) => {
+                    let index = Self::read_operand(code, ip, width);
+                    let val = self.pop()?;
+                    let obj = self.pop()?;
+                    self.set_field(&obj, &constants[index], val)?;
+                }
+                None => return Err(RuntimeError::new(format!("Unknown opcode {}", byte)))
+            }
+        }
+        return Ok(self.evaluated_stack.pop().map_or(StackSlot::Nil, Packed::into_slot));
+    }
+
+    /// Calls the value sitting below the top `argc` arguments. Calling a
+    /// class creates an instance and runs its `init` method, if any.
+    fn call_value(&mut self, argc: usize) -> Result<(), RuntimeError> {
+        if self.evaluated_stack.len() < argc + 1 {
+            return Err(RuntimeError::new("Stack underflow"));
+        }
+        let callee_slot = self.evaluated_stack.len() - argc - 1;
+        match self.slot(callee_slot) {
+            StackSlot::Function(closure) => self.call_closure(closure, argc),
+            StackSlot::Native(index) => {
+                self.call_native(index, argc)?;
+                let result = self.pop()?;
+                self.set_slot(callee_slot, result);
+                return Ok(());
+            }
+            StackSlot::Class(class) => {
+                let instance = self.heap.alloc(Obj::Instance(Instance { class, fields: Map::new() }));
+                self.set_slot(callee_slot, StackSlot::Instance(instance));
+                let class = self.heap.class(class);
+                match class.methods.get("init") {
+                    Some(init) => self.call_closure(*init, argc),
+                    None if argc != 0 => Err(RuntimeError::new(format!("`{}` expects 0 arguments, got {}", class.name, argc))),
+                    None => Ok(())
+                }
+            }
+            StackSlot::BoundMethod(bound) => {
+                let (receiver, method) = self.heap.bound_method(bound);
+                self.set_slot(callee_slot, receiver.clone());
+                self.call_closure(method, argc)
+            }
+            val => Err(RuntimeError::new(format!("Value of type {} is not callable", val.type_name())))
+        }
+    }
+
+    /// Calls `receiver.name(args)` for the receiver sitting below the top
+    /// `argc` arguments. Instance fields shadow methods; for other values a
+    /// native named `name` is called with the receiver as first argument,
+    /// falling back to calling the field `name`.
+    fn invoke(&mut self, name: &StackSlot, argc: usize) -> Result<(), RuntimeError> {
+        if self.evaluated_stack.len() < argc + 1 {
+            return Err(RuntimeError::new("Stack underflow"));
+        }
+        let receiver_slot = self.evaluated_stack.len() - argc - 1;
+        let receiver = self.slot(receiver_slot);
+        let method_name = name.as_str().unwrap_or_default();
+        if let StackSlot::Instance(instance) = receiver {
+            let instance = self.heap.object(instance);
+            if let Some(field) = instance.fields.get(&MapKey::Str(method_name.into())) {
+                let field = field.clone();
+                self.set_slot(receiver_slot, field);
+                return self.call_value(argc);
+            }
+            let class = self.heap.class(instance.class);
+            return match class.methods.get(method_name) {
+                Some(method) => self.call_closure(*method, argc),
+                None => Err(RuntimeError::new(format!("`{}` object has no method `{}`", class.name, method_name)))
+            };
+        }
+        if let Some(index) = self.natives.iter().position(|n| n.name == method_name) {
+            return self.call_native(index, argc + 1);
+        }
+        let callee = self.get_field(&receiver, name)?;
+        self.set_slot(receiver_slot, callee);
+        return self.call_value(argc);
+    }
+
+    fn find_method(&self, class: &StackSlot, name: &StackSlot) -> Result<ObjRef, RuntimeError> {
+        let class = match class {
+            StackSlot::Class(class) => self.heap.class(*class),
+            val => return Err(RuntimeError::new(format!("Expected a class, got {}", val.type_name())))
+        };
+        return class.methods.get(name.as_str().unwrap_or_default()).copied()
+            .ok_or_else(|| RuntimeError::new(format!("`{}` has no method `{}`", class.name, name)));
+    }
+
+    /// Enters the closure sitting below its `argc` arguments. The callee
+    /// slot becomes local 0 of the new frame (`self` in methods) and the
+    /// arguments the following locals.
+    fn call_closure(&mut self, closure: ObjRef, argc: usize) -> Result<(), RuntimeError> {
+        let (function, _) = self.heap.closure(closure);
+        let proto = &self.functions[function];
+        if proto.arity != argc {
+            let name = proto.name.as_deref().unwrap_or("<anonymous>");
+            return Err(RuntimeError::new(format!("`{}` expects {} arguments, got {}", name, proto.arity, argc)));
+        }
+        if self.call_stack.len() >= MAX_FRAMES {
+            return Err(RuntimeError::new("Stack overflow"));
+        }
+        let chunk_index = proto.chunk_index;
+        self.call_stack.push(CallStackSlot {
+            bc_pos: self.bc_pos,
+            chunk_index: self.chunk_index,
+            stack_base: self.stack_base,
+            closure: self.closure
+        });
+        self.chunk_index = chunk_index;
+        self.bc_pos = 0;
+        self.stack_base = self.evaluated_stack.len() - argc - 1;
+        self.closure = Some(closure);
+        return Ok(());
+    }
+
+    /// Replaces the top `argc` values with the result of native `index`.
+    fn call_native(&mut self, index: usize, argc: usize) -> Result<(), RuntimeError> {
+        if self.evaluated_stack.len() < argc {
+            return Err(RuntimeError::new("Stack underflow"));
+        }
+        let native = &self.natives[index];
+        native.check_arity(argc)?;
+        let function = native.function;
+        let args = self.pop_many(argc)?;
+        let result = function(self, &args);
+        let escaped = self.escaped.take();
+        if let Err(err) = &result {
+            self.thrown = escaped.filter(|(escaped, _)| escaped == err).map(|(_, thrown)| thrown);
+        }
+        self.push(result?);
+        return Ok(());
+    }
+
+    fn upvalue_ref(&self, index: usize) -> Result<ObjRef, RuntimeError> {
+        let closure = self.closure.ok_or_else(|| RuntimeError::new("Upvalue access outside of a closure"))?;
+        return Ok(self.heap.closure(closure).1[index]);
+    }
+
+    /// Returns the open upvalue for stack slot `slot`, creating it if no
+    /// closure has captured that slot yet, so that closures share it.
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        for upvalue in &self.open_upvalues {
+            if let Upvalue::Open(open_slot) = self.heap.upvalue(*upvalue) && *open_slot == slot {
+                return *upvalue;
+            }
+        }
+        let upvalue = self.heap.alloc(Obj::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        return upvalue;
+    }
+
+    /// Moves the values of stack slots at or above `from` into the upvalues
+    /// that capture them.
+    fn close_upvalues(&mut self, from: usize) {
+        let mut still_open = Vec::new();
+        for upvalue in std::mem::take(&mut self.open_upvalues) {
+            let slot = match self.heap.upvalue(upvalue) {
+                Upvalue::Open(slot) => *slot,
+                Upvalue::Closed(_) => continue
+            };
+            if slot >= from {
+                *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(self.slot(slot));
             }
+            else {
+                still_open.push(upvalue);
+            }
+        }
+        self.open_upvalues = still_open;
+    }
+
+    /// Returns the display name of a function value.
+    pub fn function_name(&self, slot: &StackSlot) -> Option<&str> {
+        match slot {
+            StackSlot::Function(closure) => self.functions[self.heap.closure(*closure).0].name.as_deref(),
+            StackSlot::Native(index) => Some(&self.natives[*index].name),
+            StackSlot::BoundMethod(bound) => self.function_name(&StackSlot::Function(self.heap.bound_method(*bound).1)),
+            _ => None
         }
     }
 
-    pub fn get_index(&mut self) -> usize {
-        let mut index = 0;
-        index += (self.chunks[self.chunk_index].bytecode[self.bc_pos] as usize) << 16;
-        index += (self.chunks[self.chunk_index].bytecode[self.bc_pos + 1] as usize) << 8;
-        index += self.chunks[self.chunk_index].bytecode[self.bc_pos + 2] as usize;
-        self.bc_pos += 3;
+    /// Reads the `width`-byte operand at `ip` and moves past it.
+    #[inline(always)]
+    fn read_operand(code: &[u8], ip: &mut usize, width: usize) -> usize {
+        if width == 1 {
+            *ip += 1;
+            return code[*ip - 1] as usize;
+        }
+        let index = code[*ip..*ip + width].iter().fold(0, |index, byte| index << 8 | *byte as usize);
+        *ip += width;
         return index;
     }
-}
\ No newline at end of file
+
+    fn chunk_slices(chunks: &[Chunk], chunk_index: usize) -> (&[u8], &[StackSlot]) {
+        let chunk = &chunks[chunk_index];
+        return (&chunk.bytecode, &chunk.constants);
+    }
+
+    /// Equality used by `==`: lists are equal when their items are.
+    pub fn values_equal(&self, lhs: &StackSlot, rhs: &StackSlot) -> bool {
+        let mut visited = Vec::new();
+        return self.values_equal_inner(lhs, rhs, &mut visited);
+    }
+
+    fn values_equal_inner(&self, lhs: &StackSlot, rhs: &StackSlot, visited: &mut Vec<(ObjRef, ObjRef)>) -> bool {
+        match (lhs, rhs) {
+            (StackSlot::List(a), StackSlot::List(b)) => {
+                if a == b || visited.contains(&(*a, *b)) {
+                    return true;
+                }
+                visited.push((*a, *b));
+                let lhs_items = self.heap.list(*a);
+                let rhs_items = self.heap.list(*b);
+                lhs_items.len() == rhs_items.len() &&
+                    lhs_items.iter().zip(rhs_items.iter()).all(|(x, y)| self.values_equal_inner(x, y, visited))
+            }
+            (StackSlot::Struct(a), StackSlot::Struct(b)) => {
+                if a == b || visited.contains(&(*a, *b)) {
+                    return true;
+                }
+                visited.push((*a, *b));
+                let (lhs_def, lhs_fields) = self.heap.instance(*a);
+                let (rhs_def, rhs_fields) = self.heap.instance(*b);
+                lhs_def == rhs_def &&
+                    lhs_fields.iter().zip(rhs_fields.iter()).all(|(x, y)| self.values_equal_inner(x, y, visited))
+            }
+            (StackSlot::Variant(a), StackSlot::Variant(b)) => {
+                if a == b || visited.contains(&(*a, *b)) {
+                    return true;
+                }
+                visited.push((*a, *b));
+                let (lhs_def, lhs_variant, lhs_payload) = self.heap.variant(*a);
+                let (rhs_def, rhs_variant, rhs_payload) = self.heap.variant(*b);
+                lhs_def == rhs_def && lhs_variant == rhs_variant &&
+                    lhs_payload.iter().zip(rhs_payload.iter()).all(|(x, y)| self.values_equal_inner(x, y, visited))
+            }
+            (StackSlot::Map(a), StackSlot::Map(b)) => {
+                if a == b || visited.contains(&(*a, *b)) {
+                    return true;
+                }
+                visited.push((*a, *b));
+                let lhs_map = self.heap.map(*a);
+                let rhs_map = self.heap.map(*b);
+                lhs_map.len() == rhs_map.len() &&
+                    lhs_map.iter().all(|(key, x)| rhs_map.get(key).is_some_and(|y| self.values_equal_inner(x, y, visited)))
+            }
+            _ => lhs.equals(rhs)
+        }
+    }
+
+    fn get_item(&self, collection: &StackSlot, index: &StackSlot) -> Result<StackSlot, RuntimeError> {
+        match (collection, index) {
+            (StackSlot::List(obj_ref), StackSlot::Int(i)) => {
+                let items = self.heap.list(*obj_ref);
+                let pos = Self::check_bounds(*i, items.len())?;
+                Ok(items[pos].clone())
+            }
+            (StackSlot::Str(val), StackSlot::Int(i)) => {
+                let len = val.chars().count();
+                let pos = Self::check_bounds(*i, len)?;
+                Ok(StackSlot::Char(val.chars().nth(pos).unwrap()))
+            }
+            (StackSlot::Map(obj_ref), _) => {
+                let key = Self::map_key(index)?;
+                self.heap.map(*obj_ref).get(&key).cloned()
+                    .ok_or_else(|| RuntimeError::new(format!("Key {} not found in map", self.format_key(&key))))
+            }
+            (StackSlot::List(_), _) | (StackSlot::Str(_), _) =>
+                Err(RuntimeError::new(format!("Index must be an int, got {}", index.type_name()))),
+            _ => Err(RuntimeError::new(format!("Cannot index into a value of type {}", collection.type_name())))
+        }
+    }
+
+    fn set_item(&mut self, collection: &StackSlot, index: &StackSlot, val: StackSlot) -> Result<(), RuntimeError> {
+        match (collection, index) {
+            (StackSlot::List(obj_ref), StackSlot::Int(i)) => {
+                let items = self.heap.list_mut(*obj_ref);
+                let pos = Self::check_bounds(*i, items.len())?;
+                items[pos] = val;
+                Ok(())
+            }
+            (StackSlot::Map(obj_ref), _) => {
+                let key = Self::map_key(index)?;
+                self.heap.map_mut(*obj_ref).insert(key, val);
+                Ok(())
+            }
+            (StackSlot::List(_), _) =>
+                Err(RuntimeError::new(format!("Index must be an int, got {}", index.type_name()))),
+            _ => Err(RuntimeError::new(format!("Cannot assign by index into a value of type {}", collection.type_name())))
+        }
+    }
+
+    /// `obj.name` reads a field of a struct or an instance, or the string
+    /// key `name` of a map. Reading a method of an instance binds it to the
+    /// instance.
+    fn get_field(&mut self, obj: &StackSlot, name: &StackSlot) -> Result<StackSlot, RuntimeError> {
+        match obj {
+            StackSlot::Struct(instance) => {
+                let offset = self.field_offset(*instance, name)?;
+                Ok(self.heap.instance(*instance).1[offset].clone())
+            }
+            StackSlot::Instance(instance) => {
+                let instance = self.heap.object(*instance);
+                let key = MapKey::Str(name.as_str().unwrap_or_default().into());
+                if let Some(val) = instance.fields.get(&key) {
+                    return Ok(val.clone());
+                }
+                let class = self.heap.class(instance.class);
+                match class.methods.get(name.as_str().unwrap_or_default()) {
+                    Some(method) => Ok(StackSlot::BoundMethod(self.heap.alloc(Obj::BoundMethod(obj.clone(), *method)))),
+                    None => Err(RuntimeError::new(format!("`{}` object has no field `{}`", class.name, name)))
+                }
+            }
+            StackSlot::Map(_) => self.get_item(obj, name),
+            StackSlot::Error(err) => match (name.as_str().unwrap_or_default(), &err.location) {
+                ("message", _) => Ok(StackSlot::from(err.message.as_str())),
+                ("line", Some(loc)) => Ok(StackSlot::Int(loc.line as i64)),
+                ("col", Some(loc)) => Ok(StackSlot::Int(loc.col as i64)),
+                ("line" | "col", None) => Ok(StackSlot::Nil),
+                _ => Err(RuntimeError::new(format!("Value of type error has no field `{}`", name)))
+            }
+            _ => Err(RuntimeError::new(format!("Value of type {} has no field `{}`", obj.type_name(), name)))
+        }
+    }
+
+    fn set_field(&mut self, obj: &StackSlot, name: &StackSlot, val: StackSlot) -> Result<(), RuntimeError> {
+        match obj {
+            StackSlot::Struct(instance) => {
+                let offset = self.field_offset(*instance, name)?;
+                self.heap.instance_mut(*instance).1[offset] = val;
+                Ok(())
+            }
+            StackSlot::Instance(instance) => {
+                let key = MapKey::Str(name.as_str().unwrap_or_default().into());
+                self.heap.object_mut(*instance).fields.insert(key, val);
+                Ok(())
+            }
+            StackSlot::Map(_) => self.set_item(obj, name, val),
+            _ => Err(RuntimeError::new(format!("Cannot set field `{}` on a value of type {}", name, obj.type_name())))
+        }
+    }
+
+    fn field_offset(&self, instance: ObjRef, name: &StackSlot) -> Result<usize, RuntimeError> {
+        let def = &self.structs[self.heap.instance(instance).0];
+        return def.field_offset(name.as_str().unwrap_or_default())
+            .ok_or_else(|| RuntimeError::new(format!("Struct `{}` has no field `{}`", def.name, name)));
+    }
+
+    fn check_bounds(index: i64, len: usize) -> Result<usize, RuntimeError> {
+        if index < 0 || index as usize >= len {
+            return Err(RuntimeError::new(format!("Index {} is out of bounds for length {}", index, len)));
+        }
+        return Ok(index as usize);
+    }
+
+    /// Returns the item at position `cursor` of a `for` loop over
+    /// `iterable` together with the next cursor, or `None` once it is
+    /// exhausted. Maps yield their keys in insertion order.
+    fn iterate(&self, iterable: &StackSlot, cursor: usize) -> Result<Option<(StackSlot, usize)>, RuntimeError> {
+        match iterable {
+            StackSlot::List(obj_ref) => Ok(self.heap.list(*obj_ref).get(cursor).map(|item| (item.clone(), cursor + 1))),
+            StackSlot::Str(val) => Ok(val.chars().nth(cursor).map(|c| (StackSlot::Char(c), cursor + 1))),
+            StackSlot::Map(obj_ref) => Ok(self.heap.map(*obj_ref).entry_from(cursor).map(|(next, key, _)| (key.to_slot(), next))),
+            _ => Err(RuntimeError::new(format!("Cannot iterate over a value of type {}", iterable.type_name())))
+        }
+    }
+
+    /// The generic `+`. The specialised additions fall back to it when type
+    /// inference was defeated by a value it does not model.
+    fn add(lhs: StackSlot, rhs: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match (lhs, rhs) {
+            (StackSlot::Int(a), StackSlot::Int(b)) => Self::add_ints(a, b),
+            (StackSlot::Float(a), StackSlot::Int(b)) => Ok(StackSlot::Float(a + b as f64)),
+            (StackSlot::Int(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a as f64 + b)),
+            (StackSlot::Float(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a + b)),
+            (StackSlot::Str(a), StackSlot::Str(b)) => Ok(StackSlot::from(format!("{}{}", a, b))),
+            (a, b) => Self::big_op("+", a, b, |a, b| Some(a + b))
+        };
+    }
+
+    #[inline(always)]
+    fn add_ints(a: i64, b: i64) -> Result<StackSlot, RuntimeError> {
+        return a.checked_add(b).map(StackSlot::Int).ok_or_else(|| Self::overflow_error("+"));
+    }
+
+    fn sub(lhs: StackSlot, rhs: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match (lhs, rhs) {
+            (StackSlot::Int(a), StackSlot::Int(b)) => a.checked_sub(b).map(StackSlot::Int).ok_or_else(|| Self::overflow_error("-")),
+            (StackSlot::Float(a), StackSlot::Int(b)) => Ok(StackSlot::Float(a - b as f64)),
+            (StackSlot::Int(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a as f64 - b)),
+            (StackSlot::Float(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a - b)),
+            (a, b) => Self::big_op("-", a, b, |a, b| Some(a - b))
+        };
+    }
+
+    fn mul(lhs: StackSlot, rhs: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match (lhs, rhs) {
+            (StackSlot::Int(a), StackSlot::Int(b)) => a.checked_mul(b).map(StackSlot::Int).ok_or_else(|| Self::overflow_error("*")),
+            (StackSlot::Float(a), StackSlot::Int(b)) => Ok(StackSlot::Float(a * b as f64)),
+            (StackSlot::Int(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a as f64 * b)),
+            (StackSlot::Float(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a * b)),
+            (a, b) => Self::big_op("*", a, b, |a, b| Some(a * b))
+        };
+    }
+
+    /// `/` on two ints rounds towards zero. With a float operand it follows
+    /// IEEE 754, so dividing by zero gives an infinity or NaN.
+    fn div(lhs: StackSlot, rhs: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match (lhs, rhs) {
+            (StackSlot::Int(_), StackSlot::Int(0)) => Err(RuntimeError::new("Division by zero")),
+            (StackSlot::Int(a), StackSlot::Int(b)) => a.checked_div(b).map(StackSlot::Int).ok_or_else(|| Self::overflow_error("/")),
+            (StackSlot::Float(a), StackSlot::Int(b)) => Ok(StackSlot::Float(a / b as f64)),
+            (StackSlot::Int(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a as f64 / b)),
+            (StackSlot::Float(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a / b)),
+            (a, b) => Self::big_op("/", a, b, |a, b| a.div_rem(b).map(|(quotient, _)| quotient))
+        };
+    }
+
+    /// `//` rounds the quotient towards negative infinity, for ints and
+    /// floats alike.
+    fn floor_div(lhs: StackSlot, rhs: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match (lhs, rhs) {
+            (StackSlot::Int(_), StackSlot::Int(0)) => Err(RuntimeError::new("Division by zero")),
+            (StackSlot::Int(a), StackSlot::Int(b)) => floor_div(a, b).map(StackSlot::Int).ok_or_else(|| Self::overflow_error("//")),
+            (StackSlot::Float(a), StackSlot::Int(b)) => Ok(StackSlot::Float((a / b as f64).floor())),
+            (StackSlot::Int(a), StackSlot::Float(b)) => Ok(StackSlot::Float((a as f64 / b).floor())),
+            (StackSlot::Float(a), StackSlot::Float(b)) => Ok(StackSlot::Float((a / b).floor())),
+            (a, b) => Self::big_op("//", a, b, BigInt::div_floor)
+        };
+    }
+
+    /// `%` is the remainder of `/`: it truncates, so the result has the
+    /// sign of the dividend. `i64::MIN % -1` is 0.
+    fn rem(lhs: StackSlot, rhs: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match (lhs, rhs) {
+            (StackSlot::Int(_), StackSlot::Int(0)) => Err(RuntimeError::new("Division by zero")),
+            (StackSlot::Int(a), StackSlot::Int(b)) => Ok(StackSlot::Int(a.wrapping_rem(b))),
+            (StackSlot::Float(a), StackSlot::Int(b)) => Ok(StackSlot::Float(a % b as f64)),
+            (StackSlot::Int(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a as f64 % b)),
+            (StackSlot::Float(a), StackSlot::Float(b)) => Ok(StackSlot::Float(a % b)),
+            (a, b) => Self::big_op("%", a, b, |a, b| a.div_rem(b).map(|(_, remainder)| remainder))
+        };
+    }
+
+    fn neg(val: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match val {
+            StackSlot::Int(val) => val.checked_neg().map(StackSlot::Int).ok_or_else(|| Self::overflow_error("-")),
+            StackSlot::Float(val) => Ok(StackSlot::Float(-val)),
+            StackSlot::BigInt(val) => Ok(StackSlot::from(-&*val)),
+            val => Err(RuntimeError::new(format!("Unsupported operand type for `-`: {}", val.type_name())))
+        };
+    }
+
+    /// Applies `op` when one operand is a big int and the other an int or a
+    /// big int, which gives a big int. `op` gives `None` for a division by
+    /// zero.
+    fn big_op(symbol: &str, lhs: StackSlot, rhs: StackSlot, op: fn(&BigInt, &BigInt) -> Option<BigInt>) -> Result<StackSlot, RuntimeError> {
+        return match (lhs.as_bigint(), rhs.as_bigint()) {
+            (Some(a), Some(b)) => op(&a, &b).map(StackSlot::from).ok_or_else(|| RuntimeError::new("Division by zero")),
+            _ => Err(Self::operand_error(symbol, &lhs, &rhs))
+        };
+    }
+
+    fn not(val: StackSlot) -> Result<StackSlot, RuntimeError> {
+        return match val {
+            StackSlot::Bool(val) => Ok(StackSlot::Bool(!val)),
+            val => Err(RuntimeError::new(format!("Unsupported operand type for `!`: {}", val.type_name())))
+        };
+    }
+
+    fn equal_operands(&mut self) -> Result<bool, RuntimeError> {
+        let rhs = self.pop()?;
+        let lhs = self.pop()?;
+        return Ok(self.values_equal(&lhs, &rhs));
+    }
+
+    /// Pops and compares two operands. The ordering is `None` when a float
+    /// is NaN, which makes every comparison false as in IEEE 754.
+    fn compare_operands(&mut self, op: &str) -> Result<Option<Ordering>, RuntimeError> {
+        let rhs = self.pop()?;
+        let lhs = self.pop()?;
+        if !lhs.is_comparable(&rhs) {
+            return Err(Self::operand_error(op, &lhs, &rhs));
+        }
+        return Ok(lhs.compare(&rhs));
+    }
+
+    fn operand_error(op: &str, lhs: &StackSlot, rhs: &StackSlot) -> RuntimeError {
+        return RuntimeError::new(format!("Unsupported operand types for `{}`: {} and {}", op, lhs.type_name(), rhs.type_name()));
+    }
+
+    fn overflow_error(op: &str) -> RuntimeError {
+        return RuntimeError::new(format!("Integer overflow in `{}`", op));
+    }
+}
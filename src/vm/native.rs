@@ -0,0 +1,28 @@
+use super::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+/// Arity of a native that accepts any number of arguments.
+pub const VARIADIC: usize = usize::MAX;
+
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, RuntimeError>;
+
+/// A host function exposed to scripts under `name`.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn
+}
+
+impl NativeFunction {
+    /// Fails unless the native accepts `argc` arguments.
+    pub fn check_arity(&self, argc: usize) -> Result<(), RuntimeError> {
+        if self.arity != VARIADIC && self.arity != argc {
+            return Err(RuntimeError::new(format!("`{}` expects {} arguments, got {}", self.name, self.arity, argc)));
+        }
+        return Ok(());
+    }
+}
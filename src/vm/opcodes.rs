@@ -1,3 +1,4 @@
+#[derive(Debug)]
 pub enum OpCode {
     Push        = 0,
     Pop         = 1,
@@ -15,6 +16,55 @@ pub enum OpCode {
     StoreLoc    = 13,
     LoadLoc     = 14,
     Print       = 15,
+    CallNative  = 16,
+    Not         = 17,
+    Neg         = 18,
+    Eq          = 19,
+    NotEq       = 20,
+    Lt          = 21,
+    LtEq        = 22,
+    Gt          = 23,
+    GtEq        = 24,
+    JmpIfFalse  = 25,
+    MakeList    = 26,
+    Index       = 27,
+    StoreIndex  = 28,
+    ForIter     = 29,
+    MakeMap     = 30,
+    GetField    = 31,
+    SetField    = 32,
+    Closure     = 33,
+    GetUpvalue  = 34,
+    SetUpvalue  = 35,
+    CloseUpvalue = 36,
+    MakeStruct  = 37,
+    GetFieldAt  = 38,
+    SetFieldAt  = 39,
+    Class       = 40,
+    Inherit     = 41,
+    Method      = 42,
+    Invoke      = 43,
+    GetSuper    = 44,
+    SuperInvoke = 45,
+    MakeVariant = 46,
+    IsVariant   = 47,
+    GetPayload  = 48,
+    IsStruct    = 49,
+    IsList      = 50,
+    InRange     = 51,
+    JmpTable    = 52,
+    NoMatch     = 53,
+    AddInt      = 54,
+    AddFloat    = 55,
+    Throw       = 56,
+    PushHandler = 57,
+    PopHandler  = 58,
+    Concat      = 59,
+    AddConst    = 60,
+    IncGlobal   = 61,
+    JmpIfFalseCmp = 62,
+    Wide        = 63,
+    FloorDiv    = 64,
 }
 
 impl OpCode {
@@ -36,6 +86,55 @@ impl OpCode {
             13  => Some(OpCode::StoreLoc),
             14  => Some(OpCode::LoadLoc),
             15  => Some(OpCode::Print),
+            16  => Some(OpCode::CallNative),
+            17  => Some(OpCode::Not),
+            18  => Some(OpCode::Neg),
+            19  => Some(OpCode::Eq),
+            20  => Some(OpCode::NotEq),
+            21  => Some(OpCode::Lt),
+            22  => Some(OpCode::LtEq),
+            23  => Some(OpCode::Gt),
+            24  => Some(OpCode::GtEq),
+            25  => Some(OpCode::JmpIfFalse),
+            26  => Some(OpCode::MakeList),
+            27  => Some(OpCode::Index),
+            28  => Some(OpCode::StoreIndex),
+            29  => Some(OpCode::ForIter),
+            30  => Some(OpCode::MakeMap),
+            31  => Some(OpCode::GetField),
+            32  => Some(OpCode::SetField),
+            33  => Some(OpCode::Closure),
+            34  => Some(OpCode::GetUpvalue),
+            35  => Some(OpCode::SetUpvalue),
+            36  => Some(OpCode::CloseUpvalue),
+            37  => Some(OpCode::MakeStruct),
+            38  => Some(OpCode::GetFieldAt),
+            39  => Some(OpCode::SetFieldAt),
+            40  => Some(OpCode::Class),
+            41  => Some(OpCode::Inherit),
+            42  => Some(OpCode::Method),
+            43  => Some(OpCode::Invoke),
+            44  => Some(OpCode::GetSuper),
+            45  => Some(OpCode::SuperInvoke),
+            46  => Some(OpCode::MakeVariant),
+            47  => Some(OpCode::IsVariant),
+            48  => Some(OpCode::GetPayload),
+            49  => Some(OpCode::IsStruct),
+            50  => Some(OpCode::IsList),
+            51  => Some(OpCode::InRange),
+            52  => Some(OpCode::JmpTable),
+            53  => Some(OpCode::NoMatch),
+            54  => Some(OpCode::AddInt),
+            55  => Some(OpCode::AddFloat),
+            56  => Some(OpCode::Throw),
+            57  => Some(OpCode::PushHandler),
+            58  => Some(OpCode::PopHandler),
+            59  => Some(OpCode::Concat),
+            60  => Some(OpCode::AddConst),
+            61  => Some(OpCode::IncGlobal),
+            62  => Some(OpCode::JmpIfFalseCmp),
+            63  => Some(OpCode::Wide),
+            64  => Some(OpCode::FloorDiv),
             _   => None
         }
     }
@@ -0,0 +1,38 @@
+use super::stack_slot::StackSlot;
+
+#[cfg(feature = "nan-boxing")]
+mod nan_box;
+
+/// How the value stack and the globals store values: `StackSlot` itself,
+/// or a one-word `NanBox` with the `nan-boxing` feature.
+#[cfg(not(feature = "nan-boxing"))]
+pub type Packed = StackSlot;
+
+#[cfg(feature = "nan-boxing")]
+pub type Packed = nan_box::NanBox;
+
+/// Conversion between `StackSlot` and its stored representation.
+pub trait Pack: Clone {
+    fn pack(slot: StackSlot) -> Self;
+
+    fn unpack(&self) -> StackSlot;
+
+    fn into_slot(self) -> StackSlot;
+}
+
+impl Pack for StackSlot {
+    #[inline(always)]
+    fn pack(slot: StackSlot) -> Self {
+        return slot;
+    }
+
+    #[inline(always)]
+    fn unpack(&self) -> StackSlot {
+        return self.clone();
+    }
+
+    #[inline(always)]
+    fn into_slot(self) -> StackSlot {
+        return self;
+    }
+}
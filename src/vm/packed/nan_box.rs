@@ -0,0 +1,266 @@
+use std::{fmt, rc::Rc};
+
+use crate::vm::{heap::ObjRef, stack_slot::StackSlot};
+
+use super::Pack;
+
+/// Bits set in every tagged value: the sign, the exponent and the quiet
+/// bit of a NaN.
+const TAGGED: u64 = 0xFFF8_0000_0000_0000;
+const CANONICAL_NAN: u64 = 0x7FF8_0000_0000_0000;
+const TAG_SHIFT: u32 = 48;
+const PAYLOAD: u64 = (1 << TAG_SHIFT) - 1;
+
+const NIL: u64 = 0;
+const BOOL: u64 = 1;
+const INT: u64 = 2;
+const CHAR: u64 = 3;
+const NATIVE: u64 = 4;
+const OBJECT: u64 = 5;
+const BOXED: u64 = 6;
+
+/// Objects keep their kind in the top three payload bits and their heap
+/// index in the rest.
+const KIND_SHIFT: u32 = 45;
+const INDEX: u64 = (1 << KIND_SHIFT) - 1;
+
+const INT_MIN: i64 = -(1 << 47);
+const INT_MAX: i64 = (1 << 47) - 1;
+
+/// A value packed into one word. Floats are stored as themselves, with
+/// every NaN made canonical. Any other value is a negative quiet NaN with
+/// a three-bit tag and a 48-bit payload. Values that do not fit, strings,
+/// errors, big ints and ints beyond 48 bits, are boxed: the payload points to a
+/// reference-counted `StackSlot`.
+///
+/// Boxing allocates on every push of such a value, on top of the reference
+/// count the string or big int already has, so string-heavy code gains less
+/// from this representation than numeric code; `benches/packing.rs`
+/// measures the difference.
+pub struct NanBox(u64);
+
+impl NanBox {
+    #[inline(always)]
+    fn tagged(tag: u64, payload: u64) -> Self {
+        return NanBox(TAGGED | tag << TAG_SHIFT | payload);
+    }
+
+    /// Moves `slot` into a new allocation. Strings pay for this each time
+    /// they are pushed, although their contents are not copied.
+    fn boxed(slot: StackSlot) -> Self {
+        let ptr = Rc::into_raw(Rc::new(slot)) as u64;
+        assert!(ptr & !PAYLOAD == 0, "Pointer does not fit in a NaN box");
+        return Self::tagged(BOXED, ptr);
+    }
+
+    fn object(kind: u64, obj_ref: ObjRef, slot: StackSlot) -> Self {
+        return match obj_ref.0 as u64 <= INDEX {
+            true => Self::tagged(OBJECT, kind << KIND_SHIFT | obj_ref.0 as u64),
+            false => Self::boxed(slot)
+        };
+    }
+
+    #[inline(always)]
+    fn tag(&self) -> Option<u64> {
+        return match self.0 & TAGGED == TAGGED {
+            true => Some(self.0 >> TAG_SHIFT & 0b111),
+            false => None
+        };
+    }
+
+    #[inline(always)]
+    fn payload(&self) -> u64 {
+        return self.0 & PAYLOAD;
+    }
+
+    #[inline(always)]
+    fn boxed_ptr(&self) -> Option<*const StackSlot> {
+        return match self.tag() {
+            Some(BOXED) => Some(self.payload() as *const StackSlot),
+            _ => None
+        };
+    }
+}
+
+impl Pack for NanBox {
+    #[inline]
+    fn pack(slot: StackSlot) -> Self {
+        return match slot {
+            StackSlot::Nil => Self::tagged(NIL, 0),
+            StackSlot::Bool(val) => Self::tagged(BOOL, val as u64),
+            StackSlot::Int(val) if (INT_MIN..=INT_MAX).contains(&val) => Self::tagged(INT, val as u64 & PAYLOAD),
+            StackSlot::Float(val) if val.is_nan() => NanBox(CANONICAL_NAN),
+            StackSlot::Float(val) => NanBox(val.to_bits()),
+            StackSlot::Char(val) => Self::tagged(CHAR, val as u64),
+            StackSlot::Native(index) if index as u64 <= PAYLOAD => Self::tagged(NATIVE, index as u64),
+            StackSlot::List(obj_ref) => Self::object(0, obj_ref, slot),
+            StackSlot::Map(obj_ref) => Self::object(1, obj_ref, slot),
+            StackSlot::Function(obj_ref) => Self::object(2, obj_ref, slot),
+            StackSlot::Struct(obj_ref) => Self::object(3, obj_ref, slot),
+            StackSlot::Class(obj_ref) => Self::object(4, obj_ref, slot),
+            StackSlot::Instance(obj_ref) => Self::object(5, obj_ref, slot),
+            StackSlot::BoundMethod(obj_ref) => Self::object(6, obj_ref, slot),
+            StackSlot::Variant(obj_ref) => Self::object(7, obj_ref, slot),
+            slot => Self::boxed(slot)
+        };
+    }
+
+    #[inline]
+    fn unpack(&self) -> StackSlot {
+        let payload = self.payload();
+        return match self.tag() {
+            None => StackSlot::Float(f64::from_bits(self.0)),
+            Some(NIL) => StackSlot::Nil,
+            Some(BOOL) => StackSlot::Bool(payload != 0),
+            Some(INT) => StackSlot::Int(((payload << 16) as i64) >> 16),
+            Some(CHAR) => StackSlot::Char(char::from_u32(payload as u32).unwrap()),
+            Some(NATIVE) => StackSlot::Native(payload as usize),
+            Some(OBJECT) => {
+                let obj_ref = ObjRef((payload & INDEX) as usize);
+                match payload >> KIND_SHIFT {
+                    0 => StackSlot::List(obj_ref),
+                    1 => StackSlot::Map(obj_ref),
+                    2 => StackSlot::Function(obj_ref),
+                    3 => StackSlot::Struct(obj_ref),
+                    4 => StackSlot::Class(obj_ref),
+                    5 => StackSlot::Instance(obj_ref),
+                    6 => StackSlot::BoundMethod(obj_ref),
+                    _ => StackSlot::Variant(obj_ref)
+                }
+            }
+            // The box stays alive for as long as `self` holds its count.
+            _ => unsafe { (*(payload as *const StackSlot)).clone() }
+        };
+    }
+
+    #[inline]
+    fn into_slot(self) -> StackSlot {
+        let Some(ptr) = self.boxed_ptr() else {
+            return self.unpack();
+        };
+        std::mem::forget(self);
+        // `self` gave up its count, which `boxed` now owns.
+        let boxed = unsafe { Rc::from_raw(ptr) };
+        return Rc::try_unwrap(boxed).unwrap_or_else(|boxed| (*boxed).clone());
+    }
+}
+
+impl Clone for NanBox {
+    #[inline]
+    fn clone(&self) -> Self {
+        if let Some(ptr) = self.boxed_ptr() {
+            // `ptr` came from `Rc::into_raw` and `self` keeps it alive.
+            unsafe { Rc::increment_strong_count(ptr) };
+        }
+        return NanBox(self.0);
+    }
+}
+
+impl Drop for NanBox {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(ptr) = self.boxed_ptr() {
+            // Releases the count owned by `self`.
+            unsafe { drop(Rc::from_raw(ptr)) };
+        }
+    }
+}
+
+impl fmt::Debug for NanBox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return fmt::Debug::fmt(&self.unpack(), f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(slot: StackSlot) -> StackSlot {
+        let packed = NanBox::pack(slot);
+        let copy = packed.clone();
+        let unpacked = packed.unpack();
+        assert_eq!(format!("{:?}", copy.into_slot()), format!("{:?}", unpacked));
+        return unpacked;
+    }
+
+    fn float_bits(slot: StackSlot) -> u64 {
+        match round_trip(slot) {
+            StackSlot::Float(val) => val.to_bits(),
+            slot => panic!("expected a float, got {:?}", slot)
+        }
+    }
+
+    #[test]
+    fn floats_keep_their_bits() {
+        for val in [0.0, -0.0, 1.5, f64::INFINITY, f64::NEG_INFINITY, f64::MIN_POSITIVE, f64::MAX, f64::MIN] {
+            assert_eq!(float_bits(StackSlot::Float(val)), val.to_bits());
+        }
+    }
+
+    #[test]
+    fn every_nan_becomes_canonical() {
+        for bits in [CANONICAL_NAN, f64::NAN.to_bits(), (-f64::NAN).to_bits(), TAGGED | INT << TAG_SHIFT | 7, 0x7FF0_0000_0000_0001] {
+            let val = f64::from_bits(bits);
+            assert!(val.is_nan());
+            assert_eq!(NanBox::pack(StackSlot::Float(val)).0, CANONICAL_NAN);
+            assert_eq!(float_bits(StackSlot::Float(val)), CANONICAL_NAN);
+        }
+    }
+
+    #[test]
+    fn ints_are_inline_up_to_48_bits_and_boxed_beyond() {
+        for val in [0, 1, -1, INT_MIN, INT_MAX] {
+            assert_eq!(NanBox::pack(StackSlot::Int(val)).tag(), Some(INT));
+            assert!(matches!(round_trip(StackSlot::Int(val)), StackSlot::Int(v) if v == val));
+        }
+        for val in [INT_MIN - 1, INT_MAX + 1, i64::MIN, i64::MAX] {
+            assert_eq!(NanBox::pack(StackSlot::Int(val)).tag(), Some(BOXED));
+            assert!(matches!(round_trip(StackSlot::Int(val)), StackSlot::Int(v) if v == val));
+        }
+    }
+
+    #[test]
+    fn other_scalars_round_trip() {
+        assert!(matches!(round_trip(StackSlot::Nil), StackSlot::Nil));
+        assert!(matches!(round_trip(StackSlot::Bool(true)), StackSlot::Bool(true)));
+        assert!(matches!(round_trip(StackSlot::Bool(false)), StackSlot::Bool(false)));
+        assert!(matches!(round_trip(StackSlot::Char('\u{10FFFF}')), StackSlot::Char('\u{10FFFF}')));
+        assert!(matches!(round_trip(StackSlot::Native(3)), StackSlot::Native(3)));
+        assert!(matches!(round_trip(StackSlot::from("text")), StackSlot::Str(val) if &*val == "text"));
+    }
+
+    #[test]
+    fn objects_keep_their_kind_and_index() {
+        for index in [0, 1, INDEX as usize] {
+            let obj_ref = ObjRef(index);
+            assert_eq!(NanBox::pack(StackSlot::List(obj_ref)).tag(), Some(OBJECT));
+            assert!(matches!(round_trip(StackSlot::List(obj_ref)), StackSlot::List(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::Map(obj_ref)), StackSlot::Map(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::Function(obj_ref)), StackSlot::Function(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::Struct(obj_ref)), StackSlot::Struct(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::Class(obj_ref)), StackSlot::Class(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::Instance(obj_ref)), StackSlot::Instance(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::BoundMethod(obj_ref)), StackSlot::BoundMethod(r) if r == obj_ref));
+            assert!(matches!(round_trip(StackSlot::Variant(obj_ref)), StackSlot::Variant(r) if r == obj_ref));
+        }
+        let obj_ref = ObjRef(INDEX as usize + 1);
+        assert_eq!(NanBox::pack(StackSlot::Map(obj_ref)).tag(), Some(BOXED));
+        assert!(matches!(round_trip(StackSlot::Map(obj_ref)), StackSlot::Map(r) if r == obj_ref));
+    }
+
+    #[test]
+    fn boxes_are_shared_and_released() {
+        let text: Rc<str> = Rc::from("shared");
+        let packed = NanBox::pack(StackSlot::Str(Rc::clone(&text)));
+        let copies: Vec<NanBox> = (0..3).map(|_| packed.clone()).collect();
+        assert_eq!(Rc::strong_count(&text), 2);
+        drop(copies);
+        let StackSlot::Str(unboxed) = packed.into_slot() else {
+            panic!("expected a str");
+        };
+        assert!(Rc::ptr_eq(&unboxed, &text));
+        drop(unboxed);
+        assert_eq!(Rc::strong_count(&text), 1);
+    }
+}
@@ -0,0 +1,41 @@
+use std::rc::Rc;
+
+use crate::compiler::error::CompileWarning;
+
+use super::{
+    chunk::Chunk,
+    enum_def::EnumDef,
+    function::FunctionProto,
+    interner::Interner,
+    struct_def::StructDef
+};
+
+/// Compiled bytecode ready to be executed by the `VM`. Chunk 0 is the
+/// top-level script. `warnings` holds the diagnostics the compiler reported
+/// without rejecting the program. `interner` holds the string constants
+/// of every chunk.
+#[derive(Clone)]
+pub struct Program {
+    pub chunks: Vec<Chunk>,
+    pub functions: Vec<FunctionProto>,
+    pub structs: Vec<StructDef>,
+    pub enums: Vec<EnumDef>,
+    pub warnings: Vec<CompileWarning>,
+    pub globals: usize,
+    pub interner: Rc<Interner>
+}
+
+impl Program {
+    /// The number of instructions in all chunks.
+    pub fn instruction_count(&self) -> usize {
+        let mut count = 0;
+        for chunk in &self.chunks {
+            let mut pos = 0;
+            while pos < chunk.bytecode.len() {
+                pos += chunk.instruction_len(pos, &self.functions);
+                count += 1;
+            }
+        }
+        return count;
+    }
+}
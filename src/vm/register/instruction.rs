@@ -0,0 +1,53 @@
+/// A register of the running function's frame.
+pub type Reg = u16;
+
+/// An instruction of the register machine. Operands name registers of the
+/// current frame instead of popping a stack: the destination comes first,
+/// followed by the sources. Constants, globals, functions and natives are
+/// referred to by index, jumps by instruction index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    LoadConst(Reg, u32),
+    Move(Reg, Reg),
+    LoadGlobal(Reg, u32),
+    StoreGlobal(u32, Reg),
+    Add(Reg, Reg, Reg),
+    Sub(Reg, Reg, Reg),
+    Mul(Reg, Reg, Reg),
+    Div(Reg, Reg, Reg),
+    FloorDiv(Reg, Reg, Reg),
+    Rem(Reg, Reg, Reg),
+    Eq(Reg, Reg, Reg),
+    NotEq(Reg, Reg, Reg),
+    Lt(Reg, Reg, Reg),
+    LtEq(Reg, Reg, Reg),
+    Gt(Reg, Reg, Reg),
+    GtEq(Reg, Reg, Reg),
+    Neg(Reg, Reg),
+    Not(Reg, Reg),
+    Jmp(u32),
+    JmpIfFalse(Reg, u32),
+    JmpIfTrue(Reg, u32),
+    /// Calls function `u32` with the `u8` arguments held in the registers
+    /// starting at the second `Reg`, storing its result in the first.
+    Call(Reg, u32, Reg, u8),
+    /// Like `Call`, for the native function `u32`.
+    CallNative(Reg, u32, Reg, u8),
+    Ret(Reg),
+    Print(Reg),
+    /// Builds a list from the `u32` registers starting at the second `Reg`.
+    MakeList(Reg, Reg, u32),
+    /// Builds a map from the `u32` key and value pairs in the registers
+    /// starting at the second `Reg`.
+    MakeMap(Reg, Reg, u32),
+    /// Joins the formatted values of the `u32` registers starting at the
+    /// second `Reg` into a string.
+    Concat(Reg, Reg, u32),
+    Index(Reg, Reg, Reg),
+    /// `collection[index] = value`, in that order.
+    StoreIndex(Reg, Reg, Reg),
+    /// Stores the next item of the iterable in the second `Reg` into the
+    /// first, or jumps to `u32` once it is exhausted. The cursor lives in
+    /// the register after the iterable.
+    ForIter(Reg, Reg, u32)
+}
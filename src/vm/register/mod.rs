@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+
+pub mod instruction;
+use instruction::{Instr, Reg};
+
+pub mod program;
+use program::RegisterProgram;
+
+use super::{
+    VM,
+    MAX_FRAMES,
+    error::RuntimeError,
+    map::Map,
+    stack_slot::StackSlot
+};
+
+/// The registers of every active frame, one window per frame, and where
+/// the running function is.
+struct Frames {
+    registers: Vec<StackSlot>,
+    stack: Vec<Frame>,
+    function: usize,
+    ip: usize,
+    base: usize
+}
+
+/// A suspended caller and the register its callee's result goes to.
+struct Frame {
+    function: usize,
+    ip: usize,
+    base: usize,
+    dst: Reg
+}
+
+impl VM {
+    /// Executes `program` on the register machine, returning the value of
+    /// a trailing expression statement. It shares the heap, globals and
+    /// natives of the stack machine. The collector only runs when a native
+    /// asks for it, and then sees the registers of every frame as roots.
+    pub fn run_registers(&mut self, program: &RegisterProgram) -> Result<StackSlot, RuntimeError> {
+        self.globals = vec![None; program.globals];
+        let mut frames = Frames {
+            registers: vec![StackSlot::Nil; program.functions[0].registers],
+            stack: Vec::new(),
+            function: 0,
+            ip: 0,
+            base: 0
+        };
+        let result = self.execute_registers(program, &mut frames).map_err(|err| {
+            let location = program.functions[frames.function].locations.get(frames.ip.wrapping_sub(1));
+            err.at(location.cloned())
+        })?;
+        self.keep_result(&result);
+        return Ok(result);
+    }
+
+    /// The register interpreter loop. `frames.ip` is left past the
+    /// instruction that failed when an error is returned.
+    fn execute_registers(&mut self, program: &RegisterProgram, frames: &mut Frames) -> Result<StackSlot, RuntimeError> {
+        let mut function = &program.functions[frames.function];
+        while let Some(&instr) = function.code.get(frames.ip) {
+            frames.ip += 1;
+            let base = frames.base;
+            let r = move |reg: Reg| base + reg as usize;
+            let regs = &mut frames.registers;
+            match instr {
+                Instr::LoadConst(dst, index) => regs[r(dst)] = function.constants[index as usize].clone(),
+                Instr::Move(dst, src) => regs[r(dst)] = regs[r(src)].clone(),
+                Instr::LoadGlobal(dst, index) => regs[r(dst)] = self.global(index as usize)?,
+                Instr::StoreGlobal(index, src) => self.set_global(index as usize, regs[r(src)].clone()),
+                Instr::Add(dst, a, b) => regs[r(dst)] = Self::add(regs[r(a)].clone(), regs[r(b)].clone())?,
+                Instr::Sub(dst, a, b) => regs[r(dst)] = Self::sub(regs[r(a)].clone(), regs[r(b)].clone())?,
+                Instr::Mul(dst, a, b) => regs[r(dst)] = Self::mul(regs[r(a)].clone(), regs[r(b)].clone())?,
+                Instr::Div(dst, a, b) => regs[r(dst)] = Self::div(regs[r(a)].clone(), regs[r(b)].clone())?,
+                Instr::FloorDiv(dst, a, b) => regs[r(dst)] = Self::floor_div(regs[r(a)].clone(), regs[r(b)].clone())?,
+                Instr::Rem(dst, a, b) => regs[r(dst)] = Self::rem(regs[r(a)].clone(), regs[r(b)].clone())?,
+                Instr::Eq(dst, a, b) => regs[r(dst)] = StackSlot::Bool(self.values_equal(&regs[r(a)], &regs[r(b)])),
+                Instr::NotEq(dst, a, b) => regs[r(dst)] = StackSlot::Bool(!self.values_equal(&regs[r(a)], &regs[r(b)])),
+                Instr::Lt(dst, a, b) => regs[r(dst)] = StackSlot::Bool(Self::compare_registers("<", &regs[r(a)], &regs[r(b)])?.is_some_and(Ordering::is_lt)),
+                Instr::LtEq(dst, a, b) => regs[r(dst)] = StackSlot::Bool(Self::compare_registers("<=", &regs[r(a)], &regs[r(b)])?.is_some_and(Ordering::is_le)),
+                Instr::Gt(dst, a, b) => regs[r(dst)] = StackSlot::Bool(Self::compare_registers(">", &regs[r(a)], &regs[r(b)])?.is_some_and(Ordering::is_gt)),
+                Instr::GtEq(dst, a, b) => regs[r(dst)] = StackSlot::Bool(Self::compare_registers(">=", &regs[r(a)], &regs[r(b)])?.is_some_and(Ordering::is_ge)),
+                Instr::Neg(dst, src) => regs[r(dst)] = Self::neg(regs[r(src)].clone())?,
+                Instr::Not(dst, src) => regs[r(dst)] = Self::not(regs[r(src)].clone())?,
+                Instr::Jmp(target) => frames.ip = target as usize,
+                Instr::JmpIfFalse(cond, target) => if !Self::condition(&regs[r(cond)])? {
+                    frames.ip = target as usize;
+                },
+                Instr::JmpIfTrue(cond, target) => if Self::condition(&regs[r(cond)])? {
+                    frames.ip = target as usize;
+                },
+                Instr::Call(dst, callee, first, argc) => {
+                    if frames.stack.len() >= MAX_FRAMES {
+                        return Err(RuntimeError::new("Stack overflow"));
+                    }
+                    let callee_base = base + function.registers;
+                    let args = r(first)..r(first) + argc as usize;
+                    regs.resize(callee_base + program.functions[callee as usize].registers, StackSlot::Nil);
+                    for (i, arg) in args.enumerate() {
+                        regs[callee_base + i] = regs[arg].clone();
+                    }
+                    frames.stack.push(Frame { function: frames.function, ip: frames.ip, base, dst });
+                    frames.function = callee as usize;
+                    frames.ip = 0;
+                    frames.base = callee_base;
+                    function = &program.functions[frames.function];
+                }
+                Instr::CallNative(dst, index, first, argc) => {
+                    let native = &self.natives[index as usize];
+                    native.check_arity(argc as usize)?;
+                    let function = native.function;
+                    let args = regs[r(first)..r(first) + argc as usize].to_vec();
+                    // lend the registers to the VM, where the collector finds them
+                    self.registers = std::mem::take(regs);
+                    let result = function(self, &args);
+                    frames.registers = std::mem::take(&mut self.registers);
+                    frames.registers[r(dst)] = result?;
+                }
+                Instr::Ret(src) => {
+                    let result = regs[r(src)].clone();
+                    let Some(caller) = frames.stack.pop() else {
+                        return Ok(result);
+                    };
+                    regs.truncate(base);
+                    regs[caller.base + caller.dst as usize] = result;
+                    frames.function = caller.function;
+                    frames.ip = caller.ip;
+                    frames.base = caller.base;
+                    function = &program.functions[frames.function];
+                }
+                Instr::Print(src) => println!("{}", self.format_value(&regs[r(src)])),
+                Instr::MakeList(dst, first, count) => {
+                    let items = regs[r(first)..r(first) + count as usize].to_vec();
+                    frames.registers[r(dst)] = self.alloc_list(items);
+                }
+                Instr::MakeMap(dst, first, count) => {
+                    let mut map = Map::new();
+                    for pair in regs[r(first)..r(first) + count as usize * 2].chunks(2) {
+                        map.insert(Self::map_key(&pair[0])?, pair[1].clone());
+                    }
+                    frames.registers[r(dst)] = self.alloc_map(map);
+                }
+                Instr::Concat(dst, first, count) => {
+                    let text: String = regs[r(first)..r(first) + count as usize].iter()
+                        .map(|part| self.format_value(part))
+                        .collect();
+                    regs[r(dst)] = StackSlot::from(text);
+                }
+                Instr::Index(dst, collection, index) => regs[r(dst)] = self.get_item(&regs[r(collection)], &regs[r(index)])?,
+                Instr::StoreIndex(collection, index, src) => {
+                    let val = regs[r(src)].clone();
+                    self.set_item(&regs[r(collection)], &regs[r(index)], val)?;
+                }
+                Instr::ForIter(dst, iterable, exit) => {
+                    let cursor = regs[r(iterable) + 1].as_i64().unwrap_or(0);
+                    match self.iterate(&regs[r(iterable)], cursor as usize)? {
+                        Some((item, next_cursor)) => {
+                            regs[r(iterable) + 1] = StackSlot::Int(next_cursor as i64);
+                            regs[r(dst)] = item;
+                        }
+                        None => frames.ip = exit as usize
+                    }
+                }
+            }
+        }
+        return Ok(StackSlot::Nil);
+    }
+
+    fn condition(cond: &StackSlot) -> Result<bool, RuntimeError> {
+        return cond.as_bool()
+            .ok_or_else(|| RuntimeError::new(format!("Condition must be a bool, got {}", cond.type_name())));
+    }
+
+    fn compare_registers(op: &str, lhs: &StackSlot, rhs: &StackSlot) -> Result<Option<Ordering>, RuntimeError> {
+        if !lhs.is_comparable(rhs) {
+            return Err(Self::operand_error(op, lhs, rhs));
+        }
+        return Ok(lhs.compare(rhs));
+    }
+}
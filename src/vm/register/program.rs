@@ -0,0 +1,34 @@
+use crate::{
+    compiler::location::Location,
+    vm::stack_slot::StackSlot
+};
+
+use super::instruction::Instr;
+
+/// A function compiled for the register machine. `locations` has the
+/// source position of every instruction in `code`. `registers` is the size
+/// of its frame; the parameters come first.
+#[derive(Debug, Clone)]
+pub struct RegisterFunction {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub registers: usize,
+    pub code: Vec<Instr>,
+    pub constants: Vec<StackSlot>,
+    pub locations: Vec<Location>
+}
+
+/// A program compiled for the register machine. Function 0 is the
+/// top-level script.
+#[derive(Debug, Clone)]
+pub struct RegisterProgram {
+    pub functions: Vec<RegisterFunction>,
+    pub globals: usize
+}
+
+impl RegisterProgram {
+    /// The number of instructions in all functions.
+    pub fn instruction_count(&self) -> usize {
+        return self.functions.iter().map(|function| function.code.len()).sum();
+    }
+}
@@ -1,9 +1,39 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    fmt,
+    rc::Rc
+};
+
+use super::{
+    bigint::BigInt,
+    error::RuntimeError,
+    heap::ObjRef
+};
+
 #[derive(Debug, Clone)]
 pub enum StackSlot {
+    Nil,
     Int(i64),
-    Float(f64)
+    BigInt(Rc<BigInt>),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Str(Rc<str>),
+    List(ObjRef),
+    Map(ObjRef),
+    Function(ObjRef),
+    Native(usize),
+    Struct(ObjRef),
+    Class(ObjRef),
+    Instance(ObjRef),
+    BoundMethod(ObjRef),
+    Variant(ObjRef),
+    Error(Rc<RuntimeError>)
 }
 
+pub type Value = StackSlot;
+
 impl StackSlot {
     pub fn as_i64(&self) -> Option<i64> {
         match self {
@@ -12,10 +42,187 @@ impl StackSlot {
         }
     }
 
+    /// The int a number equals: an int, a big int that fits in one, or a
+    /// float without a fractional part inside the int range.
+    pub fn as_integral(&self) -> Option<i64> {
+        match self {
+            StackSlot::Int(val) => Some(*val),
+            StackSlot::BigInt(val) => val.to_i64(),
+            StackSlot::Float(val) if val.fract() == 0.0 && *val >= i64::MIN as f64 && *val < i64::MAX as f64 => Some(*val as i64),
+            _ => None
+        }
+    }
+
+    /// The value as a big int, if it is an int or a big int.
+    pub fn as_bigint(&self) -> Option<Cow<'_, BigInt>> {
+        match self {
+            StackSlot::Int(val) => Some(Cow::Owned(BigInt::from_i64(*val))),
+            StackSlot::BigInt(val) => Some(Cow::Borrowed(val)),
+            _ => None
+        }
+    }
+
     pub fn as_f64(&self) -> Option<f64> {
         match self {
             StackSlot::Float(val) => Some(*val),
             _ => None
         }
     }
-}
\ No newline at end of file
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            StackSlot::Bool(val) => Some(*val),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            StackSlot::Str(val) => Some(val),
+            _ => None
+        }
+    }
+
+    /// The heap object this value refers to, if any.
+    pub fn obj_ref(&self) -> Option<ObjRef> {
+        match self {
+            StackSlot::List(obj_ref) | StackSlot::Map(obj_ref) | StackSlot::Function(obj_ref) |
+            StackSlot::Struct(obj_ref) | StackSlot::Class(obj_ref) | StackSlot::Instance(obj_ref) |
+            StackSlot::BoundMethod(obj_ref) | StackSlot::Variant(obj_ref) => Some(*obj_ref),
+            _ => None
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        return matches!(self, StackSlot::Nil);
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            StackSlot::Nil => "nil",
+            StackSlot::Int(_) => "int",
+            StackSlot::BigInt(_) => "bigint",
+            StackSlot::Float(_) => "float",
+            StackSlot::Bool(_) => "bool",
+            StackSlot::Char(_) => "char",
+            StackSlot::Str(_) => "str",
+            StackSlot::List(_) => "list",
+            StackSlot::Map(_) => "map",
+            StackSlot::Function(_) | StackSlot::Native(_) | StackSlot::BoundMethod(_) => "function",
+            StackSlot::Struct(_) => "struct",
+            StackSlot::Class(_) => "class",
+            StackSlot::Instance(_) => "object",
+            StackSlot::Variant(_) => "enum",
+            StackSlot::Error(_) => "error"
+        }
+    }
+
+    /// Language-level equality: ints, big ints and floats compare
+    /// numerically, values of unrelated types are never equal. Heap values
+    /// compare by identity here, `VM::values_equal` compares their contents.
+    pub fn equals(&self, other: &StackSlot) -> bool {
+        match (self, other) {
+            (StackSlot::Nil, StackSlot::Nil) => true,
+            (StackSlot::Int(a), StackSlot::Int(b)) => a == b,
+            (StackSlot::Float(a), StackSlot::Float(b)) => a == b,
+            (StackSlot::Int(a), StackSlot::Float(b)) => (*a as f64) == *b,
+            (StackSlot::Float(a), StackSlot::Int(b)) => *a == (*b as f64),
+            (StackSlot::Bool(a), StackSlot::Bool(b)) => a == b,
+            (StackSlot::Char(a), StackSlot::Char(b)) => a == b,
+            (StackSlot::Str(a), StackSlot::Str(b)) => a == b,
+            (StackSlot::List(a), StackSlot::List(b)) => a == b,
+            (StackSlot::Map(a), StackSlot::Map(b)) => a == b,
+            (StackSlot::Function(a), StackSlot::Function(b)) => a == b,
+            (StackSlot::Native(a), StackSlot::Native(b)) => a == b,
+            (StackSlot::Struct(a), StackSlot::Struct(b)) => a == b,
+            (StackSlot::Class(a), StackSlot::Class(b)) => a == b,
+            (StackSlot::Instance(a), StackSlot::Instance(b)) => a == b,
+            (StackSlot::BoundMethod(a), StackSlot::BoundMethod(b)) => a == b,
+            (StackSlot::Variant(a), StackSlot::Variant(b)) => a == b,
+            (StackSlot::Error(a), StackSlot::Error(b)) => Rc::ptr_eq(a, b),
+            (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) => self.compare(other) == Some(Ordering::Equal),
+            _ => false
+        }
+    }
+
+    /// Whether `<`, `<=`, `>` and `>=` apply to the values: two numbers,
+    /// two chars or two strs.
+    pub fn is_comparable(&self, other: &StackSlot) -> bool {
+        let is_number = |slot: &StackSlot| matches!(slot, StackSlot::Int(_) | StackSlot::BigInt(_) | StackSlot::Float(_));
+        (is_number(self) && is_number(other)) || matches!((self, other), (StackSlot::Char(_), StackSlot::Char(_)) | (StackSlot::Str(_), StackSlot::Str(_)))
+    }
+
+    /// Ordering used by `<`, `<=`, `>` and `>=`; `None` if the values are
+    /// not comparable, or are unordered because one of them is NaN.
+    pub fn compare(&self, other: &StackSlot) -> Option<Ordering> {
+        match (self, other) {
+            (StackSlot::Int(a), StackSlot::Int(b)) => Some(a.cmp(b)),
+            (StackSlot::Float(a), StackSlot::Float(b)) => a.partial_cmp(b),
+            (StackSlot::Int(a), StackSlot::Float(b)) => (*a as f64).partial_cmp(b),
+            (StackSlot::Float(a), StackSlot::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (StackSlot::Char(a), StackSlot::Char(b)) => Some(a.cmp(b)),
+            (StackSlot::Str(a), StackSlot::Str(b)) => Some(a.cmp(b)),
+            (StackSlot::BigInt(a), StackSlot::Float(b)) => a.to_f64().partial_cmp(b),
+            (StackSlot::Float(a), StackSlot::BigInt(b)) => a.partial_cmp(&b.to_f64()),
+            (StackSlot::BigInt(_), _) | (_, StackSlot::BigInt(_)) => match (self.as_bigint(), other.as_bigint()) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+impl From<BigInt> for StackSlot {
+    fn from(val: BigInt) -> Self {
+        StackSlot::BigInt(Rc::new(val))
+    }
+}
+
+impl From<&str> for StackSlot {
+    fn from(val: &str) -> Self {
+        StackSlot::Str(Rc::from(val))
+    }
+}
+
+impl From<String> for StackSlot {
+    fn from(val: String) -> Self {
+        StackSlot::Str(Rc::from(val))
+    }
+}
+
+impl fmt::Display for StackSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackSlot::Nil => write!(f, "nil"),
+            StackSlot::Int(val) => write!(f, "{}", val),
+            StackSlot::BigInt(val) => write!(f, "{}", val),
+            StackSlot::Float(val) => write!(f, "{:?}", val),
+            StackSlot::Bool(val) => write!(f, "{}", val),
+            StackSlot::Char(val) => write!(f, "{}", val),
+            StackSlot::Str(val) => write!(f, "{}", val),
+            StackSlot::List(_) => write!(f, "<list>"),
+            StackSlot::Map(_) => write!(f, "<map>"),
+            StackSlot::Function(_) | StackSlot::Native(_) => write!(f, "<fn>"),
+            StackSlot::Struct(_) => write!(f, "<struct>"),
+            StackSlot::Class(_) => write!(f, "<class>"),
+            StackSlot::Instance(_) => write!(f, "<object>"),
+            StackSlot::BoundMethod(_) => write!(f, "<fn>"),
+            StackSlot::Variant(_) => write!(f, "<enum>"),
+            StackSlot::Error(err) => match &err.location {
+                Some(loc) => write!(f, "<error at {}:{}: {}>", loc.line, loc.col, err.message),
+                None => write!(f, "<error: {}>", err.message)
+            }
+        }
+    }
+}
+
+/// `a // b` on ints: the quotient rounded towards negative infinity, or
+/// `None` when `b` is zero or the quotient overflows.
+pub fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let quotient = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        return Some(quotient - 1);
+    }
+    return Some(quotient);
+}
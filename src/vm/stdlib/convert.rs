@@ -0,0 +1,63 @@
+use super::type_error;
+use crate::vm::{
+    VM,
+    bigint::BigInt,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("int", 1, int);
+    vm.register_native("float", 1, float);
+    vm.register_native("bigint", 1, bigint);
+    vm.register_native("str", 1, str);
+}
+
+fn int(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(val) => Ok(Value::Int(*val)),
+        Value::BigInt(val) => val.to_i64()
+            .map(Value::Int)
+            .ok_or_else(|| RuntimeError::new(format!("Cannot convert {:?} to int", val))),
+        Value::Float(val) => {
+            let truncated = val.trunc();
+            if !truncated.is_finite() || truncated < i64::MIN as f64 || truncated >= i64::MAX as f64 {
+                return Err(RuntimeError::new(format!("Cannot convert {:?} to int", val)));
+            }
+            Ok(Value::Int(truncated as i64))
+        }
+        Value::Bool(val) => Ok(Value::Int(*val as i64)),
+        Value::Char(val) => Ok(Value::Int(*val as i64)),
+        Value::Str(val) => val.trim().parse::<i64>()
+            .map(Value::Int)
+            .map_err(|_| RuntimeError::new(format!("Cannot convert \"{}\" to int", val))),
+        val => Err(type_error("int", 0, "a number, bool, char or str", val))
+    }
+}
+
+fn float(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(val) => Ok(Value::Float(*val as f64)),
+        Value::BigInt(val) => Ok(Value::Float(val.to_f64())),
+        Value::Float(val) => Ok(Value::Float(*val)),
+        Value::Str(val) => val.trim().parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| RuntimeError::new(format!("Cannot convert \"{}\" to float", val))),
+        val => Err(type_error("float", 0, "a number or str", val))
+    }
+}
+
+fn bigint(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(val) => Ok(Value::from(BigInt::from_i64(*val))),
+        Value::BigInt(val) => Ok(Value::BigInt(val.clone())),
+        Value::Str(val) => BigInt::parse(val.trim())
+            .map(Value::from)
+            .ok_or_else(|| RuntimeError::new(format!("Cannot convert \"{}\" to bigint", val))),
+        val => Err(type_error("bigint", 0, "an int or str", val))
+    }
+}
+
+fn str(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return Ok(Value::from(vm.format_value(&args[0])));
+}
@@ -0,0 +1,39 @@
+use super::type_error;
+use crate::vm::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("type_of", 1, type_of);
+    vm.register_native("assert", 1, assert);
+    vm.register_native("len", 1, len);
+    vm.register_native("gc", 0, gc);
+}
+
+/// Forces a collection and returns the number of objects freed.
+fn gc(vm: &mut VM, _args: &[Value]) -> Result<Value, RuntimeError> {
+    return Ok(Value::Int(vm.collect_garbage() as i64));
+}
+
+fn len(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Str(val) => Ok(Value::Int(val.chars().count() as i64)),
+        Value::List(_) => Ok(Value::Int(vm.list(&args[0]).unwrap().len() as i64)),
+        Value::Map(_) => Ok(Value::Int(vm.map(&args[0]).unwrap().len() as i64)),
+        val => Err(type_error("len", 0, "a str, list or map", val))
+    }
+}
+
+fn type_of(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return Ok(Value::from(args[0].type_name()));
+}
+
+fn assert(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Bool(true) => Ok(Value::Nil),
+        Value::Bool(false) => Err(RuntimeError::new("Assertion failed")),
+        val => Err(type_error("assert", 0, "a bool", val))
+    }
+}
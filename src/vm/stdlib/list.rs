@@ -0,0 +1,28 @@
+use super::type_error;
+use crate::vm::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("push", 2, push);
+    vm.register_native("pop", 1, pop);
+}
+
+fn push(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match vm.list_mut(&args[0]) {
+        Some(items) => {
+            items.push(args[1].clone());
+            Ok(Value::Nil)
+        }
+        None => Err(type_error("push", 0, "a list", &args[0]))
+    }
+}
+
+fn pop(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match vm.list_mut(&args[0]) {
+        Some(items) => items.pop().ok_or_else(|| RuntimeError::new("`pop` from an empty list")),
+        None => Err(type_error("pop", 0, "a list", &args[0]))
+    }
+}
@@ -0,0 +1,47 @@
+use super::type_error;
+use crate::vm::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("keys", 1, keys);
+    vm.register_native("has", 2, has);
+    vm.register_native("get", 3, get);
+    vm.register_native("remove", 2, remove);
+}
+
+fn keys(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let keys = match vm.map(&args[0]) {
+        Some(map) => map.iter().map(|(key, _)| key.to_slot()).collect(),
+        None => return Err(type_error("keys", 0, "a map", &args[0]))
+    };
+    return Ok(vm.alloc_list(keys));
+}
+
+fn has(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let key = VM::map_key(&args[1])?;
+    match vm.map(&args[0]) {
+        Some(map) => Ok(Value::Bool(map.contains_key(&key))),
+        None => Err(type_error("has", 0, "a map", &args[0]))
+    }
+}
+
+/// `get(map, key, default)` returns `default` when `key` is missing.
+fn get(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let key = VM::map_key(&args[1])?;
+    match vm.map(&args[0]) {
+        Some(map) => Ok(map.get(&key).cloned().unwrap_or_else(|| args[2].clone())),
+        None => Err(type_error("get", 0, "a map", &args[0]))
+    }
+}
+
+fn remove(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let key = VM::map_key(&args[1])?;
+    let key_str = vm.format_key(&key);
+    match vm.map_mut(&args[0]) {
+        Some(map) => map.remove(&key).ok_or_else(|| RuntimeError::new(format!("Key {} not found in map", key_str))),
+        None => Err(type_error("remove", 0, "a map", &args[0]))
+    }
+}
@@ -0,0 +1,111 @@
+use super::{expect_int, expect_number, type_error};
+use crate::vm::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("sqrt", 1, sqrt);
+    vm.register_native("pow", 2, pow);
+    vm.register_native("floor", 1, floor);
+    vm.register_native("abs", 1, abs);
+    vm.register_native("min", 2, min);
+    vm.register_native("max", 2, max);
+    vm.register_native("wrapping_add", 2, wrapping_add);
+    vm.register_native("wrapping_sub", 2, wrapping_sub);
+    vm.register_native("wrapping_mul", 2, wrapping_mul);
+    vm.register_native("saturating_add", 2, saturating_add);
+    vm.register_native("saturating_sub", 2, saturating_sub);
+    vm.register_native("saturating_mul", 2, saturating_mul);
+}
+
+fn sqrt(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let val = expect_number("sqrt", args, 0)?;
+    if val < 0.0 {
+        return Err(RuntimeError::new("`sqrt` of a negative number"));
+    }
+    return Ok(Value::Float(val.sqrt()));
+}
+
+/// Integer base and non-negative integer exponent give an int, anything
+/// else is computed in floating point.
+fn pow(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    if let (Value::Int(base), Value::Int(exp)) = (&args[0], &args[1]) && *exp >= 0 {
+        let exp = u32::try_from(*exp).map_err(|_| RuntimeError::new("`pow` exponent is too large"))?;
+        return base.checked_pow(exp)
+            .map(Value::Int)
+            .ok_or_else(|| RuntimeError::new("Integer overflow in `pow`"));
+    }
+    let base = expect_number("pow", args, 0)?;
+    let exp = expect_number("pow", args, 1)?;
+    return Ok(Value::Float(base.powf(exp)));
+}
+
+fn floor(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(val) => Ok(Value::Int(*val)),
+        Value::Float(val) => Ok(Value::Float(val.floor())),
+        val => Err(type_error("floor", 0, "a number", val))
+    }
+}
+
+fn abs(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match &args[0] {
+        Value::Int(val) => val.checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| RuntimeError::new("Integer overflow in `abs`")),
+        Value::Float(val) => Ok(Value::Float(val.abs())),
+        val => Err(type_error("abs", 0, "a number", val))
+    }
+}
+
+fn min(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let lhs = expect_number("min", args, 0)?;
+    let rhs = expect_number("min", args, 1)?;
+    if let (Value::Int(a), Value::Int(b)) = (&args[0], &args[1]) {
+        return Ok(Value::Int(*a.min(b)));
+    }
+    return Ok(Value::Float(lhs.min(rhs)));
+}
+
+fn max(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let lhs = expect_number("max", args, 0)?;
+    let rhs = expect_number("max", args, 1)?;
+    if let (Value::Int(a), Value::Int(b)) = (&args[0], &args[1]) {
+        return Ok(Value::Int(*a.max(b)));
+    }
+    return Ok(Value::Float(lhs.max(rhs)));
+}
+
+/// The int operands of the wrapping and saturating builtins, which give
+/// the result of `op` on them where `+`, `-` and `*` would overflow.
+fn int_op(name: &str, args: &[Value], op: fn(i64, i64) -> i64) -> Result<Value, RuntimeError> {
+    let lhs = expect_int(name, args, 0)?;
+    let rhs = expect_int(name, args, 1)?;
+    return Ok(Value::Int(op(lhs, rhs)));
+}
+
+fn wrapping_add(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return int_op("wrapping_add", args, i64::wrapping_add);
+}
+
+fn wrapping_sub(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return int_op("wrapping_sub", args, i64::wrapping_sub);
+}
+
+fn wrapping_mul(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return int_op("wrapping_mul", args, i64::wrapping_mul);
+}
+
+fn saturating_add(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return int_op("saturating_add", args, i64::saturating_add);
+}
+
+fn saturating_sub(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return int_op("saturating_sub", args, i64::saturating_sub);
+}
+
+fn saturating_mul(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return int_op("saturating_mul", args, i64::saturating_mul);
+}
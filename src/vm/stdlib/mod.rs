@@ -0,0 +1,51 @@
+//! Built-in functions available to every script.
+
+mod core;
+mod math;
+mod string;
+mod list;
+mod map;
+mod convert;
+mod result;
+
+use super::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    core::register(vm);
+    math::register(vm);
+    string::register(vm);
+    list::register(vm);
+    map::register(vm);
+    convert::register(vm);
+    result::register(vm);
+}
+
+fn type_error(name: &str, index: usize, expected: &str, got: &Value) -> RuntimeError {
+    return RuntimeError::new(format!("`{}` expects argument {} to be {}, got {}", name, index + 1, expected, got.type_name()));
+}
+
+fn expect_int(name: &str, args: &[Value], index: usize) -> Result<i64, RuntimeError> {
+    match &args[index] {
+        Value::Int(val) => Ok(*val),
+        val => Err(type_error(name, index, "an int", val))
+    }
+}
+
+fn expect_number(name: &str, args: &[Value], index: usize) -> Result<f64, RuntimeError> {
+    match &args[index] {
+        Value::Int(val) => Ok(*val as f64),
+        Value::Float(val) => Ok(*val),
+        val => Err(type_error(name, index, "a number", val))
+    }
+}
+
+fn expect_str<'a>(name: &str, args: &'a [Value], index: usize) -> Result<&'a str, RuntimeError> {
+    match &args[index] {
+        Value::Str(val) => Ok(val),
+        val => Err(type_error(name, index, "a str", val))
+    }
+}
@@ -0,0 +1,39 @@
+use super::type_error;
+use crate::vm::{
+    VM,
+    error::RuntimeError,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("is_ok", 1, is_ok);
+    vm.register_native("unwrap_or", 2, unwrap_or);
+    vm.register_native("map_err", 2, map_err);
+}
+
+fn is_ok(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match vm.result(&args[0]) {
+        Some((is_ok, _)) => Ok(Value::Bool(is_ok)),
+        None => Err(type_error("is_ok", 0, "a Result", &args[0]))
+    }
+}
+
+/// The value of an `Ok`, or `args[1]` for an `Err`.
+fn unwrap_or(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    match vm.result(&args[0]) {
+        Some((true, val)) => Ok(val.clone()),
+        Some((false, _)) => Ok(args[1].clone()),
+        None => Err(type_error("unwrap_or", 0, "a Result", &args[0]))
+    }
+}
+
+/// Applies `args[1]` to the error of an `Err`; an `Ok` is returned as is.
+fn map_err(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let err = match vm.result(&args[0]) {
+        Some((true, _)) => return Ok(args[0].clone()),
+        Some((false, err)) => err.clone(),
+        None => return Err(type_error("map_err", 0, "a Result", &args[0]))
+    };
+    let mapped = vm.call(args[1].clone(), &[err])?;
+    return Ok(vm.alloc_result(false, mapped));
+}
@@ -0,0 +1,189 @@
+use super::{expect_int, expect_str};
+use crate::vm::{
+    VM,
+    error::RuntimeError,
+    native::VARIADIC,
+    stack_slot::Value
+};
+
+pub fn register(vm: &mut VM) {
+    vm.register_native("substr", 3, substr);
+    vm.register_native("split", 2, split);
+    vm.register_native("trim", 1, trim);
+    vm.register_native("to_upper", 1, to_upper);
+    vm.register_native("format", VARIADIC, format);
+}
+
+/// `substr(s, start, count)` takes `count` characters starting at the
+/// character index `start`.
+fn substr(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let val = expect_str("substr", args, 0)?;
+    let start = expect_int("substr", args, 1)?;
+    let count = expect_int("substr", args, 2)?;
+    let len = val.chars().count() as i64;
+    if start < 0 || count < 0 || start.checked_add(count).is_none_or(|end| end > len) {
+        return Err(RuntimeError::new(format!("`substr` range of length {} at {} is out of bounds for a str of length {}", count, start, len)));
+    }
+    let result: String = val.chars().skip(start as usize).take(count as usize).collect();
+    return Ok(Value::from(result));
+}
+
+fn split(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let val = expect_str("split", args, 0)?;
+    let sep = expect_str("split", args, 1)?;
+    if sep.is_empty() {
+        return Err(RuntimeError::new("`split` separator must not be empty"));
+    }
+    let parts = val.split(sep).map(Value::from).collect();
+    return Ok(vm.alloc_list(parts));
+}
+
+fn trim(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let val = expect_str("trim", args, 0)?;
+    return Ok(Value::from(val.trim()));
+}
+
+fn to_upper(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    let val = expect_str("to_upper", args, 0)?;
+    return Ok(Value::from(val.to_uppercase()));
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Right
+}
+
+/// A placeholder's `[[fill]align][0][width][.precision]` specification.
+/// `zero` pads numbers with zeros after their sign.
+struct FormatSpec {
+    fill: char,
+    align: Option<Align>,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>
+}
+
+/// `format(template, values...)` replaces each `{}` or `{:spec}` in
+/// `template` with the next value; `{{` and `}}` stand for braces.
+fn format(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    if args.is_empty() {
+        return Err(RuntimeError::new("`format` expects a format string"));
+    }
+    let template = expect_str("format", args, 0)?;
+    let values = &args[1..];
+    let mut out = String::new();
+    let mut used = 0;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                out.push('{');
+            }
+            '{' => {
+                let end = chars.as_str().find('}')
+                    .ok_or_else(|| RuntimeError::new("Unterminated placeholder in `format` string"))?;
+                let placeholder = &chars.as_str()[..end];
+                let spec = match placeholder.strip_prefix(':') {
+                    Some(spec) => parse_spec(spec)?,
+                    None if placeholder.is_empty() => parse_spec("")?,
+                    None => return Err(RuntimeError::new(format!("Invalid placeholder `{{{}}}` in `format` string", placeholder)))
+                };
+                let value = values.get(used)
+                    .ok_or_else(|| RuntimeError::new(format!("`format` string has more placeholders than the {} values given", values.len())))?;
+                out.push_str(&format_with(vm, value, &spec));
+                used += 1;
+                chars = chars.as_str()[end + 1..].chars();
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                out.push('}');
+            }
+            '}' => return Err(RuntimeError::new("Unmatched `}` in `format` string")),
+            c => out.push(c)
+        }
+    }
+    if used < values.len() {
+        return Err(RuntimeError::new(format!("`format` got {} values but its string has {} placeholders", values.len(), used)));
+    }
+    return Ok(Value::from(out));
+}
+
+/// The largest width or precision a `format` spec may ask for, so that a
+/// typo cannot request more padding than memory holds.
+const MAX_FORMAT_WIDTH: usize = 1 << 16;
+
+fn parse_spec(spec: &str) -> Result<FormatSpec, RuntimeError> {
+    let invalid = || RuntimeError::new(format!("Invalid format spec `{{:{}}}`", spec));
+    let align_of = |c: char| match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None
+    };
+    let mut rest = spec;
+    let mut fill = ' ';
+    let mut align = None;
+    let mut chars = spec.chars();
+    if let (Some(first), Some(second)) = (chars.next(), chars.next()) && align_of(second).is_some() {
+        fill = first;
+        align = align_of(second);
+        rest = &spec[first.len_utf8() + 1..];
+    }
+    else if let Some(first) = spec.chars().next() && align_of(first).is_some() {
+        align = align_of(first);
+        rest = &spec[1..];
+    }
+    let zero = align.is_none() && rest.len() > 1 && rest.starts_with('0');
+    if zero {
+        rest = &rest[1..];
+    }
+    let bounded = |name: &str, digits: &str| {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        return digits.parse::<usize>().ok().filter(|val| *val <= MAX_FORMAT_WIDTH)
+            .ok_or_else(|| RuntimeError::new(format!("`format` {} {} exceeds the maximum of {}", name, digits, MAX_FORMAT_WIDTH)));
+    };
+    let (width, precision) = match rest.split_once('.') {
+        Some((width, precision)) => (width, Some(bounded("precision", precision)?)),
+        None => (rest, None)
+    };
+    let width = match width {
+        "" => 0,
+        width => bounded("width", width)?
+    };
+    return Ok(FormatSpec { fill, align, zero, width, precision });
+}
+
+/// Numbers are right-aligned unless the spec says otherwise, other values
+/// left-aligned. A precision sets the decimals of a number and truncates
+/// any other value.
+fn format_with(vm: &VM, value: &Value, spec: &FormatSpec) -> String {
+    let is_number = matches!(value, Value::Int(_) | Value::Float(_));
+    let text = match (value, spec.precision) {
+        (Value::Int(val), Some(precision)) => format!("{:.*}", precision, *val as f64),
+        (Value::Float(val), Some(precision)) => format!("{:.*}", precision, val),
+        (value, Some(precision)) => vm.format_value(value).chars().take(precision).collect(),
+        (value, None) => vm.format_value(value)
+    };
+    let len = text.chars().count();
+    if len >= spec.width {
+        return text;
+    }
+    let padding = spec.width - len;
+    if spec.zero && is_number {
+        let (sign, digits) = text.split_at(text.starts_with('-') as usize);
+        return format!("{}{}{}", sign, "0".repeat(padding), digits);
+    }
+    let align = spec.align.unwrap_or(if is_number { Align::Right } else { Align::Left });
+    let (before, after) = match align {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0)
+    };
+    let fill = |count: usize| spec.fill.to_string().repeat(count);
+    return format!("{}{}{}", fill(before), text, fill(after));
+}
@@ -0,0 +1,13 @@
+/// A `struct` declaration. Instances store their field values in the
+/// order of `fields`, so a field is addressed by its offset.
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<String>
+}
+
+impl StructDef {
+    pub fn field_offset(&self, name: &str) -> Option<usize> {
+        return self.fields.iter().position(|field| field == name);
+    }
+}
@@ -0,0 +1,151 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::{Engine, Error};
+
+/// Evaluates `src` unoptimized, optimized and in strict mode, checks that
+/// they agree, and returns the debug form of the result or the error
+/// message.
+fn eval(src: &str) -> Result<String, String> {
+    let mut results = Vec::new();
+    for (strict, level) in [(false, 0), (false, 1), (false, 2), (true, 2)] {
+        let mut engine = Engine::new();
+        engine.set_strict(strict);
+        engine.set_opt_level(level);
+        results.push(match engine.eval(src) {
+            Ok(val) => Ok(format!("{:?}", val)),
+            Err(Error::Runtime(err)) => Err(err.message),
+            Err(err) => Err(err.to_string())
+        });
+    }
+    for result in &results[1..] {
+        assert_eq!(result, &results[0], "levels disagree on {:?}", src);
+    }
+    return results.remove(0);
+}
+
+fn int(val: i64) -> Result<String, String> {
+    return Ok(format!("Int({})", val));
+}
+
+fn float(val: f64) -> Result<String, String> {
+    return Ok(format!("Float({:?})", val));
+}
+
+fn error(message: &str) -> Result<String, String> {
+    return Err(String::from(message));
+}
+
+#[test]
+fn int_overflow_is_an_error() {
+    assert_eq!(eval("9223372036854775807 + 1;"), error("Integer overflow in `+`"));
+    assert_eq!(eval("var x = 9223372036854775807; x + 1;"), error("Integer overflow in `+`"));
+    assert_eq!(eval("var x = -9223372036854775807; x - 2;"), error("Integer overflow in `-`"));
+    assert_eq!(eval("var x = 4611686018427387904; x * 2;"), error("Integer overflow in `*`"));
+    assert_eq!(eval("var x = -9223372036854775807 - 1; -x;"), error("Integer overflow in `-`"));
+    assert_eq!(eval("var x = -9223372036854775807 - 1; x / -1;"), error("Integer overflow in `/`"));
+    assert_eq!(eval("var x = -9223372036854775807 - 1; x // -1;"), error("Integer overflow in `//`"));
+    assert_eq!(eval("var x = 9223372036854775806; x = x + 1; x;"), int(i64::MAX));
+}
+
+#[test]
+fn overflow_in_fused_additions() {
+    assert_eq!(eval("var x = 9223372036854775807; fn f() { x = x + 1; } f();"), error("Integer overflow in `+`"));
+    assert_eq!(eval("fn f(x) { return x + 1; } f(9223372036854775807);"), error("Integer overflow in `+`"));
+}
+
+#[test]
+fn int_division_by_zero_is_an_error() {
+    assert_eq!(eval("var x = 0; 1 / x;"), error("Division by zero"));
+    assert_eq!(eval("var x = 0; 1 // x;"), error("Division by zero"));
+    assert_eq!(eval("var x = 0; 1 % x;"), error("Division by zero"));
+}
+
+#[test]
+fn float_division_follows_ieee() {
+    assert_eq!(eval("1.0 / 0;"), float(f64::INFINITY));
+    assert_eq!(eval("-1 / 0.0;"), float(f64::NEG_INFINITY));
+    assert_eq!(eval("var x = 0.0; x / x == x / x;"), Ok(String::from("Bool(false)")));
+}
+
+#[test]
+fn comparisons_with_nan_are_false() {
+    let bool = |val: bool| Ok(format!("Bool({})", val));
+    assert_eq!(eval("var n = 0.0 / 0; n < 1;"), bool(false));
+    assert_eq!(eval("var n = 0.0 / 0; n >= n;"), bool(false));
+    assert_eq!(eval("var n = 0.0 / 0; 1 > n;"), bool(false));
+    assert_eq!(eval("var n = 0.0 / 0; 1n <= n;"), bool(false));
+    assert_eq!(eval("var n = 0.0 / 0; var r = 0; if (n < 1) { r = 1; } else { r = 2; } r;"), int(2));
+    assert_eq!(eval("var s = str(1); s < 1;"), error("Unsupported operand types for `<`: str and int"));
+}
+
+#[test]
+fn division_truncates_and_floor_division_floors() {
+    assert_eq!(eval("7 / 2;"), int(3));
+    assert_eq!(eval("-7 / 2;"), int(-3));
+    assert_eq!(eval("7 // 2;"), int(3));
+    assert_eq!(eval("-7 // 2;"), int(-4));
+    assert_eq!(eval("7 // -2;"), int(-4));
+    assert_eq!(eval("-8 // 2;"), int(-4));
+    assert_eq!(eval("7.0 / 2;"), float(3.5));
+    assert_eq!(eval("7.0 // 2;"), float(3.0));
+    assert_eq!(eval("-7.5 // 2;"), float(-4.0));
+}
+
+#[test]
+fn remainder_has_the_sign_of_the_dividend() {
+    assert_eq!(eval("7 % 3;"), int(1));
+    assert_eq!(eval("-7 % 3;"), int(-1));
+    assert_eq!(eval("7 % -3;"), int(1));
+    assert_eq!(eval("-7.5 % 2;"), float(-1.5));
+    assert_eq!(eval("var x = -9223372036854775807 - 1; x % -1;"), int(0));
+}
+
+#[test]
+fn wrapping_and_saturating_builtins() {
+    assert_eq!(eval("wrapping_add(9223372036854775807, 1);"), int(i64::MIN));
+    assert_eq!(eval("wrapping_sub(-9223372036854775807, 2);"), int(i64::MAX));
+    assert_eq!(eval("wrapping_mul(4611686018427387904, 4);"), int(0));
+    assert_eq!(eval("saturating_add(9223372036854775807, 1);"), int(i64::MAX));
+    assert_eq!(eval("saturating_sub(-9223372036854775807, 2);"), int(i64::MIN));
+    assert_eq!(eval("saturating_mul(-4611686018427387904, 4);"), int(i64::MIN));
+    assert_eq!(eval("saturating_add(1, 2);"), int(3));
+    assert_eq!(eval("wrapping_add(1, 2.0);"), error("`wrapping_add` expects argument 2 to be an int, got float"));
+}
+
+fn big(val: &str) -> Result<String, String> {
+    return Ok(format!("BigInt({}n)", val));
+}
+
+#[test]
+fn big_int_arithmetic_does_not_overflow() {
+    assert_eq!(eval("9223372036854775807n + 1;"), big("9223372036854775808"));
+    assert_eq!(eval("var x = 1n; var i = 1; while (i <= 25) { x = x * i; i = i + 1; } x;"), big("15511210043330985984000000"));
+    assert_eq!(eval("123456789012345678901234567890n * -987654321098765432109876543210n;"),
+        big("-121932631137021795226185032733622923332237463801111263526900"));
+    assert_eq!(eval("var x = 100000000000000000000n; x - 100000000000000000001n;"), big("-1"));
+    assert_eq!(eval("var x = -9223372036854775807n - 1; -x;"), big("9223372036854775808"));
+    assert_eq!(eval("5n - 5;"), big("0"));
+}
+
+#[test]
+fn big_int_division_rounds_like_int_division() {
+    assert_eq!(eval("-7n / 2;"), big("-3"));
+    assert_eq!(eval("-7n // 2;"), big("-4"));
+    assert_eq!(eval("7 // -2n;"), big("-4"));
+    assert_eq!(eval("-7n % 3;"), big("-1"));
+    assert_eq!(eval("var x = 1000000000000000000000000n; x / 7000000000000n;"), big("142857142857"));
+    assert_eq!(eval("var x = 1000000000000000000000000n; x % 7000000000000n;"), big("1000000000000"));
+    assert_eq!(eval("var x = 0; 1n / x;"), error("Division by zero"));
+    assert_eq!(eval("var x = 0n; 1 % x;"), error("Division by zero"));
+}
+
+#[test]
+fn big_ints_compare_and_convert() {
+    assert_eq!(eval("1n == 1;"), Ok(String::from("Bool(true)")));
+    assert_eq!(eval("var x = 10000000000000000000n; x > 9223372036854775807;"), Ok(String::from("Bool(true)")));
+    assert_eq!(eval("var x = -2n; x < -1.5;"), Ok(String::from("Bool(true)")));
+    assert_eq!(eval("str(-12345678901234567890n);"), Ok(String::from("Str(\"-12345678901234567890\")")));
+    assert_eq!(eval("bigint(\"123456789012345678901\") + 1;"), big("123456789012345678902"));
+    assert_eq!(eval("int(42n);"), int(42));
+    assert_eq!(eval("int(9223372036854775808n);"), error("Cannot convert 9223372036854775808n to int"));
+}
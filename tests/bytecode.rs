@@ -0,0 +1,158 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+mod common;
+
+use common::at_every_level;
+
+/// Evaluates `src` at every optimization level, checks that they agree
+/// and returns the printed form of the result.
+fn eval(src: &str) -> String {
+    return at_every_level(|engine| engine.eval(src).unwrap().to_string());
+}
+
+/// The number of instructions with `prefixes` `Wide` prefixes in the
+/// unoptimized listing of `src`.
+fn wide_instructions(src: &str, prefixes: usize) -> usize {
+    let mut engine = Engine::new();
+    engine.set_opt_level(0);
+    let listing = engine.compile(src).unwrap().disassemble();
+    let prefix = format!("  {}", "Wide ".repeat(prefixes));
+    return listing.lines()
+        .filter(|line| line.contains(&prefix) && !line.contains(&format!("{}Wide", prefix)))
+        .count();
+}
+
+/// `count` statements adding distinct constants to `total`.
+fn additions(count: usize) -> String {
+    return (0..count).map(|i| format!("total = total + {};\n", 1000 + i)).collect();
+}
+
+#[test]
+fn jumps_are_narrowed_to_one_byte() {
+    let src = "var i = 0; var n = 0; while (i < 10) { if (i % 2 == 0) { n = n + i; } i = i + 1; } n;";
+    assert_eq!(wide_instructions(src, 1), 0);
+    assert_eq!(eval(src), "20");
+}
+
+#[test]
+fn more_than_255_constants_use_wide_operands() {
+    let src = format!("var total = 0;\n{}total;", additions(300));
+    assert!(wide_instructions(&src, 1) > 0);
+    let expected: usize = (1000..1300).sum();
+    assert_eq!(eval(&src), expected.to_string());
+}
+
+#[test]
+fn more_than_255_locals_use_wide_operands() {
+    let vars: String = (0..300).map(|i| format!("var v{} = {};\n", i, i)).collect();
+    let src = format!("fn f() {{\n{}return v0 + v150 + v299;\n}}\nf();", vars);
+    assert!(wide_instructions(&src, 1) > 0);
+    assert_eq!(eval(&src), "449");
+}
+
+#[test]
+fn more_than_255_upvalues_use_wide_operands() {
+    let vars: String = (0..300).map(|i| format!("var v{} = {};\n", i, i)).collect();
+    let sum: String = (0..300).map(|i| format!("sum = sum + v{};\n", i)).collect();
+    let src = format!("fn outer() {{\n{}fn inner() {{\nvar sum = 0;\n{}return sum;\n}}\nreturn inner;\n}}\nouter()();", vars, sum);
+    assert!(wide_instructions(&src, 1) > 0);
+    assert_eq!(eval(&src), (0..300).sum::<i32>().to_string());
+}
+
+#[test]
+fn jumps_over_more_than_65535_bytes() {
+    let src = format!(r#"
+        var total = 0;
+        var rounds = 0;
+        while (rounds < 3) {{
+            if (rounds == 1) {{
+                {}
+            }}
+            else {{
+                total = total + 1;
+            }}
+            rounds = rounds + 1;
+        }}
+        total;
+    "#, additions(20000));
+    assert!(wide_instructions(&src, 2) > 0);
+    let expected: usize = (1000..21000).sum::<usize>() + 2;
+    assert_eq!(eval(&src), expected.to_string());
+}
+
+#[test]
+fn short_jumps_across_wide_instructions() {
+    let src = format!(r#"
+        var total = 0;
+        var i = 0;
+        while (i < 2) {{
+            if (i == 0) {{ total = total + 5; }}
+            {}
+            i = i + 1;
+        }}
+        total;
+    "#, additions(300));
+    let expected: usize = (1000..1300).sum::<usize>() * 2 + 5;
+    assert_eq!(eval(&src), expected.to_string());
+}
+
+#[test]
+fn jump_table_with_wide_constants_and_long_arms() {
+    let arm = |value: usize, body: &str| format!("{} => {{ {} total = total + {}; }},\n", value, body, value);
+    let src = format!(r#"
+        var total = 0;
+        fn pick(n) {{
+            return match (n) {{
+                {}{}{}{}_ => {{ total = total - 1; }}
+            }};
+        }}
+        var i = 0;
+        while (i < 6) {{ pick(i); i = i + 1; }}
+        total;
+    "#, arm(1, ""), arm(2, &additions(20000)), arm(3, ""), arm(4, ""));
+    let listing = {
+        let mut engine = Engine::new();
+        engine.set_opt_level(0);
+        engine.compile(&src).unwrap().disassemble()
+    };
+    assert!(listing.contains("JmpTable"));
+    let arm_two: usize = (1000..21000).sum();
+    let expected = 1 + (arm_two + 2) + 3 + 4 - 2;
+    assert_eq!(eval(&src), expected.to_string());
+}
+
+/// The constants of the top-level chunk of `src` compiled at -O1.
+fn script_constants(src: &str) -> Vec<String> {
+    let mut engine = Engine::new();
+    engine.set_opt_level(1);
+    let listing = engine.compile(src).unwrap().disassemble();
+    return listing.split("== ").nth(1).unwrap().lines()
+        .filter_map(|line| line.trim_start().strip_prefix('['))
+        .map(|line| line.split_once("] ").unwrap().1.to_string())
+        .collect();
+}
+
+#[test]
+fn repeated_literals_share_a_constant() {
+    let src = r#"[1, 1, 2.5, 2.5, "s", "s", nil, nil, true, true, 'c', 'c', 10000000000000000000000n, 10000000000000000000000n];"#;
+    assert_eq!(script_constants(src), [
+        "Int(1)", "Float(2.5)", "Str(\"s\")", "Nil", "Bool(true)", "Char('c')", "BigInt(10000000000000000000000n)"
+    ]);
+}
+
+#[test]
+fn numerically_equal_literals_keep_their_own_constants() {
+    let src = "[0.0, -0.0, 1, 1.0, -0.0, 1];";
+    assert_eq!(script_constants(src), ["Float(0.0)", "Float(-0.0)", "Int(1)", "Float(1.0)"]);
+    assert_eq!(eval("str([0.0, -0.0, 1, 1.0, -0.0, 1]);"), "[0.0, -0.0, 1, 1.0, -0.0, 1]");
+}
+
+#[test]
+fn strings_are_interned_across_chunks() {
+    let src = r#"fn f() { return "shared"; } fn g() { return "shared" + "!"; } str([f(), "shared", g()]);"#;
+    let listing = Engine::new().compile(src).unwrap().disassemble();
+    assert!(listing.starts_with("3 chunks, 3 constants, 2 interned strings"), "{}", listing);
+    assert_eq!(eval(src), "[\"shared\", \"shared\", \"shared!\"]");
+}
@@ -0,0 +1,116 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{at_every_level, outcome};
+
+/// Evaluates `src` at every optimization level and returns the printed
+/// form of the result or the error message.
+fn eval(src: &str) -> Result<String, String> {
+    return at_every_level(|engine| outcome(engine.eval(src)));
+}
+
+fn ok(val: &str) -> Result<String, String> {
+    return Ok(String::from(val));
+}
+
+fn error(message: &str) -> Result<String, String> {
+    return Err(String::from(message));
+}
+
+/// Three levels of classes: `C` overrides `who` again and reaches both of
+/// its ancestors through `super`.
+const HIERARCHY: &str = r#"
+    class A {
+        fn init(name) { self.name = name; }
+        fn hi() { return "hi from " + self.name; }
+        fn who() { return "A"; }
+        fn call_who() { return self.who(); }
+    }
+    class B : A {
+        fn who() { return "B"; }
+        fn parent() { return super.who(); }
+    }
+    class C : B {
+        fn init() { super.init("c"); }
+        fn who() { return "C"; }
+        fn up() { return super.who() + super.parent(); }
+    }
+"#;
+
+#[test]
+fn fields_are_set_by_the_initialiser_and_assigned_later() {
+    let src = r#"
+        class Counter {
+            fn init(start) { self.count = start; }
+            fn increment() { self.count = self.count + 1; return self; }
+        }
+        var counter = Counter(5);
+        counter.increment().increment();
+        counter.extra = "added";
+        str(counter.count) + " " + counter.extra;
+    "#;
+    assert_eq!(eval(src), ok("7 added"));
+    assert_eq!(eval("class X { fn init(a) { self.a = a; } } X();"), error("`init` expects 1 arguments, got 0"));
+    assert_eq!(eval("class X {} X().nope;"), error("`X` object has no field `nope`"));
+    assert_eq!(eval("class X {} X().nope();"), error("`X` object has no method `nope`"));
+}
+
+#[test]
+fn methods_are_bound_to_their_receiver() {
+    let src = r#"
+        class Greeter {
+            fn init(name) { self.name = name; }
+            fn greet() { return "hello " + self.name; }
+        }
+        var ada = Greeter("ada");
+        var bound = ada.greet;
+        ada.name = "grace";
+        var other = Greeter("alan").greet;
+        bound() + ", " + other();
+    "#;
+    assert_eq!(eval(src), ok("hello grace, hello alan"));
+}
+
+#[test]
+fn fields_holding_functions_are_called_like_methods() {
+    let src = r#"
+        class Button {
+            fn init(label) { self.on_click = fn() { return "clicked " + label; }; }
+        }
+        Button("ok").on_click();
+    "#;
+    assert_eq!(eval(src), ok("clicked ok"));
+}
+
+#[test]
+fn inherited_methods_dispatch_on_the_receiver() {
+    let src = format!("{} var c = C(); str([c.hi(), c.call_who(), A(\"a\").call_who(), B(\"b\").call_who()]);", HIERARCHY);
+    assert_eq!(eval(&src), ok("[\"hi from c\", \"C\", \"A\", \"B\"]"));
+}
+
+#[test]
+fn super_calls_the_superclass_method() {
+    let src = format!("{} var c = C(); var up = c.up; str([up(), B(\"b\").parent()]);", HIERARCHY);
+    assert_eq!(eval(&src), ok("[\"BA\", \"A\"]"));
+    assert_eq!(eval("class X { fn f() { return super.f(); } }"), error("Error at 1:27: `super` can only be used inside methods of a subclass"));
+}
+
+#[test]
+fn methods_are_inherited_from_the_superclass_at_declaration() {
+    let src = r#"
+        class A { fn hi() { return "A"; } }
+        class Z { fn hi() { return "Z"; } }
+        var Base = A;
+        class B : Base { fn call() { return super.hi(); } }
+        Base = Z;
+        B().hi() + B().call();
+    "#;
+    assert_eq!(eval(src), ok("AA"));
+}
+
+#[test]
+fn superclasses_must_be_classes() {
+    assert_eq!(eval("var NotClass = 1; class D : NotClass {}"), error("Superclass must be a class, got int"));
+    assert_eq!(eval("class X : X {}"), error("Error at 1:11: A class cannot inherit from itself"));
+}
@@ -0,0 +1,36 @@
+#![allow(clippy::needless_return, dead_code)]
+
+use std::fmt::Debug;
+
+use cherry_script::{Engine, Error, RuntimeError, VM, Value};
+
+/// Calls `args[0]` without arguments, returning its result or the message
+/// of the error it raised.
+pub fn safe_call(vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return match vm.call(args[0].clone(), &[]) {
+        Ok(val) => Ok(val),
+        Err(err) => Ok(Value::from(err.message))
+    };
+}
+
+/// Runs `run` on a fresh engine at every optimization level, checks that
+/// the levels agree and returns their result.
+pub fn at_every_level<T: PartialEq + Debug>(mut run: impl FnMut(&mut Engine) -> T) -> T {
+    let mut results = Vec::new();
+    for level in 0..=2 {
+        let mut engine = Engine::new();
+        engine.set_opt_level(level);
+        results.push(run(&mut engine));
+    }
+    assert!(results.iter().all(|result| *result == results[0]), "levels disagree: {:?}", results);
+    return results.remove(0);
+}
+
+/// The printed form of a result, or the message of its error.
+pub fn outcome(result: Result<Value, Error>) -> Result<String, String> {
+    return match result {
+        Ok(val) => Ok(val.to_string()),
+        Err(Error::Runtime(err)) => Err(err.message),
+        Err(err) => Err(err.to_string())
+    };
+}
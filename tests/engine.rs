@@ -0,0 +1,81 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+/// Allocates enough garbage to force collections under a threshold of 1.
+const CHURN: &str = r#"
+    var i = 0;
+    while (i < 50) { var xs = [i, [i]]; i = i + 1; }
+    i;
+"#;
+
+#[test]
+fn list_results_survive_collections() {
+    let mut engine = Engine::new();
+    engine.set_gc_threshold(1);
+    let list = engine.eval("var i = 0; while (i < 50) { var xs = [i]; i = i + 1; } [1, \"two\", [3]];").unwrap();
+    assert!(engine.gc_stats().collections > 0);
+    engine.collect_garbage();
+    let items = engine.list(&list).unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].to_string(), "1");
+    assert_eq!(engine.format_value(&items[2]).unwrap(), "[3]");
+    assert_eq!(engine.format_value(&list).unwrap(), "[1, \"two\", [3]]");
+}
+
+#[test]
+fn map_results_survive_collections() {
+    let mut engine = Engine::new();
+    let map = engine.eval("var m = { name: \"cherry\", tags: [\"red\"] }; m;").unwrap();
+    engine.collect_garbage();
+    let entries = engine.map(&map).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(engine.format_value(&map).unwrap(), "{\"name\": \"cherry\", \"tags\": [\"red\"]}");
+    assert!(engine.list(&map).is_none());
+}
+
+#[test]
+fn register_results_survive_collections() {
+    let mut engine = Engine::new();
+    let program = engine.compile_registers("var xs = [1, 2]; push(xs, 3); xs;").unwrap();
+    let list = engine.run_registers(&program).unwrap();
+    engine.collect_garbage();
+    assert_eq!(engine.format_value(&list).unwrap(), "[1, 2, 3]");
+}
+
+#[test]
+fn earlier_results_are_collected_and_rejected() {
+    let mut engine = Engine::new();
+    engine.set_gc_threshold(1);
+    let list = engine.eval("[[1], [2], [3]];").unwrap();
+    let map = engine.eval("var m = { a: [1] }; m;").unwrap();
+    engine.eval(CHURN).unwrap();
+    engine.collect_garbage();
+    assert!(engine.list(&list).is_none());
+    assert!(engine.map(&map).is_none());
+    assert!(engine.format_value(&list).is_none());
+    // The freed slots are reused by new objects, which stale handles must
+    // not reach.
+    let latest = engine.eval("[[4], [5], [6]];").unwrap();
+    assert!(engine.list(&list).is_none());
+    assert!(engine.format_value(&map).is_none());
+    assert_eq!(engine.format_value(&latest).unwrap(), "[[4], [5], [6]]");
+    let scalar = engine.eval(CHURN).unwrap();
+    engine.collect_garbage();
+    assert_eq!(engine.format_value(&scalar).unwrap(), "50");
+    assert!(engine.list(&latest).is_none());
+}
+
+#[test]
+fn retention_is_bounded_by_the_latest_result() {
+    let mut engine = Engine::new();
+    engine.set_gc_threshold(1_000_000);
+    engine.eval("[[1], [2], [3]];").unwrap();
+    engine.collect_garbage();
+    let kept = engine.gc_stats().live_objects;
+    for _ in 0..100 {
+        engine.eval("[[1], [2], [3]];").unwrap();
+    }
+    engine.collect_garbage();
+    assert_eq!(engine.gc_stats().live_objects, kept);
+}
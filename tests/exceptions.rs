@@ -0,0 +1,142 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{at_every_level, outcome, safe_call};
+
+/// Evaluates `src` at every optimization level with `safe_call` registered
+/// and returns the printed form of the result or the error message.
+fn eval(src: &str) -> Result<String, String> {
+    return at_every_level(|engine| {
+        engine.register_fn("safe_call", 1, safe_call);
+        outcome(engine.eval(src))
+    });
+}
+
+fn ok(val: &str) -> Result<String, String> {
+    return Ok(String::from(val));
+}
+
+#[test]
+fn exceptions_unwind_across_frames() {
+    let src = r#"
+        fn dive(n) {
+            var here = [n];
+            if (n == 0) { throw "bottom"; }
+            return dive(n - 1) + here[0];
+        }
+        var kept = "kept";
+        var message = "";
+        try { dive(50); } catch (e) { message = e; }
+        message + " " + kept;
+    "#;
+    assert_eq!(eval(src), ok("bottom kept"));
+}
+
+#[test]
+fn unwinding_discards_the_frames_it_leaves() {
+    let src = r#"
+        fn dive(n) { if (n == 0) { return 1 / 0; } return dive(n - 1); }
+        var caught = 0;
+        var i = 0;
+        while (i < 2000) {
+            try { dive(5); } catch (e) { caught = caught + 1; }
+            i = i + 1;
+        }
+        caught;
+    "#;
+    assert_eq!(eval(src), ok("2000"));
+}
+
+#[test]
+fn runtime_errors_carry_their_location_across_frames() {
+    let src = "fn f() {\n  return [1][5];\n}\nvar where = [];\ntry { f(); } catch (e) { where = [e.line, e.col]; }\nstr(where);";
+    let result = eval(src).unwrap();
+    assert!(result.starts_with("[2, "), "{}", result);
+}
+
+#[test]
+fn finally_runs_when_a_frame_is_unwound() {
+    let src = r#"
+        var log = [];
+        fn inner() { try { throw "boom"; } finally { push(log, "inner finally"); } }
+        fn outer() { try { inner(); } finally { push(log, "outer finally"); } }
+        try { outer(); } catch (e) { push(log, "caught " + e); }
+        str(log);
+    "#;
+    assert_eq!(eval(src), ok("[\"inner finally\", \"outer finally\", \"caught boom\"]"));
+}
+
+#[test]
+fn throw_from_finally_replaces_the_pending_exception() {
+    let src = r#"
+        var log = [];
+        fn f() {
+            try { throw "first"; } finally { push(log, "finally"); throw "second"; }
+        }
+        try { f(); } catch (e) { push(log, "caught " + e); }
+        str(log);
+    "#;
+    assert_eq!(eval(src), ok("[\"finally\", \"caught second\"]"));
+}
+
+#[test]
+fn rethrow_from_catch_runs_finally_first() {
+    let src = r#"
+        var log = [];
+        fn f() {
+            try { throw {"code": 7}; }
+            catch (e) { push(log, "catch"); throw e; }
+            finally { push(log, "finally"); }
+        }
+        try { f(); } catch (e) { push(log, "outer " + str(e["code"])); }
+        str(log);
+    "#;
+    assert_eq!(eval(src), ok("[\"catch\", \"finally\", \"outer 7\"]"));
+}
+
+#[test]
+fn uncaught_exception_from_finally_stops_the_program() {
+    let src = "try { print 1; } finally { throw \"late\"; }";
+    assert_eq!(eval(src), Err(String::from("Uncaught exception: late")));
+}
+
+#[test]
+fn handlers_outside_a_native_call_do_not_catch_inside_it() {
+    let src = r#"
+        var log = [];
+        try {
+            var result = safe_call(fn() { throw "inside"; });
+            push(log, result);
+        } catch (e) {
+            push(log, "outer caught " + e);
+        }
+        str(log);
+    "#;
+    assert_eq!(eval(src), ok("[\"Uncaught exception: inside\"]"));
+}
+
+#[test]
+fn handlers_inside_a_native_call_still_catch() {
+    let src = r#"
+        var result = safe_call(fn() {
+            try { throw "inside"; } catch (e) { return "callee caught " + e; }
+        });
+        result;
+    "#;
+    assert_eq!(eval(src), ok("callee caught inside"));
+}
+
+#[test]
+fn errors_propagated_by_a_native_reach_script_handlers() {
+    let src = r#"
+        var caught = "";
+        try {
+            map_err(Err(1), fn(e) { throw "from callback"; });
+        } catch (e) {
+            caught = e;
+        }
+        caught;
+    "#;
+    assert_eq!(eval(src), ok("from callback"));
+}
@@ -0,0 +1,69 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+mod common;
+
+use common::{at_every_level, outcome};
+
+/// Evaluates `src` at every optimization level, with and without strict
+/// mode, and returns the printed form of the result or the error message.
+fn eval(src: &str) -> Result<String, String> {
+    let loose = at_every_level(|engine| outcome(engine.eval(src)));
+    let strict = at_every_level(|engine| {
+        engine.set_strict(true);
+        outcome(engine.eval(src))
+    });
+    assert_eq!(loose, strict, "strict mode disagrees on {:?}", src);
+    return loose;
+}
+
+/// Runs `src` on the register machine.
+fn eval_registers(src: &str) -> Result<String, String> {
+    let mut engine = Engine::new();
+    let program = engine.compile_registers(src).map_err(|err| err.to_string())?;
+    return engine.run_registers(&program).map(|val| val.to_string()).map_err(|err| err.message);
+}
+
+const FORWARD: &str = "fn a() { return b(); } fn b() { return 1; } a();";
+
+const MUTUAL: &str = r#"
+    fn is_even(n) { if (n == 0) { return true; } return is_odd(n - 1); }
+    fn is_odd(n) { if (n == 0) { return false; } return is_even(n - 1); }
+    str([is_even(10), is_odd(7), is_even(3)]);
+"#;
+
+#[test]
+fn functions_can_call_functions_declared_later() {
+    assert_eq!(eval(FORWARD), Ok(String::from("1")));
+    assert_eq!(eval_registers(FORWARD), Ok(String::from("1")));
+}
+
+#[test]
+fn functions_can_be_mutually_recursive() {
+    assert_eq!(eval(MUTUAL), Ok(String::from("[true, true, false]")));
+    let src = r#"
+        fn is_even(n) { if (n == 0) { return 1; } return is_odd(n - 1); }
+        fn is_odd(n) { if (n == 0) { return 0; } return is_even(n - 1); }
+        is_even(10) + is_odd(7);
+    "#;
+    assert_eq!(eval_registers(src), Ok(String::from("2")));
+}
+
+#[test]
+fn later_functions_shadow_natives() {
+    let src = "fn twice(xs) { return len(xs) * 2; } fn len(xs) { return 21; } twice([1]);";
+    assert_eq!(eval(src), Ok(String::from("42")));
+}
+
+#[test]
+fn calling_a_function_before_its_declaration_runs_is_an_error() {
+    let src = "var early = late(); fn late() { return 1; }";
+    assert_eq!(eval(src), Err(String::from("Global variable used before initialisation")));
+}
+
+#[test]
+fn unknown_functions_are_still_reported() {
+    let err = eval("fn a() { return missing(); } a();").unwrap_err();
+    assert!(err.contains("Function `missing` is not defined"), "{}", err);
+}
@@ -0,0 +1,92 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+const CYCLES: &str = r#"
+    class Node {
+        fn init() { self.next = self; }
+    }
+    fn make() {
+        var a = { name: "a" };
+        var b = { name: "b", other: a };
+        a.other = b;
+        var xs = [a, b];
+        push(xs, xs);
+        var node = Node();
+        var holder = [];
+        var get = fn() { return holder; };
+        push(holder, get);
+        return 0;
+    }
+    var i = 0;
+    while (i < 10) { make(); i = i + 1; }
+"#;
+
+#[test]
+fn cycles_are_reclaimed() {
+    let mut engine = Engine::new();
+    engine.set_gc_threshold(1_000_000);
+    engine.eval(CYCLES).unwrap();
+    let before = engine.gc_stats().live_objects;
+    let freed = engine.collect_garbage();
+    let stats = engine.gc_stats();
+    assert!(freed >= 10 * 6, "freed only {} of {} objects", freed, before);
+    assert_eq!(stats.live_objects, before - freed);
+    assert!(stats.live_objects < 10, "{} objects survived", stats.live_objects);
+}
+
+#[test]
+fn gc_builtin_reports_reclaimed_cycles() {
+    let mut engine = Engine::new();
+    engine.set_gc_threshold(1_000_000);
+    let freed = engine.eval(&format!("{} gc();", CYCLES)).unwrap();
+    let freed: usize = freed.to_string().parse().unwrap();
+    assert!(freed >= 10 * 6, "freed only {} objects", freed);
+}
+
+const ROOTS: &str = r#"
+    class Counter {
+        fn init() { self.items = []; }
+        fn add(item) { push(self.items, item); return self; }
+    }
+    var global_list = [[1, 2], { k: [3] }];
+    var global_map = { "nested": { "deep": [4, [5]] } };
+    fn make_adder(n) {
+        var captured = [n];
+        return fn(x) { return x + captured[0]; };
+    }
+    fn churn(n) {
+        var local = [n, [n + 1]];
+        var counter = Counter();
+        var i = 0;
+        while (i < 50) {
+            var garbage = [i, [i], { v: i }];
+            counter.add([i]);
+            i = i + 1;
+        }
+        var add = make_adder(n);
+        return add(local[0] + local[1][0] + len(counter.items));
+    }
+    var results = [];
+    var round = 0;
+    while (round < 20) {
+        push(results, churn(round));
+        round = round + 1;
+    }
+    str([global_list, global_map, results]);
+"#;
+
+#[test]
+fn stack_and_globals_survive_stress_collection() {
+    let mut relaxed = Engine::new();
+    relaxed.set_gc_threshold(1_000_000);
+    let expected = relaxed.eval(ROOTS).unwrap().to_string();
+    assert_eq!(relaxed.gc_stats().collections, 0);
+
+    let mut stressed = Engine::new();
+    stressed.set_gc_threshold(1);
+    let result = stressed.eval(ROOTS).unwrap().to_string();
+    assert_eq!(result, expected);
+    assert!(stressed.gc_stats().collections > 100, "only {} collections", stressed.gc_stats().collections);
+    assert!(expected.starts_with("[[[1, 2], {\"k\": [3]}], {\"nested\": {\"deep\": [4, [5]]}}, [51, "), "{}", expected);
+}
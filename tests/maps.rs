@@ -0,0 +1,96 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{at_every_level, outcome};
+
+/// Evaluates `src` at every optimization level and returns the printed
+/// form of the result or the error message.
+fn eval(src: &str) -> Result<String, String> {
+    return at_every_level(|engine| outcome(engine.eval(src)));
+}
+
+fn ok(val: &str) -> Result<String, String> {
+    return Ok(String::from(val));
+}
+
+#[test]
+fn literals_take_any_hashable_key() {
+    let src = r#"var m = { "b": 1, a: 2, 3: "three", true: "yes", 'c': 'd' }; str(m);"#;
+    assert_eq!(eval(src), ok("{\"b\": 1, \"a\": 2, 3: \"three\", true: \"yes\", 'c': 'd'}"));
+    assert_eq!(eval("var m = {}; str(m) + str(len(m));"), ok("{}0"));
+    assert_eq!(eval("var m = { [1]: 1 };"), Err(String::from("Map key must be an int, bool, char or str, got list")));
+}
+
+#[test]
+fn indexing_reads_entries() {
+    let src = r#"var m = { name: "cherry", 1: "one", 'x': true }; str([m["name"], m.name, m[1], m['x']]);"#;
+    assert_eq!(eval(src), ok("[\"cherry\", \"cherry\", \"one\", true]"));
+    assert_eq!(eval("var m = { 1: 1 }; m[1.0];"), Err(String::from("Map key must be an int, bool, char or str, got float")));
+}
+
+#[test]
+fn missing_keys_are_errors_unless_a_default_is_given() {
+    assert_eq!(eval("var m = { a: 1 }; m[\"b\"];"), Err(String::from("Key \"b\" not found in map")));
+    assert_eq!(eval("var m = { a: 1 }; m[2];"), Err(String::from("Key 2 not found in map")));
+    assert_eq!(eval("var m = { a: 1 }; remove(m, \"b\");"), Err(String::from("Key \"b\" not found in map")));
+    assert_eq!(eval("var m = { a: 1 }; str([get(m, \"b\", 0), get(m, \"a\", 0), has(m, \"a\"), has(m, \"b\")]);"),
+        ok("[0, 1, true, false]"));
+}
+
+#[test]
+fn insert_and_remove_update_entries() {
+    let src = r#"
+        var m = { a: 1 };
+        m["b"] = 2;
+        m.c = 3;
+        m["a"] = 10;
+        var removed = remove(m, "b");
+        str([removed, len(m), has(m, "b")]) + " " + str(m);
+    "#;
+    assert_eq!(eval(src), ok("[2, 2, false] {\"a\": 10, \"c\": 3}"));
+    assert_eq!(eval("var m = {}; m[[1]] = 1;"), Err(String::from("Map key must be an int, bool, char or str, got list")));
+}
+
+#[test]
+fn iteration_follows_insertion_order() {
+    let src = r#"
+        var m = { "z": 1, "a": 2, "m": 3 };
+        m["b"] = 4;
+        m["z"] = 5;
+        var seen = [];
+        for (key in m) { push(seen, key); }
+        str(seen) + " " + str(keys(m));
+    "#;
+    assert_eq!(eval(src), ok("[\"z\", \"a\", \"m\", \"b\"] [\"z\", \"a\", \"m\", \"b\"]"));
+}
+
+#[test]
+fn reinserted_keys_move_to_the_end() {
+    let src = r#"
+        var m = { a: 1, b: 2, c: 3 };
+        remove(m, "a");
+        m["a"] = 4;
+        str(keys(m));
+    "#;
+    assert_eq!(eval(src), ok("[\"b\", \"c\", \"a\"]"));
+}
+
+#[test]
+fn order_survives_removing_most_entries() {
+    let src = r#"
+        var m = {};
+        var i = 0;
+        while (i < 20) { m[i] = i * i; i = i + 1; }
+        i = 0;
+        while (i < 20) { if (i % 5 != 0) { remove(m, i); } i = i + 1; }
+        m[3] = 9;
+        str(m);
+    "#;
+    assert_eq!(eval(src), ok("{0: 0, 5: 25, 10: 100, 15: 225, 3: 9}"));
+}
+
+#[test]
+fn maps_compare_by_contents() {
+    assert_eq!(eval("str([{ x: [1] } == { x: [1] }, { x: 1 } == { x: 2 }, { x: 1 } == { y: 1 }]);"), ok("[true, false, false]"));
+}
@@ -0,0 +1,61 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::at_every_level;
+
+/// Evaluates `src` at every optimization level, checks that they agree
+/// and returns the printed form of the result.
+fn eval(src: &str) -> String {
+    return at_every_level(|engine| engine.eval(src).unwrap().to_string());
+}
+
+const CLASSIFY: &str = r#"
+    fn classify(n) {
+        return match (n) {
+            -1 => "minus one",
+            0 => "zero",
+            1 | 2 => "small",
+            _ => "other"
+        };
+    }
+"#;
+
+#[test]
+fn jump_table_dispatches_dense_int_arms() {
+    let src = format!("{} str([classify(-1), classify(0), classify(2), classify(3), classify(-2)]);", CLASSIFY);
+    assert_eq!(eval(&src), "[\"minus one\", \"zero\", \"small\", \"other\", \"other\"]");
+}
+
+#[test]
+fn jump_table_handles_values_far_from_its_minimum() {
+    let src = format!("{} classify(9223372036854775807);", CLASSIFY);
+    assert_eq!(eval(&src), "other");
+    let src = format!("{} classify(-9223372036854775807 - 1);", CLASSIFY);
+    assert_eq!(eval(&src), "other");
+    let src = r#"
+        fn f(n) { return match (n) { 5 => "five", 6 => "six", 7 => "seven", _ => "other" }; }
+        f(-9223372036854775807 - 1);
+    "#;
+    assert_eq!(eval(src), "other");
+}
+
+#[test]
+fn jump_table_matches_integral_floats_like_linear_arms() {
+    let table = r#"fn f(n) { return match (n) { 0 => "a", 1 => "b", 2 => "c", 3 => "d", _ => "other" }; }"#;
+    let linear = r#"fn f(n) { return match (n) { 2 => "c", _ => "other" }; }"#;
+    for src in [table, linear] {
+        assert_eq!(eval(&format!("{} str([f(2.0), f(2.5), f(-0.0 + 2), f(0.0 / 0.0)]);", src)), "[\"c\", \"other\", \"c\", \"other\"]");
+    }
+    assert_eq!(eval(&format!("{} f(-0.0);", table)), "a");
+    assert_eq!(eval(&format!("{} f(100000000000000000000000.0);", table)), "other");
+}
+
+#[test]
+fn jump_table_matches_big_ints_that_fit() {
+    let table = r#"fn f(n) { return match (n) { 0 => "a", 1 => "b", 2 => "c", 3 => "d", _ => "other" }; }"#;
+    let linear = r#"fn f(n) { return match (n) { 2 => "c", _ => "other" }; }"#;
+    for src in [table, linear] {
+        assert_eq!(eval(&format!("{} str([f(2n), f(bigint(2)), f(100000000000000000000n)]);", src)), "[\"c\", \"c\", \"other\"]");
+    }
+}
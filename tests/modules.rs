@@ -0,0 +1,59 @@
+#![allow(clippy::needless_return)]
+
+use std::{fs, path::PathBuf};
+
+use cherry_script::{Engine, Error};
+
+mod common;
+
+use common::at_every_level;
+
+fn fixture(name: &str) -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/modules").join(name);
+}
+
+/// Compiles and runs the fixture `name` at every optimization level, and
+/// returns the printed form of the result or the error.
+fn run(name: &str) -> Result<String, String> {
+    let path = fixture(name);
+    let src = fs::read_to_string(&path).unwrap();
+    return at_every_level(|engine| {
+        let result = engine.compile_file(&src, &path)
+            .map_err(Error::from)
+            .and_then(|program| engine.run(&program).map_err(Error::from));
+        result.map(|val| val.to_string()).map_err(|err| err.to_string())
+    });
+}
+
+#[test]
+fn modules_are_initialised_once_before_their_first_importer() {
+    assert_eq!(run("main.sd"), Ok(String::from("[\"log\", \"a\", \"b after a\", \"main\"]")));
+}
+
+/// The file names in the import cycle reported by `err`.
+fn cycle(err: &str) -> Vec<&str> {
+    let (_, cycle) = err.split_once("Import cycle: ").unwrap_or_else(|| panic!("not a cycle: {}", err));
+    return cycle.split(" -> ").map(|file| file.rsplit(['/', '\\']).next().unwrap()).collect();
+}
+
+#[test]
+fn import_cycles_are_reported() {
+    let err = run("cycle/first.sd").unwrap_err();
+    assert!(err.contains("third.sd at 1:1"), "{}", err);
+    assert_eq!(cycle(&err), ["first.sd", "second.sd", "third.sd", "first.sd"]);
+}
+
+#[test]
+fn a_module_importing_itself_is_a_cycle() {
+    let err = run("cycle/own.sd").unwrap_err();
+    assert_eq!(cycle(&err), ["own.sd", "own.sd"]);
+}
+
+#[test]
+fn modules_are_found_through_search_paths() {
+    let src = "import \"shapes.sd\" as shapes; shapes.area(4);";
+    let mut engine = Engine::new();
+    assert!(engine.eval(src).unwrap_err().to_string().contains("Module `shapes.sd` not found"));
+    engine.add_search_path(fixture("search"));
+    assert_eq!(engine.eval(src).unwrap().to_string(), "16");
+}
@@ -0,0 +1,70 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{at_every_level, safe_call};
+
+/// Evaluates `src` at every optimization level with `safe_call` registered
+/// and returns the printed form of the result.
+fn eval(src: &str) -> String {
+    return at_every_level(|engine| {
+        engine.register_fn("safe_call", 1, safe_call);
+        engine.eval(src).unwrap().to_string()
+    });
+}
+
+#[test]
+fn native_catches_an_error_thrown_by_its_callee() {
+    let src = r#"
+        fn inner(n) { var unused = [n, n]; throw "boom " + str(n); }
+        fn run() {
+            var a = 1;
+            var b = 2;
+            var message = safe_call(fn() { var x = 10; return inner(x); });
+            var total = 0;
+            for (i in [a, b, 3]) { total = total + i; }
+            return message + " " + str(total) + " " + str(a) + str(b);
+        }
+        run();
+    "#;
+    assert_eq!(eval(src), "Uncaught exception: boom 10 6 12");
+}
+
+#[test]
+fn native_catches_a_runtime_error_inside_try() {
+    let src = r#"
+        var log = [];
+        fn risky() { try { return 1 / 0; } finally { push(log, "finally"); } }
+        fn run() {
+            var before = "kept";
+            var message = safe_call(risky);
+            try { throw "later"; } catch (e) { push(log, e); }
+            return [before, message, len(log)];
+        }
+        var result = run();
+        str(result);
+    "#;
+    assert_eq!(eval(src), "[\"kept\", \"Division by zero\", 2]");
+}
+
+#[test]
+fn native_survives_a_callee_with_bad_arguments() {
+    let src = r#"
+        fn two(a, b) { return a + b; }
+        var x = 5;
+        var message = safe_call(two);
+        message + " " + str(x);
+    "#;
+    assert_eq!(eval(src), "`two` expects 2 arguments, got 0 5");
+}
+
+#[test]
+fn handled_throw_does_not_leak_into_later_errors() {
+    let src = r#"
+        var handled = safe_call(fn() { throw "handled"; });
+        var message = "";
+        try { [1][5]; } catch (e) { message = e.message; }
+        handled + ", then " + message;
+    "#;
+    assert_eq!(eval(src), "Uncaught exception: handled, then Index 5 is out of bounds for length 1");
+}
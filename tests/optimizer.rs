@@ -0,0 +1,92 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+mod common;
+
+use common::{at_every_level, outcome};
+
+/// The instructions of every chunk of `src` compiled at `level`, without
+/// their offsets and locations.
+fn instructions(src: &str, level: u8) -> Vec<String> {
+    let mut engine = Engine::new();
+    engine.set_opt_level(level);
+    let listing = engine.compile(src).unwrap().disassemble();
+    return listing.lines()
+        .filter(|line| line.starts_with(|c: char| c.is_ascii_digit()))
+        .filter_map(|line| line[4..].trim_start().split_once("  "))
+        .map(|(_, instr)| instr.to_string())
+        .collect();
+}
+
+/// Evaluates `src` at every optimization level and returns the printed
+/// form of the result or the error message.
+fn eval(src: &str) -> Result<String, String> {
+    return at_every_level(|engine| outcome(engine.eval(src)));
+}
+
+#[test]
+fn literal_operators_are_folded() {
+    assert_eq!(instructions("1 + 2 * 3;", 1), ["Push 0 (Int(7))"]);
+    assert_eq!(instructions("-(5) + 7 // 2 - 7 % 3;", 1), ["Push 0 (Int(-3))"]);
+    assert_eq!(instructions("\"a\" + \"b\";", 1), ["Push 0 (Str(\"ab\"))"]);
+    assert_eq!(instructions("1 < 2 && !false;", 1), ["Push 0 (Bool(true))"]);
+    assert_eq!(instructions("1.5 * 2.0;", 1), ["Push 0 (Float(3.0))"]);
+    assert_eq!(instructions("1 + 2 * 3;", 0).len(), 5);
+    assert_eq!(eval("str([1 + 2 * 3, -(5) + 7 // 2 - 7 % 3, 1.5 * 2.0]) + \"a\" + \"b\";"), Ok(String::from("[7, -3, 3.0]ab")));
+}
+
+#[test]
+fn const_bindings_are_propagated() {
+    let src = "const K = 4; fn f() { return K * 2 + 1; } f();";
+    let folded = instructions(src, 1);
+    assert!(folded.contains(&String::from("Push 0 (Int(9))")), "{:?}", folded);
+    assert!(!folded.iter().any(|instr| instr.starts_with("LoadGlob 1") || instr.starts_with("Mul")), "{:?}", folded);
+    assert_eq!(eval(src), Ok(String::from("9")));
+}
+
+#[test]
+fn variables_and_shadowed_consts_are_not_propagated() {
+    let src = "var v = 4; const K = 1; fn f(K) { return K + v; } f(2);";
+    let folded = instructions(src, 1);
+    assert!(folded.iter().any(|instr| instr == "Add"), "{:?}", folded);
+    assert_eq!(eval(src), Ok(String::from("6")));
+}
+
+#[test]
+fn dead_code_is_eliminated() {
+    let src = r#"
+        fn f() { return "kept"; print "after return"; }
+        if (false) { print "dead branch"; } else { print "live branch"; }
+        while (false) { print "dead loop"; }
+        f();
+    "#;
+    let listing = instructions(src, 1).join("\n");
+    for dead in ["after return", "dead branch", "dead loop", "Jmp"] {
+        assert!(!listing.contains(dead), "{} in\n{}", dead, listing);
+    }
+    assert!(listing.contains("live branch"), "{}", listing);
+    let listing = instructions(src, 0).join("\n");
+    assert!(listing.contains("dead branch") && listing.contains("dead loop"), "{}", listing);
+    assert_eq!(eval(src), Ok(String::from("kept")));
+}
+
+#[test]
+fn overflowing_expressions_are_not_folded() {
+    for (src, op) in [("9223372036854775807 + 1;", "Add"), ("9223372036854775807 * 2;", "Mul"), ("-(-9223372036854775807 - 1);", "Neg")] {
+        let folded = instructions(src, 1);
+        assert!(folded.iter().any(|instr| instr.starts_with(op)), "{:?}", folded);
+    }
+    assert_eq!(eval("9223372036854775807 + 1;"), Err(String::from("Integer overflow in `+`")));
+    assert_eq!(eval("9223372036854775807 * 2;"), Err(String::from("Integer overflow in `*`")));
+    assert_eq!(eval("-(-9223372036854775807 - 1);"), Err(String::from("Integer overflow in `-`")));
+}
+
+#[test]
+fn divisions_by_zero_are_not_folded() {
+    for src in ["1 / 0;", "1 % 0;", "1 // 0;"] {
+        assert_eq!(instructions(src, 1).len(), 3, "{}", src);
+        assert_eq!(eval(src), Err(String::from("Division by zero")));
+    }
+    assert_eq!(instructions("1.0 / 0.0;", 1), ["Push 0 (Float(inf))"]);
+}
@@ -0,0 +1,90 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+mod common;
+
+use common::outcome;
+
+/// Runs `src` at -O0 and -O2 and returns the printed form of the result,
+/// after checking that both levels agree and that the optimized listing
+/// uses `superinstruction`.
+fn eval_fused(src: &str, superinstruction: &str) -> Result<String, String> {
+    let mut results = Vec::new();
+    for level in [0, 2] {
+        let mut engine = Engine::new();
+        engine.set_opt_level(level);
+        let listing = engine.compile(src).unwrap().disassemble();
+        assert_eq!(listing.contains(superinstruction), level == 2, "{} at -O{}:\n{}", superinstruction, level, listing);
+        results.push(outcome(engine.eval(src)));
+    }
+    assert_eq!(results[0], results[1], "-O0 and -O2 disagree on {:?}", src);
+    return results.remove(0);
+}
+
+#[test]
+fn add_const_matches_unoptimized_additions() {
+    let src = "fn f(x) { return x + 5; } str([f(1), f(2.5), f(-5)]);";
+    assert_eq!(eval_fused(src, "AddConst"), Ok(String::from("[6, 7.5, 0]")));
+    let src = "fn f(s) { return s + \"!\"; } f(\"hi\");";
+    assert_eq!(eval_fused(src, "AddConst"), Ok(String::from("hi!")));
+    let src = "fn f(s) { return s + 1; } f(\"hi\");";
+    assert_eq!(eval_fused(src, "AddConst"), Err(String::from("Unsupported operand types for `+`: str and int")));
+}
+
+#[test]
+fn inc_global_matches_unoptimized_additions() {
+    let src = "var count = 0; fn bump() { count = count + 3; } var i = 0; while (i < 10) { bump(); i = i + 1; } count;";
+    assert_eq!(eval_fused(src, "IncGlobal"), Ok(String::from("30")));
+    let src = "var total = 9223372036854775800; fn bump() { total = total + 10; } bump();";
+    assert_eq!(eval_fused(src, "IncGlobal"), Err(String::from("Integer overflow in `+`")));
+}
+
+#[test]
+fn fused_comparisons_branch_like_unoptimized_ones() {
+    let src = r#"
+        fn classify(a, b) {
+            var out = "";
+            if (a < b) { out = out + "<"; }
+            if (a <= b) { out = out + "<="; }
+            if (a > b) { out = out + ">"; }
+            if (a >= b) { out = out + ">="; }
+            if (a == b) { out = out + "=="; }
+            if (a != b) { out = out + "!="; }
+            return out;
+        }
+        str([classify(1, 2), classify(2, 2), classify(3, 2), classify(1.5, 1)]);
+    "#;
+    assert_eq!(eval_fused(src, "JmpIfFalseCmp"), Ok(String::from("[\"<<=!=\", \"<=>===\", \">>=!=\", \">>=!=\"]")));
+}
+
+#[test]
+fn fused_loops_keep_their_jump_targets() {
+    let src = r#"
+        fn run(n) {
+            var total = 0;
+            var i = 0;
+            while (i < n) {
+                var j = 0;
+                while (j <= i) {
+                    if (j != 2) { total = total + j; } else { total = total + 100; }
+                    j = j + 1;
+                }
+                i = i + 1;
+            }
+            return total;
+        }
+        str([run(0), run(1), run(5)]);
+    "#;
+    assert_eq!(eval_fused(src, "JmpIfFalseCmp"), Ok(String::from("[0, 0, 314]")));
+}
+
+#[test]
+fn sequences_entered_by_a_jump_are_not_fused() {
+    let src = r#"
+        fn f(a, b, c) { if (a || b == c) { return "then"; } return "else"; }
+        fn g(x) { return x + 1; }
+        str([f(true, 1, 2), f(false, 1, 1), f(false, 1, 2), g(1)]);
+    "#;
+    assert_eq!(eval_fused(src, "AddConst"), Ok(String::from("[\"then\", \"then\", \"else\", 2]")));
+}
@@ -0,0 +1,51 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::{Engine, RuntimeError, VM, Value};
+
+fn run(engine: &mut Engine, src: &str) -> Result<String, String> {
+    let program = engine.compile_registers(src).map_err(|err| err.to_string())?;
+    return engine.run_registers(&program).map(|val| val.to_string()).map_err(|err| err.message);
+}
+
+#[test]
+fn collection_keeps_objects_held_in_registers() {
+    let src = r#"
+        fn f() {
+            var l = [1, 2, 3];
+            var m = {"a": [4]};
+            gc();
+            return l[0] + m["a"][0];
+        }
+        f() + f();
+    "#;
+    assert_eq!(run(&mut Engine::new(), src), Ok(String::from("10")));
+}
+
+#[test]
+fn collection_frees_unreachable_objects() {
+    let src = r#"
+        fn garbage() { var l = [[1], [2]]; return 0; }
+        garbage();
+        var kept = [3];
+        gc();
+    "#;
+    assert_eq!(run(&mut Engine::new(), src), Ok(String::from("3")));
+}
+
+fn one(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return Ok(args[0].clone());
+}
+
+fn two(_vm: &mut VM, args: &[Value]) -> Result<Value, RuntimeError> {
+    return Ok(args[1].clone());
+}
+
+#[test]
+fn native_calls_check_arity_at_runtime() {
+    let mut engine = Engine::new();
+    engine.register_fn("pick", 1, one);
+    let program = engine.compile_registers("pick(5);").unwrap();
+    engine.register_fn("pick", 2, two);
+    let err = engine.run_registers(&program).unwrap_err();
+    assert_eq!(err.message, "`pick` expects 2 arguments, got 1");
+}
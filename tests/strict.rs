@@ -0,0 +1,91 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::{Engine, Error};
+
+/// Compiles `src` in strict mode without optimizations and returns its
+/// listing or the message of the type error.
+fn check(src: &str) -> Result<String, String> {
+    let mut engine = Engine::new();
+    engine.set_strict(true);
+    engine.set_opt_level(0);
+    return engine.compile(src).map(|program| program.disassemble()).map_err(|err| err.message);
+}
+
+/// Runs `src` in strict mode and returns the printed form of the result.
+fn eval(src: &str) -> String {
+    let mut engine = Engine::new();
+    engine.set_strict(true);
+    return match engine.eval(src) {
+        Ok(val) => val.to_string(),
+        Err(Error::Runtime(err)) => panic!("{}", err),
+        Err(err) => panic!("{}", err)
+    };
+}
+
+#[test]
+fn list_methods_check_their_items() {
+    assert_eq!(eval("var l = [1, 2]; l.push(3); l.pop() + 10;"), "13");
+    assert_eq!(check("var l = [1, 2]; l.push(\"x\");"), Err(String::from("Type mismatch: expected int, found str")));
+    assert_eq!(check("var l = [1, 2]; var s = l.pop() + \"!\";"), Err(String::from("Cannot apply `+` to int and str")));
+    assert_eq!(check("var l = [1]; push(l, 2.5);"), Err(String::from("Type mismatch: expected int, found float")));
+}
+
+#[test]
+fn map_methods_check_keys_and_values() {
+    let src = r#"
+        var m = { "a": 1 };
+        var found = m.has("a");
+        var fallback = m.get("b", 2) + m.remove("a");
+        str(found) + " " + str(fallback) + " " + str(len(m.keys()));
+    "#;
+    assert_eq!(eval(src), "true 3 0");
+    assert_eq!(check("var m = { \"a\": 1 }; m.has(1);"), Err(String::from("Type mismatch: expected str, found int")));
+    assert_eq!(check("var m = { \"a\": 1 }; m.get(\"a\", \"none\");"), Err(String::from("Type mismatch: expected int, found str")));
+    assert_eq!(check("var l = [1]; l.keys();").unwrap_err(), "Type mismatch: expected map<t1, t2>, found list<int>");
+}
+
+#[test]
+fn string_methods_check_their_arguments() {
+    assert_eq!(eval("var s = \" a,b \"; str(s.trim().split(\",\"));"), "[\"a\", \"b\"]");
+    assert_eq!(check("var s = \"abc\"; s.substr(\"0\", 1);"), Err(String::from("Type mismatch: expected int, found str")));
+    assert_eq!(check("var n = 1; n.to_upper();"), Err(String::from("Type mismatch: expected str, found int")));
+    assert_eq!(check("var s = \"abc\"; s.trim(1);"), Err(String::from("`trim` expects 1 arguments, got 2")));
+}
+
+#[test]
+fn class_methods_are_not_checked_against_builtins() {
+    let src = r#"
+        class Stack {
+            fn init() { self.items = []; }
+            fn push(item, times) { var i = 0; while (i < times) { push(self.items, item); i = i + 1; } }
+        }
+        var stack = Stack();
+        stack.push("x", 2);
+        len(stack.items);
+    "#;
+    assert_eq!(eval(src), "2");
+}
+
+#[test]
+fn additions_of_known_types_are_specialised() {
+    let listing = check("fn f() { var a = 1; var b = 2; return a + b; } f();").unwrap();
+    assert!(listing.contains("AddInt"), "{}", listing);
+    let listing = check("fn f() { var a = 1.5; var b = 2.0; return a + b; } f();").unwrap();
+    assert!(listing.contains("AddFloat"), "{}", listing);
+    let listing = check("var l = [1, 2]; fn f() { return l.pop() + 1; } f();").unwrap();
+    assert!(listing.contains("AddInt"), "{}", listing);
+}
+
+#[test]
+fn additions_of_unknown_types_fall_back_to_add() {
+    let src = r#"
+        fn add(a, b) { return a + b; }
+        class Counter { fn init() { self.count = 1; } }
+        var counter = Counter();
+        counter.count + counter.count;
+    "#;
+    let listing = check(src).unwrap();
+    assert!(!listing.contains("AddInt") && !listing.contains("AddFloat"), "{}", listing);
+    assert_eq!(listing.matches("  Add\n").count(), 2, "{}", listing);
+    assert_eq!(eval(src), "2");
+}
@@ -0,0 +1,42 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+mod common;
+
+use common::outcome;
+
+/// Evaluates `src` and returns the printed form of the result or the
+/// error message.
+fn eval(src: &str) -> Result<String, String> {
+    return outcome(Engine::new().eval(src));
+}
+
+#[test]
+fn substr_takes_a_character_range() {
+    assert_eq!(eval("substr(\"héllo\", 1, 3);"), Ok(String::from("éll")));
+    assert_eq!(eval("substr(\"abc\", 3, 0);"), Ok(String::new()));
+}
+
+#[test]
+fn substr_rejects_ranges_out_of_bounds() {
+    assert_eq!(eval("substr(\"abc\", 2, 2);"), Err(String::from("`substr` range of length 2 at 2 is out of bounds for a str of length 3")));
+    assert_eq!(eval("substr(\"abc\", -1, 1);"), Err(String::from("`substr` range of length 1 at -1 is out of bounds for a str of length 3")));
+    assert_eq!(eval("substr(\"abc\", 1, 9223372036854775807);"),
+        Err(String::from("`substr` range of length 9223372036854775807 at 1 is out of bounds for a str of length 3")));
+}
+
+#[test]
+fn format_pads_to_a_width() {
+    assert_eq!(eval("format(\"[{:>5}]\", 42);"), Ok(String::from("[   42]")));
+    assert_eq!(eval("format(\"[{:.2}]\", 1.5);"), Ok(String::from("[1.50]")));
+}
+
+#[test]
+fn format_rejects_widths_over_the_maximum() {
+    assert_eq!(eval("format(\"{:99999999999999}\", 1);"),
+        Err(String::from("`format` width 99999999999999 exceeds the maximum of 65536")));
+    assert_eq!(eval("format(\"{:.99999999999999999999999}\", 1.0);"),
+        Err(String::from("`format` precision 99999999999999999999999 exceeds the maximum of 65536")));
+    assert_eq!(eval("format(\"{:5x}\", 1);"), Err(String::from("Invalid format spec `{:5x}`")));
+}
@@ -0,0 +1,80 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::Engine;
+
+mod common;
+
+use common::{at_every_level, outcome};
+
+/// Evaluates `src` at every optimization level and returns the printed
+/// form of the result or the error message.
+fn eval(src: &str) -> Result<String, String> {
+    return at_every_level(|engine| outcome(engine.eval(src)));
+}
+
+fn ok(val: &str) -> Result<String, String> {
+    return Ok(String::from(val));
+}
+
+fn error(message: &str) -> Result<String, String> {
+    return Err(String::from(message));
+}
+
+/// Declares `Point` and `Pair`, which share the field `x`, and `Size`,
+/// whose fields are unique to it.
+const SHAPES: &str = r#"
+    struct Point { x, y }
+    struct Pair { x, other }
+    struct Size { w, h }
+"#;
+
+#[test]
+fn fields_are_read_and_assigned() {
+    let src = format!("{} var p = Point {{ y: 2, x: 1 }}; p.x = p.x + 10; p.y = [p.y]; str(p);", SHAPES);
+    assert_eq!(eval(&src), ok("Point { x: 11, y: [2] }"));
+    let src = format!("{} var s = Size {{ w: 3, h: 4 }}; s.w = s.w * s.h; str(s);", SHAPES);
+    assert_eq!(eval(&src), ok("Size { w: 12, h: 4 }"));
+}
+
+#[test]
+fn unique_fields_are_accessed_by_offset() {
+    let listing = Engine::new().compile(&format!("{} fn w(s) {{ s.w = s.h; return s.w; }} fn x(p) {{ return p.x; }}", SHAPES)).unwrap().disassemble();
+    assert!(listing.contains("GetFieldAt") && listing.contains("SetFieldAt"), "{}", listing);
+    assert!(listing.lines().any(|line| line.contains("GetField ") && line.contains("\"x\"")), "{}", listing);
+}
+
+#[test]
+fn offset_access_falls_back_to_lookup_by_name() {
+    let src = format!(r#"{}
+        class Box {{ fn init() {{ self.w = "box"; }} }}
+        fn w_of(v) {{ return v.w; }}
+        fn set_w(v) {{ v.w = "set"; return v; }}
+        var values = [Size {{ w: "size", h: 0 }}, {{ w: "map" }}, Box()];
+        var read = [];
+        var written = [];
+        for (v in values) {{ push(read, w_of(v)); push(written, set_w(v).w); }}
+        str(read) + " " + str(written);
+    "#, SHAPES);
+    assert_eq!(eval(&src), ok("[\"size\", \"map\", \"box\"] [\"set\", \"set\", \"set\"]"));
+}
+
+#[test]
+fn unknown_fields_are_errors() {
+    assert_eq!(eval(&format!("{} fn w_of(v) {{ return v.w; }} w_of(Point {{ x: 1, y: 2 }});", SHAPES)), error("Struct `Point` has no field `w`"));
+    assert_eq!(eval(&format!("{} fn set_w(v) {{ v.w = 1; }} set_w(Pair {{ x: 1, other: 2 }});", SHAPES)), error("Struct `Pair` has no field `w`"));
+    assert_eq!(eval(&format!("{} var p = Point {{ x: 1, y: 2 }}; p.z;", SHAPES)), error("Struct `Point` has no field `z`"));
+    assert_eq!(eval("fn x_of(v) { return v.x; } x_of([1]);"), error("Value of type list has no field `x`"));
+}
+
+#[test]
+fn literals_must_name_every_field_once() {
+    assert_eq!(eval("struct Point { x, y } Point { x: 1 };"), error("Error at 1:23: Missing field `y` in `Point` literal"));
+    assert_eq!(eval("struct Point { x, y } Point { x: 1, y: 2, z: 3 };"), error("Error at 1:46: Struct `Point` has no field `z`"));
+    assert_eq!(eval("struct Point { x, y } Point { x: 1, x: 2, y: 3 };"), error("Error at 1:37: Field `x` is specified more than once"));
+}
+
+#[test]
+fn structs_compare_by_contents() {
+    let src = format!("{} str([Point {{ x: 1, y: 2 }} == Point {{ x: 1, y: 2 }}, Point {{ x: 1, y: 2 }} == Point {{ x: 2, y: 1 }}]);", SHAPES);
+    assert_eq!(eval(&src), ok("[true, false]"));
+}
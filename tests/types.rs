@@ -0,0 +1,82 @@
+#![allow(clippy::needless_return)]
+
+use cherry_script::{Engine, Error};
+
+mod common;
+
+use common::at_every_level;
+
+/// Runs `src` at every optimization level and returns the printed form of
+/// its result.
+fn eval(src: &str) -> String {
+    return at_every_level(|engine| engine.eval(src).unwrap().to_string());
+}
+
+/// The message of the compile error `src` is rejected with.
+fn reject(src: &str) -> String {
+    return match Engine::new().eval(src) {
+        Err(Error::Compile(err)) => err.message,
+        result => panic!("{:?} was not rejected: {:?}", src, result)
+    };
+}
+
+#[test]
+fn mismatched_annotations_are_rejected() {
+    assert_eq!(reject("var n: int = \"one\";"), "Type mismatch: expected int, found str");
+    assert_eq!(reject("var n: int = 1; n = 2.5;"), "Type mismatch: expected int, found float");
+    assert_eq!(reject("const flag: bool = [1];"), "Type mismatch: expected bool, found list");
+    assert_eq!(reject("var n: widget = 1;"), "Unknown type `widget`");
+}
+
+#[test]
+fn wrong_argument_types_are_rejected() {
+    let src = "fn scale(v: float, by: int) -> float { return v * by; } scale(1.5, \"2\");";
+    assert_eq!(reject(src), "Type mismatch: expected int, found str");
+    assert_eq!(reject("fn one(x: int) -> int { return x; } one(1, 2);"), "`one` expects 1 arguments, got 2");
+    let src = "struct Point { x, y } struct Size { w, h } fn area(p: Point) -> int { return 0; } area(Size { w: 1, h: 2 });";
+    assert_eq!(reject(src), "Type mismatch: expected Point, found Size");
+}
+
+#[test]
+fn bad_returns_are_rejected() {
+    assert_eq!(reject("fn name() -> str { return 1; }"), "Type mismatch: expected str, found int");
+    assert_eq!(reject("fn nothing() -> int { return; }"), "Type mismatch: expected int, found nil");
+    assert_eq!(reject("fn sign(x: int) -> int { if (x > 0) { return 1; } }"),
+        "Function `sign` does not return a value of type int on every path");
+}
+
+#[test]
+fn unannotated_code_is_not_checked() {
+    let src = r#"
+        fn add(a, b) { return a + b; }
+        var x = 1;
+        x = "now a str";
+        str(add(1, 2)) + add("a", "b") + x;
+    "#;
+    assert_eq!(eval(src), "3abnow a str");
+}
+
+#[test]
+fn annotated_code_calls_unannotated_code() {
+    let src = r#"
+        struct Point { x, y }
+        fn untyped(value) { return value; }
+        fn norm_sq(p: Point) -> int { return untyped(p.x) * p.x + p.y * p.y; }
+        fn label(n: int) -> str { return "n=" + str(n); }
+        var n: int = untyped(3);
+        var twice: function = fn(x: int) -> int { return x * 2; };
+        label(norm_sq(Point { x: n, y: 4 }) + twice(untyped(1)));
+    "#;
+    assert_eq!(eval(src), "n=27");
+}
+
+#[test]
+fn unannotated_values_are_accepted_where_types_are_expected() {
+    let src = r#"
+        fn typed(n: int) -> int { return n + 1; }
+        fn anything() { return 41; }
+        var result: int = typed(anything());
+        result;
+    "#;
+    assert_eq!(eval(src), "42");
+}